@@ -0,0 +1,255 @@
+use cgmath::Vector2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{history::Command, Game},
+    juno::directions::manhattan_distance,
+    sector::{combat::max_damage, threat::ThreatMap, Sector, Team, Unit, UnitId},
+};
+
+#[cfg(test)]
+pub mod tests;
+
+/// How much each consideration counts towards the score of a possible action. Threat and
+/// objective distance count against an action, and the rest count in its favour.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct UtilityWeights {
+    /// Per point of damage that enemies could deal to the unit where it ends up
+    pub threat: f32,
+    /// Per point of damage dealt by attacking, not counting any overkill
+    pub damage: f32,
+    /// Per point of defense given by the terrain the unit ends up on
+    pub defense: f32,
+    /// Per tile between where the unit ends up and its objective
+    pub objective: f32,
+}
+
+impl Default for UtilityWeights {
+    fn default() -> Self {
+        Self {
+            threat: 0.25,
+            damage: 2.,
+            defense: 1.,
+            objective: 0.5,
+        }
+    }
+}
+
+/// What a single unit intends to do this phase: move (possibly staying put), then maybe attack
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub unit: UnitId,
+    pub destination: Vector2<u32>,
+    pub target: Option<UnitId>,
+    pub score: f32,
+}
+
+/// Computer-controlled player for one team. It only knows about enemies that its team can see,
+/// and acts through the same commands as a human player. Ties between equally good actions are
+/// broken with its own seeded generator, so the same seed always plays the same way.
+pub struct Ai {
+    team: Team,
+    weights: UtilityWeights,
+    // Tile that units head towards. Without one, they head for the nearest enemy they know of.
+    objective: Option<Vector2<u32>>,
+    rng: ChaCha8Rng,
+}
+
+impl Ai {
+    pub fn new(team: Team, weights: UtilityWeights, seed: u64) -> Self {
+        Self {
+            team,
+            weights,
+            objective: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn team(&self) -> Team {
+        self.team
+    }
+
+    pub fn weights(&self) -> &UtilityWeights {
+        &self.weights
+    }
+
+    pub fn objective(&self) -> Option<Vector2<u32>> {
+        self.objective
+    }
+
+    pub fn set_objective(&mut self, objective: Option<Vector2<u32>>) {
+        self.objective = objective;
+    }
+
+    /// Play out the team's whole phase, one unit at a time in order of ID, then recruit what it
+    /// can afford, and then end the turn.
+    /// Returns every command that was applied, or nothing if it isn't this team's phase.
+    pub fn take_turn(&mut self, game: &mut Game) -> Vec<Command> {
+        if game.turns().active_team() != self.team {
+            return Vec::new();
+        }
+        let units = game
+            .sector()
+            .units()
+            .filter(|u| u.team() == self.team)
+            .map(|u| u.id())
+            .collect::<Vec<_>>();
+        // Each unit plans against the state left by the ones before it
+        let mut commands = Vec::new();
+        for unit in units {
+            let Some(plan) = self.plan(game.sector(), unit) else {
+                continue;
+            };
+            if game.sector().unit(unit).map(|u| u.pos()) != Some(plan.destination) {
+                let command = Command::Move {
+                    unit,
+                    to: plan.destination,
+                };
+                if game.apply(command.clone()).is_err() {
+                    continue;
+                }
+                commands.push(command);
+            }
+            // Moving can run into a hidden enemy, so the attack might not be possible anymore
+            if let Some(target) = plan.target {
+                let command = Command::Attack {
+                    attacker: unit,
+                    target,
+                };
+                if game.apply(command.clone()).is_ok() {
+                    commands.push(command);
+                }
+            }
+        }
+        commands.extend(self.recruit(game));
+        if game.apply(Command::EndTurn).is_ok() {
+            commands.push(Command::EndTurn);
+        }
+        commands
+    }
+
+    /// Spend the team's resources from each of its structures in turn, on the most expensive unit
+    /// it can afford each time, put on the first tile next to the structure that will take it
+    fn recruit(&mut self, game: &mut Game) -> Vec<Command> {
+        let structures = game
+            .sector()
+            .structures()
+            .iter()
+            .filter(|s| s.owner == Some(self.team) && s.kind.benefits().recruits > 0)
+            .map(|s| s.pos)
+            .collect::<Vec<_>>();
+        let mut commands = Vec::new();
+        for structure in structures {
+            loop {
+                let resources = game.sector().resources(self.team);
+                let Some(unit_type) = game
+                    .sector()
+                    .roster()
+                    .iter()
+                    .filter(|t| t.cost() <= resources)
+                    .max_by_key(|t| t.cost())
+                    .map(|t| t.id().to_string())
+                else {
+                    break;
+                };
+                let next_to = game
+                    .sector()
+                    .tiles()
+                    .map(|t| t.pos())
+                    .filter(|pos| manhattan_distance(*pos, structure) == 1)
+                    .collect::<Vec<_>>();
+                let recruited = next_to.into_iter().find_map(|to| {
+                    let command = Command::Recruit {
+                        structure,
+                        unit_type: unit_type.clone(),
+                        to,
+                    };
+                    game.apply(command.clone()).ok().map(|_| command)
+                });
+                match recruited {
+                    Some(command) => commands.push(command),
+                    None => break,
+                }
+            }
+        }
+        commands
+    }
+
+    /// Pick the best thing for `unit` to do right now, out of every tile it can reach and every
+    /// enemy it could attack from there
+    pub fn plan(&mut self, sector: &Sector, unit: UnitId) -> Option<Plan> {
+        let unit = sector.unit(unit)?;
+        let enemies = sector
+            .visible_units(self.team)
+            .filter(|u| u.team() != self.team)
+            .collect::<Vec<_>>();
+        let threats = sector.threat_map(self.team);
+        let mut best = Vec::<Plan>::new();
+        let reachable = sector
+            .tiles()
+            .map(|t| t.pos())
+            .filter(|pos| unit.can_reach_tile(*pos, sector) == Some(true));
+        for pos in reachable {
+            let attacks = enemies
+                .iter()
+                .filter(|e| unit.actions() > 0 && sector.check_attack_from(unit, pos, e).is_ok())
+                .map(|e| Some(*e));
+            for target in std::iter::once(None).chain(attacks) {
+                let plan = Plan {
+                    unit: unit.id(),
+                    destination: pos,
+                    target: target.map(|t| t.id()),
+                    score: self.score(sector, unit, pos, target, &enemies, &threats),
+                };
+                match best.first().map(|b| b.score) {
+                    Some(score) if plan.score < score => {}
+                    Some(score) if plan.score == score => best.push(plan),
+                    _ => best = vec![plan],
+                }
+            }
+        }
+        match best.len() {
+            0 => None,
+            len => Some(best.swap_remove(self.rng.gen_range(0..len))),
+        }
+    }
+
+    /// Utility of `unit` ending up on `pos`, having attacked `target` from there
+    fn score(
+        &self,
+        sector: &Sector,
+        unit: &Unit,
+        pos: Vector2<u32>,
+        target: Option<&Unit>,
+        enemies: &[&Unit],
+        threats: &ThreatMap,
+    ) -> f32 {
+        let terrain_defense = sector.terrain_defense(pos, unit.footprint());
+        let damage = target.map_or(0, |t| sector.base_damage(unit, t).min(t.health()));
+        let killed = target.filter(|t| damage >= t.health()).map(|t| t.id());
+        // Worst case from enemies that could attack `pos` next turn, other than any killed by
+        // this attack
+        let defense = unit.defense() + terrain_defense;
+        let threat = threats
+            .footprint_threats(pos, unit.footprint())
+            .threats()
+            .iter()
+            .filter(|t| Some(t.unit) != killed)
+            .map(|t| max_damage(t.attack, defense))
+            .sum::<u32>();
+        let objective = self.objective.or_else(|| {
+            enemies
+                .iter()
+                .filter(|e| Some(e.id()) != killed)
+                .map(|e| e.pos())
+                .min_by_key(|e| manhattan_distance(*e, pos))
+        });
+        let distance = objective.map_or(0, |o| manhattan_distance(o, pos));
+
+        self.weights.damage * damage as f32 + self.weights.defense * terrain_defense as f32
+            - self.weights.threat * threat as f32
+            - self.weights.objective * distance as f32
+    }
+}
@@ -0,0 +1,211 @@
+use cgmath::Vector2;
+
+use crate::{
+    game::{history::Command, Game},
+    sector::{
+        archetype::load_unit_types_json,
+        structure::StructureKind,
+        tests::{spawn, spawn_with_stats, test_sector, test_stats},
+        Sector, Team, Terrain,
+    },
+};
+
+use super::{Ai, UtilityWeights};
+
+fn set_terrain(sector: &mut Sector, pos: Vector2<u32>, terrain: Terrain) {
+    sector
+        .tile_mut(pos)
+        .unwrap()
+        .contents_mut()
+        .set_terrain(terrain);
+}
+
+#[test]
+pub fn test_ai_attacks_in_reach() {
+    let mut sector = test_sector(Vector2::new(6, 1), &[]);
+    let attacker = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(3, 0), 3., Team::new(1));
+    let mut ai = Ai::new(Team::new(0), UtilityWeights::default(), 0);
+    let plan = ai.plan(&sector, attacker).unwrap();
+    assert_eq!(plan.destination, Vector2::new(2, 0));
+    assert_eq!(plan.target, Some(target));
+}
+
+#[test]
+pub fn test_ai_attacks_large_units() {
+    // Only the corner of the siege engine nearest the attacker is in range
+    let mut sector = test_sector(Vector2::new(3, 3), &[]);
+    let attacker = spawn(&mut sector, Vector2::new(2, 1), 0., Team::new(0));
+    let siege = load_unit_types_json()
+        .unwrap()
+        .spawn("siege_engine", Vector2::new(0, 0), Team::new(1), &sector)
+        .unwrap();
+    let target = sector.add_unit(siege).unwrap();
+    let mut ai = Ai::new(Team::new(0), UtilityWeights::default(), 0);
+    let plan = ai.plan(&sector, attacker).unwrap();
+    assert_eq!(plan.target, Some(target));
+}
+
+#[test]
+pub fn test_ai_prefers_defensive_terrain() {
+    let mut sector = test_sector(Vector2::new(5, 5), &[]);
+    set_terrain(&mut sector, Vector2::new(0, 2), Terrain::Forest);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let weights = UtilityWeights {
+        threat: 0.,
+        damage: 0.,
+        defense: 1.,
+        objective: 0.,
+    };
+    let mut ai = Ai::new(Team::new(0), weights, 0);
+    assert_eq!(
+        ai.plan(&sector, unit).unwrap().destination,
+        Vector2::new(0, 2)
+    );
+}
+
+#[test]
+pub fn test_ai_avoids_threat() {
+    // A strong enemy sits out of reach, so staying back is better than closing in
+    let mut sector = test_sector(Vector2::new(9, 1), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let mut strong = test_stats(3.);
+    strong.attack = 20;
+    spawn_with_stats(&mut sector, Vector2::new(8, 0), strong, Team::new(1));
+    let weights = UtilityWeights {
+        threat: 1.,
+        damage: 1.,
+        defense: 0.,
+        objective: 0.1,
+    };
+    let mut ai = Ai::new(Team::new(0), weights, 0);
+    let plan = ai.plan(&sector, unit).unwrap();
+    // The enemy can reach 4 tiles, so anything further than 4 tiles away is safe
+    assert_eq!(plan.destination, Vector2::new(3, 0));
+    assert_eq!(plan.target, None);
+}
+
+#[test]
+pub fn test_ai_large_unit_avoids_threat() {
+    // The archer can only reach the bottom right of the siege engine from where the threat is
+    // greatest and the objective closest, so it should stay back
+    let mut sector = test_sector(Vector2::new(6, 3), &[]);
+    let siege = load_unit_types_json()
+        .unwrap()
+        .spawn("siege_engine", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    let siege = sector.add_unit(siege).unwrap();
+    spawn(&mut sector, Vector2::new(5, 0), 0., Team::new(0));
+    let mut archer = test_stats(0.);
+    archer.attack = 20;
+    archer.range = 3;
+    spawn_with_stats(&mut sector, Vector2::new(5, 2), archer, Team::new(1));
+    let weights = UtilityWeights {
+        threat: 1.,
+        damage: 0.,
+        defense: 0.,
+        objective: 0.1,
+    };
+    let mut ai = Ai::new(Team::new(0), weights, 0);
+    let plan = ai.plan(&sector, siege).unwrap();
+    assert!([Vector2::new(1, 0), Vector2::new(0, 1)].contains(&plan.destination));
+}
+
+#[test]
+pub fn test_ai_only_plays_its_own_turn() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    spawn(&mut sector, Vector2::new(3, 3), 3., Team::new(1));
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    let mut ai = Ai::new(Team::new(1), UtilityWeights::default(), 0);
+    assert!(ai.take_turn(&mut game).is_empty());
+    game.apply(Command::EndTurn).unwrap();
+    let commands = ai.take_turn(&mut game);
+    assert_eq!(commands.last(), Some(&Command::EndTurn));
+    assert_eq!(game.turns().active_team(), Team::new(0));
+}
+
+/// Two AIs play against each other until one side is wiped out, or `max_turns` pass. Returns
+/// every command applied, along with the teams left standing.
+fn self_play(seed: u64, max_turns: u32) -> (Vec<Command>, Vec<Team>) {
+    let mut sector = test_sector(
+        Vector2::new(8, 8),
+        &[Vector2::new(3, 3), Vector2::new(4, 4)],
+    );
+    set_terrain(&mut sector, Vector2::new(2, 5), Terrain::Forest);
+    set_terrain(&mut sector, Vector2::new(5, 2), Terrain::Mountain);
+    for (x, y) in [(0, 0), (1, 0), (0, 1)] {
+        spawn(&mut sector, Vector2::new(x, y), 3., Team::new(0));
+    }
+    for (x, y) in [(7, 7), (6, 7), (7, 6)] {
+        spawn(&mut sector, Vector2::new(x, y), 3., Team::new(1));
+    }
+    let teams = vec![Team::new(0), Team::new(1)];
+    let mut game = Game::new(sector, teams.clone(), seed);
+    let mut players = teams
+        .iter()
+        .map(|team| Ai::new(*team, UtilityWeights::default(), seed + team.id() as u64))
+        .collect::<Vec<_>>();
+    let mut log = Vec::new();
+    let alive = |game: &Game| {
+        let mut alive = game.sector().units().map(|u| u.team()).collect::<Vec<_>>();
+        alive.dedup();
+        alive
+    };
+    while game.turns().turn() <= max_turns && alive(&game).len() > 1 {
+        let phase = game.turns().phase();
+        log.extend(players[phase].take_turn(&mut game));
+    }
+    let survivors = alive(&game);
+    (log, survivors)
+}
+
+#[test]
+pub fn test_ai_self_play() {
+    let (log, survivors) = self_play(7, 30);
+    assert!(log.iter().any(|c| matches!(c, Command::Attack { .. })));
+    assert_eq!(survivors.len(), 1);
+}
+
+#[test]
+pub fn test_ai_is_deterministic() {
+    for seed in 0..3 {
+        assert_eq!(self_play(seed, 10), self_play(seed, 10));
+    }
+}
+
+#[test]
+pub fn test_ai_recruits() {
+    let mut sector = test_sector(Vector2::new(6, 6), &[]);
+    let fort = Vector2::new(2, 2);
+    let villages = [Vector2::new(0, 0), Vector2::new(0, 1), Vector2::new(1, 0)];
+    sector
+        .place_structure(StructureKind::Fort, Some(Team::new(0)), fort)
+        .unwrap();
+    for village in villages {
+        sector
+            .place_structure(StructureKind::Village, Some(Team::new(0)), village)
+            .unwrap();
+    }
+    let registry = load_unit_types_json().unwrap();
+    let infantry = registry.get("infantry").unwrap().clone();
+    sector.set_roster(vec![infantry]).unwrap();
+    spawn(&mut sector, Vector2::new(5, 0), 1., Team::new(0));
+    spawn(&mut sector, Vector2::new(5, 5), 1., Team::new(1));
+    // The villages pay out at the start of the first phase, enough for one recruit
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    let mut ai = Ai::new(Team::new(0), UtilityWeights::default(), 0);
+    let commands = ai.take_turn(&mut game);
+    let recruits = commands
+        .iter()
+        .filter_map(|c| match c {
+            Command::Recruit { unit_type, to, .. } => Some((unit_type.as_str(), *to)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(recruits.len(), 1);
+    assert_eq!(recruits[0].0, "infantry");
+    let recruit = game.sector().unit_at_tile(recruits[0].1).unwrap();
+    assert_eq!(recruit.team(), Team::new(0));
+    assert_eq!(game.sector().resources(Team::new(0)), 2);
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use cgmath::Vector2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    sector::{archetype::UnitRegistry, ActionError, Sector, Team, Unit},
+    terrain::generate_terrain,
+};
+
+use super::{outcome::Outcome, scenario::Scenario, Game};
+
+/// Version written into every campaign save. The games inside are saves of their own, and are
+/// migrated the same way, so this only needs bumping when the rest of the campaign changes shape.
+pub const CAMPAIGN_VERSION: u32 = 1;
+
+/// The sectors making up a campaign, and the routes between them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CampaignMap {
+    name: String,
+    // Team the player controls in every sector
+    player: Team,
+    // Types of the units the player starts the campaign with
+    army: Vec<String>,
+    // The campaign starts in the first sector
+    sectors: Vec<CampaignSector>,
+}
+
+/// One sector on a campaign map, named after its scenario
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CampaignSector {
+    // What the player finds there. It has to include the player's team, and doesn't need any
+    // units for it, since the player's army is brought in.
+    scenario: Scenario,
+    // Where the player's units arrive
+    entry: Vector2<u32>,
+    // Names of the sectors that can be travelled to from here
+    links: Vec<String>,
+}
+
+impl CampaignMap {
+    pub fn from_json(json: &str) -> Result<CampaignMap> {
+        let map: CampaignMap = serde_json::from_str(json).context("Could not parse campaign")?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<CampaignMap> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Could not read campaign {path:?}"))?;
+        CampaignMap::from_json(&json).with_context(|| format!("Could not load campaign {path:?}"))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn player(&self) -> Team {
+        self.player
+    }
+
+    pub fn sectors(&self) -> &[CampaignSector] {
+        &self.sectors
+    }
+
+    pub fn sector(&self, name: &str) -> Option<&CampaignSector> {
+        self.sectors.iter().find(|s| s.name() == name)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.sectors.is_empty(),
+            "Campaign {} has no sectors",
+            self.name
+        );
+        ensure!(!self.army.is_empty(), "Campaign {} has no army", self.name);
+        let mut names = HashSet::new();
+        for sector in &self.sectors {
+            ensure!(
+                names.insert(sector.name()),
+                "Campaign {} has sector {} more than once",
+                self.name,
+                sector.name()
+            );
+        }
+        for sector in &self.sectors {
+            let size = sector.scenario.size();
+            ensure!(
+                sector.entry.x < size.x && sector.entry.y < size.y,
+                "Sector {} has its entry outside of it",
+                sector.name()
+            );
+            ensure!(
+                sector.scenario.teams().contains(&self.player),
+                "Sector {} has no place for the player's team",
+                sector.name()
+            );
+            for link in &sector.links {
+                ensure!(
+                    names.contains(link.as_str()),
+                    "Sector {} leads to {link}, which doesn't exist",
+                    sector.name()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CampaignSector {
+    pub fn name(&self) -> &str {
+        self.scenario.name()
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn entry(&self) -> Vector2<u32> {
+        self.entry
+    }
+
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+}
+
+/// What a sector is generated from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SectorSeeds {
+    pub terrain: u64,
+    pub game: u64,
+}
+
+/// A campaign being played. The player fights in one sector at a time, and once they've won
+/// there they can travel on to a neighbouring sector, taking their surviving units with them.
+/// Sectors are generated when they're first visited, and kept as they were left for if the
+/// player comes back.
+pub struct Campaign {
+    map: CampaignMap,
+    // Drawn for every sector when the campaign starts, so the same seed always gives the same
+    // campaign whichever way the player goes through it
+    seeds: BTreeMap<String, SectorSeeds>,
+    // Sector the player is in
+    current: String,
+    game: Game,
+    // Every other sector the player has been to, as they left it
+    left: BTreeMap<String, Game>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CampaignSave {
+    version: u32,
+    map: CampaignMap,
+    seeds: BTreeMap<String, SectorSeeds>,
+    current: String,
+    // Games are saved the same way as on their own
+    game: Value,
+    left: BTreeMap<String, Value>,
+}
+
+impl Campaign {
+    /// Start a campaign in the map's first sector, with the player's whole army
+    pub fn new(map: CampaignMap, unit_types: &UnitRegistry, seed: u64) -> Result<Campaign> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let seeds = map
+            .sectors
+            .iter()
+            .map(|s| {
+                let seeds = SectorSeeds {
+                    terrain: rng.gen(),
+                    game: rng.gen(),
+                };
+                (s.name().to_string(), seeds)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let first = &map.sectors[0];
+        let mut sector = generate_sector(first, seeds[first.name()]);
+        let army = map
+            .army
+            .iter()
+            .map(|id| unit_types.spawn(id, first.entry, map.player, &sector))
+            .collect::<Result<Vec<_>>>()?;
+        arrive(&mut sector, army, first.entry)
+            .map_err(|e| anyhow!("No room for the army in {}: {e:?}", first.name()))?;
+        let game = first
+            .scenario
+            .start(sector, unit_types, seeds[first.name()].game)?;
+        Ok(Campaign {
+            current: first.name().to_string(),
+            map,
+            seeds,
+            game,
+            left: BTreeMap::new(),
+        })
+    }
+
+    pub fn map(&self) -> &CampaignMap {
+        &self.map
+    }
+
+    pub fn seeds(&self, sector: &str) -> Option<SectorSeeds> {
+        self.seeds.get(sector).copied()
+    }
+
+    /// Name of the sector the player is in
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// The match in the sector the player is in
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// Whether the player has been to `sector`, which is when it's generated
+    pub fn visited(&self, sector: &str) -> bool {
+        self.current == sector || self.left.contains_key(sector)
+    }
+
+    /// Sectors that can be travelled to from the current one
+    pub fn destinations(&self) -> &[String] {
+        self.map
+            .sector(&self.current)
+            .map_or(&[], |s| s.links.as_slice())
+    }
+
+    /// Whether the player has won the current sector, and can move on
+    pub fn can_travel(&self) -> bool {
+        self.game.outcome(self.map.player) == Some(Outcome::Won)
+    }
+
+    /// Whether the player has failed in the current sector, which ends the campaign
+    pub fn is_lost(&self) -> bool {
+        matches!(
+            self.game.outcome(self.map.player),
+            Some(Outcome::Lost | Outcome::Drawn)
+        )
+    }
+
+    /// Take the player's units to `to`, generating it if this is the first visit. Units keep
+    /// their health and status effects, and arrive as close to the sector's entry as they can.
+    /// If the move can't be made, nothing changes.
+    pub fn travel(&mut self, to: &str, unit_types: &UnitRegistry) -> Result<()> {
+        ensure!(
+            self.destinations().iter().any(|d| d == to),
+            "Can't get to {to} from {}",
+            self.current
+        );
+        ensure!(
+            self.can_travel(),
+            "{} has to be won before moving on",
+            self.current
+        );
+        let destination = self
+            .map
+            .sector(to)
+            .with_context(|| format!("No sector called {to}"))?;
+        let player = self.map.player;
+        let units = self
+            .game
+            .sector()
+            .units()
+            .filter(|u| u.team() == player)
+            .cloned()
+            .collect::<Vec<_>>();
+        let game = match self.left.get_mut(to) {
+            Some(game) => {
+                arrive(&mut game.sector, units, destination.entry)
+                    .map_err(|e| anyhow!("No room for the army in {to}: {e:?}"))?;
+                self.left.remove(to).expect("Sector was just found")
+            }
+            None => {
+                let seeds = self.seeds[to];
+                let mut sector = generate_sector(destination, seeds);
+                arrive(&mut sector, units, destination.entry)
+                    .map_err(|e| anyhow!("No room for the army in {to}: {e:?}"))?;
+                destination.scenario.start(sector, unit_types, seeds.game)?
+            }
+        };
+        let leaving = self
+            .game
+            .sector()
+            .units()
+            .filter(|u| u.team() == player)
+            .map(|u| u.id())
+            .collect::<Vec<_>>();
+        for id in leaving {
+            self.game.sector.remove_unit(id);
+        }
+        let previous = std::mem::replace(&mut self.game, game);
+        let previous_name = std::mem::replace(&mut self.current, to.to_string());
+        self.left.insert(previous_name, previous);
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let left = self
+            .left
+            .iter()
+            .map(|(name, game)| Ok((name.clone(), game.to_value()?)))
+            .collect::<Result<_>>()?;
+        let save = CampaignSave {
+            version: CAMPAIGN_VERSION,
+            map: self.map.clone(),
+            seeds: self.seeds.clone(),
+            current: self.current.clone(),
+            game: self.game.to_value()?,
+            left,
+        };
+        serde_json::to_string(&save).context("Could not write campaign as JSON")
+    }
+
+    pub fn from_json(json: &str) -> Result<Campaign> {
+        let save: CampaignSave =
+            serde_json::from_str(json).context("Could not parse campaign save")?;
+        ensure!(
+            save.version == CAMPAIGN_VERSION,
+            "Campaign save is from version {}, and only version {CAMPAIGN_VERSION} can be loaded",
+            save.version
+        );
+        save.map.validate()?;
+        for sector in &save.map.sectors {
+            ensure!(
+                save.seeds.contains_key(sector.name()),
+                "Campaign save has no seeds for {}",
+                sector.name()
+            );
+        }
+        let mut left = BTreeMap::new();
+        for (name, game) in save.left {
+            ensure!(
+                save.map.sector(&name).is_some() && name != save.current,
+                "Campaign save has a game for {name}, which isn't somewhere the player left"
+            );
+            let game = Game::from_value(game).with_context(|| format!("Could not load {name}"))?;
+            left.insert(name, game);
+        }
+        if save.map.sector(&save.current).is_none() {
+            bail!("Campaign save is in {}, which doesn't exist", save.current);
+        }
+        let game = Game::from_value(save.game)
+            .with_context(|| format!("Could not load {}", save.current))?;
+        Ok(Campaign {
+            map: save.map,
+            seeds: save.seeds,
+            current: save.current,
+            game,
+            left,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_json()?)
+            .with_context(|| format!("Could not write campaign to {path:?}"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Campaign> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Could not read campaign save {path:?}"))?;
+        Campaign::from_json(&json).with_context(|| format!("Could not load campaign {path:?}"))
+    }
+}
+
+fn generate_sector(sector: &CampaignSector, seeds: SectorSeeds) -> Sector {
+    generate_terrain(
+        sector.scenario.size(),
+        sector.name().to_string(),
+        seeds.terrain,
+    )
+}
+
+/// Place every unit as close to `entry` as it can get. If any of them can't be placed, none of
+/// them are.
+fn arrive(sector: &mut Sector, units: Vec<Unit>, entry: Vector2<u32>) -> Result<(), ActionError> {
+    let mut placed = Vec::new();
+    for unit in units {
+        match sector.place_unit(unit, entry) {
+            Ok(id) => placed.push(id),
+            Err(e) => {
+                for id in placed {
+                    sector.remove_unit(id);
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The campaign built into the game, from `assets/campaigns/frontier.json`
+pub fn load_frontier_campaign() -> Result<CampaignMap> {
+    CampaignMap::from_json(include_str!("../../assets/campaigns/frontier.json"))
+}
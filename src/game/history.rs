@@ -0,0 +1,129 @@
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    juno::pathfinding::Path,
+    sector::{combat::AttackResult, ActionError, UnitId},
+};
+
+/// A single state-changing action that a player (or AI) can take
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Command {
+    Move {
+        unit: UnitId,
+        to: Vector2<u32>,
+    },
+    Attack {
+        attacker: UnitId,
+        target: UnitId,
+    },
+    /// Spend resources on a unit from the sector's roster, recruited from the structure on
+    /// `structure` and put on `to`
+    Recruit {
+        structure: Vector2<u32>,
+        unit_type: String,
+        to: Vector2<u32>,
+    },
+    EndTurn,
+    /// Take back the most recent command in the current phase
+    Undo,
+    /// Apply the most recently undone command again
+    Redo,
+}
+
+/// What happened when a command was applied
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandOutcome {
+    Moved(Path),
+    Attacked(AttackResult),
+    Recruited(UnitId),
+    TurnEnded,
+    /// The command that was taken back
+    Undone(Command),
+}
+
+/// How to take back a command. Only commands that can be cleanly reversed have one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Inverse {
+    /// Walk the unit back along `path`, restoring the movement it had before
+    Move {
+        unit: UnitId,
+        path: Path,
+        movement: f32,
+    },
+    /// Take the recruit away again, refunding what it cost
+    Recruit {
+        unit: UnitId,
+        structure: Vector2<u32>,
+        cost: u32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    command: Command,
+    // `None` for irreversible commands
+    inverse: Option<Inverse>,
+}
+
+/// Commands applied during the current phase, so that they can be undone and redone. Irreversible
+/// commands stay in the history, and nothing before them can be undone. Undoing and redoing
+/// aren't recorded themselves.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    done: Vec<Entry>,
+    undone: Vec<Command>,
+}
+
+impl History {
+    /// Add a command that has just been applied. Anything that was undone can no longer be redone.
+    pub fn record(&mut self, command: Command, inverse: Option<Inverse>) {
+        self.done.push(Entry { command, inverse });
+        self.undone.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.done.last().is_some_and(|e| e.inverse.is_some())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Take the most recent command off the history, along with how to reverse it
+    pub fn undo(&mut self) -> Result<(Command, Inverse), ActionError> {
+        let entry = self.done.last().ok_or(ActionError::NothingToUndo)?;
+        let inverse = entry.inverse.clone().ok_or(ActionError::Irreversible)?;
+        let command = entry.command.clone();
+        self.done.pop();
+        self.undone.push(command.clone());
+        Ok((command, inverse))
+    }
+
+    /// Take the most recently undone command, ready to be applied again. Redoing it records it
+    /// again, so anything further along the redo stack is kept aside until then.
+    pub fn redo(&mut self) -> Result<(Command, Vec<Command>), ActionError> {
+        let command = self.undone.pop().ok_or(ActionError::NothingToRedo)?;
+        Ok((command, std::mem::take(&mut self.undone)))
+    }
+
+    /// Put back commands taken aside by `redo`
+    pub fn restore_redo(&mut self, undone: Vec<Command>) {
+        self.undone = undone;
+    }
+
+    /// Stop the most recent command from being undone, such as when something that can't be
+    /// reversed happened along with it
+    pub fn seal(&mut self) {
+        if let Some(entry) = self.done.last_mut() {
+            entry.inverse = None;
+        }
+        self.undone.clear();
+    }
+
+    /// Forget everything, such as when a phase ends
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sector::ActionError;
+
+use super::{
+    history::{Command, CommandOutcome},
+    Game,
+};
+
+/// A command that was applied to a game, along with the game's checksum straight afterwards
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LoggedCommand {
+    pub command: Command,
+    pub checksum: u64,
+}
+
+/// Why a game couldn't follow a command log
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The command at `index` was rejected
+    Rejected { index: usize, error: ActionError },
+    /// After the command at `index`, the game no longer matched the one the log came from
+    Desync {
+        index: usize,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl Game {
+    /// Checksum of everything that decides how the game plays out from here: the sector, turn
+    /// state and random generator. Games given the same commands from the same start always have
+    /// the same checksum, even in different processes or builds, so it can be compared between
+    /// players to catch them drifting apart.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        bincode::serialize_into(&mut hasher, &self.save_ref())
+            .expect("Game state can always be serialized");
+        hasher.0
+    }
+
+    /// Apply every command in `log` in order, checking the game against each checksum along the
+    /// way. Stops at the first command that doesn't match.
+    pub fn replay(&mut self, log: &[LoggedCommand]) -> Result<(), ReplayError> {
+        for (index, entry) in log.iter().enumerate() {
+            self.follow(index, entry)?;
+        }
+        Ok(())
+    }
+
+    /// Apply a single logged command, which was at `index` in its log, and check the checksum
+    pub(super) fn follow(
+        &mut self,
+        index: usize,
+        entry: &LoggedCommand,
+    ) -> Result<CommandOutcome, ReplayError> {
+        let outcome = self
+            .apply(entry.command.clone())
+            .map_err(|error| ReplayError::Rejected { index, error })?;
+        let actual = self.checksum();
+        match actual == entry.checksum {
+            true => Ok(outcome),
+            false => Err(ReplayError::Desync {
+                index,
+                expected: entry.checksum,
+                actual,
+            }),
+        }
+    }
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hashers is guaranteed to never change
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Write for Fnv1a {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+pub mod campaign;
+pub mod history;
+pub mod lockstep;
+pub mod net;
+pub mod outcome;
+pub mod replay;
+pub mod save;
+pub mod scenario;
+pub mod trigger;
+pub mod turn;
+
+use std::collections::{BTreeMap, HashSet};
+
+use cgmath::Vector2;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    juno::pathfinding::Path,
+    sector::{combat::AttackResult, ActionError, Sector, Team, UnitId},
+};
+
+use self::{
+    history::{Command, CommandOutcome, History, Inverse},
+    lockstep::LoggedCommand,
+    outcome::Outcome,
+    trigger::{Dialogue, Trigger},
+    turn::{TurnHooks, TurnState},
+};
+
+#[cfg(test)]
+pub mod tests;
+
+/// A sector being played, along with whose turn it is. Actions taken through `Game` are checked
+/// against the turn order, unlike those made on the `Sector` directly.
+///
+/// Every change goes through `apply`, and all randomness comes from the game's own seeded
+/// generator, so the same commands from the same start always play out the same way.
+pub struct Game {
+    sector: Sector,
+    turns: TurnState,
+    hooks: TurnHooks,
+    history: History,
+    // Teams that have finished, along with how. Worked out from the sector and turn state after
+    // every change, so it isn't saved.
+    outcomes: BTreeMap<Team, Outcome>,
+    rng: ChaCha8Rng,
+    // What `rng` was first seeded with
+    seed: u64,
+    // Every command applied since the game was started or loaded
+    log: Vec<LoggedCommand>,
+    // Scripted events, checked after every command
+    triggers: Vec<Trigger>,
+    // Every line of dialogue shown since the game was started or loaded
+    dialogue: Vec<Dialogue>,
+}
+
+impl Game {
+    /// Start playing on `sector`, with `teams` taking their phases in order. The first team's
+    /// phase starts straight away, before any hooks can be registered. All randomness in the
+    /// game is drawn from `seed`.
+    pub fn new(mut sector: Sector, teams: Vec<Team>, seed: u64) -> Self {
+        let turns = TurnState::new(teams);
+        sector.start_phase(turns.active_team());
+        let mut game = Self {
+            sector,
+            turns,
+            hooks: TurnHooks::default(),
+            history: History::default(),
+            outcomes: BTreeMap::new(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            seed,
+            log: Vec::new(),
+            triggers: Vec::new(),
+            dialogue: Vec::new(),
+        };
+        game.update_outcomes();
+        game
+    }
+
+    pub fn sector(&self) -> &Sector {
+        &self.sector
+    }
+
+    pub fn turns(&self) -> &TurnState {
+        &self.turns
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// How the match ended for `team`, or `None` if it's still playing
+    pub fn outcome(&self, team: Team) -> Option<Outcome> {
+        self.outcomes.get(&team).copied()
+    }
+
+    pub fn outcomes(&self) -> &BTreeMap<Team, Outcome> {
+        &self.outcomes
+    }
+
+    /// Whether every team has finished, one way or another
+    pub fn is_over(&self) -> bool {
+        self.turns
+            .teams()
+            .iter()
+            .all(|t| self.outcomes.contains_key(t))
+    }
+
+    /// Every command applied since the game was started or loaded, with the checksum after each
+    pub fn log(&self) -> &[LoggedCommand] {
+        &self.log
+    }
+
+    /// Seed that all of the game's randomness is drawn from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Run `hook` at the start of every phase, after the active team's units have been refreshed.
+    /// Hooks run as part of `apply`, so they need to be deterministic too.
+    pub fn on_turn_start(&mut self, hook: impl FnMut(&mut Sector, &TurnState) + 'static) {
+        self.hooks.add_start(Box::new(hook));
+    }
+
+    /// Run `hook` at the end of every phase, before play passes to the next team
+    pub fn on_turn_end(&mut self, hook: impl FnMut(&mut Sector, &TurnState) + 'static) {
+        self.hooks.add_end(Box::new(hook));
+    }
+
+    /// Apply any command, recording it in the history and the log, then fire any triggers it set
+    /// off. Rejected commands don't change anything. Commands that fire a trigger can't be undone.
+    pub fn apply(&mut self, command: Command) -> Result<CommandOutcome, ActionError> {
+        let outcome = self.execute(command.clone())?;
+        if self.run_triggers() {
+            self.history.seal();
+            self.update_outcomes();
+        }
+        let checksum = self.checksum();
+        self.log.push(LoggedCommand { command, checksum });
+        Ok(outcome)
+    }
+
+    fn execute(&mut self, command: Command) -> Result<CommandOutcome, ActionError> {
+        match command {
+            Command::Move { unit, to } => self.move_unit(unit, to).map(CommandOutcome::Moved),
+            Command::Attack { attacker, target } => {
+                self.attack(attacker, target).map(CommandOutcome::Attacked)
+            }
+            Command::Recruit {
+                structure,
+                unit_type,
+                to,
+            } => self
+                .recruit(structure, unit_type, to)
+                .map(CommandOutcome::Recruited),
+            Command::EndTurn => {
+                if self.is_over() {
+                    return Err(ActionError::GameOver);
+                }
+                self.end_turn();
+                Ok(CommandOutcome::TurnEnded)
+            }
+            Command::Undo => self.undo().map(CommandOutcome::Undone),
+            Command::Redo => self.redo(),
+        }
+    }
+
+    /// Move a unit, recording how to undo it. Moves that reveal an enemy the team couldn't see
+    /// before (including running into an ambush), that let the team see tiles it had never seen,
+    /// or that finish the match for a team can't be undone.
+    fn move_unit(&mut self, unit: UnitId, to: Vector2<u32>) -> Result<Path, ActionError> {
+        self.check_active(unit)?;
+        let team = self.turns.active_team();
+        let movement = self.sector.unit(unit).map_or(0., |u| u.movement());
+        let seen_enemies = self.visible_enemies(team);
+        let unseen = self.sector.unseen_tiles(team);
+        let path = self.sector.move_unit(unit, to)?;
+        let revealed = !self.visible_enemies(team).is_subset(&seen_enemies)
+            || self.sector.unseen_tiles(team) < unseen;
+        let decided = self.update_outcomes();
+        let inverse = (!revealed && !decided).then(|| Inverse::Move {
+            unit,
+            path: path.clone(),
+            movement,
+        });
+        self.history.record(Command::Move { unit, to }, inverse);
+        Ok(path)
+    }
+
+    /// Attack with a unit. Damage is rolled, so attacks can never be undone.
+    fn attack(&mut self, attacker: UnitId, target: UnitId) -> Result<AttackResult, ActionError> {
+        self.check_active(attacker)?;
+        let result = self.sector.attack_unit(attacker, target, &mut self.rng)?;
+        self.history
+            .record(Command::Attack { attacker, target }, None);
+        self.update_outcomes();
+        Ok(result)
+    }
+
+    /// Recruit a unit for the active team, recording how to undo it. Like moves, recruits that
+    /// reveal an enemy or finish the match can't be undone.
+    fn recruit(
+        &mut self,
+        structure: Vector2<u32>,
+        unit_type: String,
+        to: Vector2<u32>,
+    ) -> Result<UnitId, ActionError> {
+        let team = self.turns.active_team();
+        if self.outcomes.contains_key(&team) {
+            return Err(ActionError::GameOver);
+        }
+        let seen_enemies = self.visible_enemies(team);
+        let unit = self.sector.recruit_unit(team, structure, &unit_type, to)?;
+        let cost = self
+            .sector
+            .roster()
+            .iter()
+            .find(|t| t.id() == unit_type)
+            .map_or(0, |t| t.cost());
+        let revealed = !self.visible_enemies(team).is_subset(&seen_enemies);
+        let decided = self.update_outcomes();
+        let inverse = (!revealed && !decided).then_some(Inverse::Recruit {
+            unit,
+            structure,
+            cost,
+        });
+        let command = Command::Recruit {
+            structure,
+            unit_type,
+            to,
+        };
+        self.history.record(command, inverse);
+        Ok(unit)
+    }
+
+    /// Finish the active team's phase and start the next team's, ticking the status effects on
+    /// both teams' units and restoring the next team's. The active team captures any structures
+    /// its units are standing on before handing over. Objectives are checked once the next
+    /// phase has started. Only the current phase's commands can be undone, so this clears the
+    /// history. Does nothing once the match is over.
+    fn end_turn(&mut self) {
+        if self.is_over() {
+            return;
+        }
+        let team = self.turns.active_team();
+        self.hooks.run_end(&mut self.sector, &self.turns);
+        self.sector.end_phase(team);
+        self.sector.capture_structures(team);
+        self.sector.update_objectives(team);
+        self.turns.advance();
+        self.sector.start_phase(self.turns.active_team());
+        self.hooks.run_start(&mut self.sector, &self.turns);
+        self.history.clear();
+        self.update_outcomes();
+    }
+
+    /// Take back the most recent command, returning it
+    fn undo(&mut self) -> Result<Command, ActionError> {
+        let (command, inverse) = self.history.undo()?;
+        match inverse {
+            Inverse::Move {
+                unit,
+                path,
+                movement,
+            } => self.sector.revert_move(unit, &path, movement)?,
+            Inverse::Recruit {
+                unit,
+                structure,
+                cost,
+            } => self.sector.revert_recruit(unit, structure, cost)?,
+        }
+        Ok(command)
+    }
+
+    /// Apply the most recently undone command again
+    fn redo(&mut self) -> Result<CommandOutcome, ActionError> {
+        let (command, undone) = self.history.redo()?;
+        let outcome = self.execute(command.clone());
+        let mut undone = undone;
+        if outcome.is_err() {
+            undone.push(command);
+        }
+        self.history.restore_redo(undone);
+        outcome
+    }
+
+    /// Enemies of `team` that it can currently see
+    fn visible_enemies(&self, team: Team) -> HashSet<UnitId> {
+        self.sector
+            .visible_units(team)
+            .filter(|u| u.team() != team)
+            .map(|u| u.id())
+            .collect()
+    }
+
+    /// Make sure that `unit` exists and belongs to the active team, which is still playing
+    fn check_active(&self, unit: UnitId) -> Result<(), ActionError> {
+        let unit = self.sector.unit(unit).ok_or(ActionError::NoUnit)?;
+        if unit.team() != self.turns.active_team() {
+            return Err(ActionError::NotYourTurn);
+        }
+        match self.outcomes.contains_key(&unit.team()) {
+            true => Err(ActionError::GameOver),
+            false => Ok(()),
+        }
+    }
+
+    /// Check every team's objectives, and whether a trigger has ended the match, returning whether
+    /// any more teams have finished. Teams keep the outcome they first finished with.
+    fn update_outcomes(&mut self) -> bool {
+        let finished = self.outcomes.len();
+        // Objectives first, the same way as when triggers fire after the command that set them off
+        let evaluated = outcome::evaluate(&self.sector, &self.turns);
+        for (team, outcome) in evaluated
+            .into_iter()
+            .chain(trigger::outcomes(&self.triggers, &self.turns))
+        {
+            self.outcomes.entry(team).or_insert(outcome);
+        }
+        self.outcomes.len() > finished
+    }
+}
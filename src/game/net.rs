@@ -0,0 +1,335 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::sector::{archetype::UnitRegistry, ActionError, Team};
+
+use super::{
+    history::{Command, CommandOutcome},
+    lockstep::{LoggedCommand, ReplayError},
+    replay::Replay,
+    Game,
+};
+
+/// Version of the messages sent between players. Both players need the same one, and the same
+/// rules, so bump this whenever either changes.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// Everything sent over the connection, one JSON message per line
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) enum Message {
+    /// The first thing a joining player sends
+    Hello { version: u32 },
+    /// The host's reply: how the match starts, which team the joining player controls, and the
+    /// checksum of the game before any commands, to make sure both players generated the same one
+    Setup {
+        replay: Replay,
+        team: Team,
+        checksum: u64,
+    },
+    /// A command that the sender applied to its own game, with the checksum straight afterwards
+    Command(LoggedCommand),
+    /// The sender is leaving the match, and why
+    Leave { reason: String },
+}
+
+/// Why a command couldn't be played in a network match
+#[derive(Debug)]
+pub enum NetError {
+    /// The local player's command was rejected, so nothing was sent
+    Rejected(ActionError),
+    /// The other player sent a command that was invalid here, or that played out differently
+    Desync(ReplayError),
+    /// The connection failed or the other player left
+    Connection(anyhow::Error),
+}
+
+impl From<anyhow::Error> for NetError {
+    fn from(error: anyhow::Error) -> Self {
+        NetError::Connection(error)
+    }
+}
+
+/// A match between two players over TCP, each with their own copy of the game. Every command
+/// either player applies is sent to the other along with the checksum it led to, and checked on
+/// arrival, so any difference between the two games is caught at the command that caused it.
+/// After a desync or a connection problem the match can't go on, and nothing more is sent.
+pub struct NetGame {
+    game: Game,
+    // How the match started, without any commands
+    replay: Replay,
+    // Team played on this side of the connection
+    team: Team,
+    stream: TcpStream,
+    // Messages read from the stream by a background thread, so that checking for them never
+    // blocks
+    incoming: Receiver<Result<Message>>,
+    connected: bool,
+}
+
+impl NetGame {
+    /// Wait for another player to connect to `listener`, and start the match in `replay` with
+    /// them. The scenario needs exactly two teams, and the host plays the first.
+    pub fn host(
+        listener: &TcpListener,
+        replay: Replay,
+        unit_types: &UnitRegistry,
+    ) -> Result<NetGame> {
+        let teams = replay.scenario().teams().to_vec();
+        ensure!(
+            teams.len() == 2,
+            "Network matches need exactly two teams, but {} has {}",
+            replay.scenario().name(),
+            teams.len()
+        );
+        ensure!(
+            replay.log().is_empty(),
+            "Network matches have to start from the beginning"
+        );
+        let game = replay.start(unit_types)?;
+        let (stream, address) = listener.accept().context("Could not accept a connection")?;
+        info!("{address} connected");
+        let incoming = read_messages(&stream)?;
+        let mut net = NetGame {
+            game,
+            replay,
+            team: teams[0],
+            stream,
+            incoming,
+            connected: true,
+        };
+        match net.incoming.recv().context("Connection closed")?? {
+            Message::Hello { version } if version == PROTOCOL_VERSION => {}
+            Message::Hello { version } => {
+                let reason = format!("Host is on version {PROTOCOL_VERSION}, not {version}");
+                net.leave(&reason);
+                bail!(reason);
+            }
+            message => bail!("Expected a greeting, but got {message:?}"),
+        }
+        let setup = Message::Setup {
+            replay: net.replay.clone(),
+            team: teams[1],
+            checksum: net.game.checksum(),
+        };
+        send(&mut net.stream, &setup)?;
+        Ok(net)
+    }
+
+    /// Connect to a player hosting a match at `address`, and set up the match they send
+    pub fn join(address: impl ToSocketAddrs, unit_types: &UnitRegistry) -> Result<NetGame> {
+        let mut stream = TcpStream::connect(address).context("Could not connect to the host")?;
+        let incoming = read_messages(&stream)?;
+        send(
+            &mut stream,
+            &Message::Hello {
+                version: PROTOCOL_VERSION,
+            },
+        )?;
+        let (replay, team, checksum) = match incoming.recv().context("Connection closed")?? {
+            Message::Setup {
+                replay,
+                team,
+                checksum,
+            } => (replay, team, checksum),
+            Message::Leave { reason } => bail!("Host turned us away: {reason}"),
+            message => bail!("Expected the match setup, but got {message:?}"),
+        };
+        // The host could send anything, so the match is only set up once it's known to be sound
+        let game = replay
+            .validate()
+            .and_then(|_| replay.start(unit_types))
+            .and_then(|game| {
+                ensure!(
+                    game.checksum() == checksum,
+                    "Set up a different game from the host"
+                );
+                Ok(game)
+            });
+        let game = match game {
+            Ok(game) => game,
+            Err(e) => {
+                let reason = e.to_string();
+                // Leaving anyway, so there's nothing to do if this fails
+                let _ = send(&mut stream, &Message::Leave { reason });
+                return Err(e);
+            }
+        };
+        Ok(NetGame {
+            game,
+            replay,
+            team,
+            stream,
+            incoming,
+            connected: true,
+        })
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn team(&self) -> Team {
+        self.team
+    }
+
+    /// Whether the match can go on. Once it can't, there's no reconnecting.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The match so far, as a replay
+    pub fn replay(&self) -> Replay {
+        let mut replay = self.replay.clone();
+        replay.record(&self.game);
+        replay
+    }
+
+    /// Apply one of the local player's commands, and send it to the other player. Commands can
+    /// only be given during the local team's phase.
+    pub fn apply(&mut self, command: Command) -> Result<CommandOutcome, NetError> {
+        self.check_connected()?;
+        if self.game.turns().active_team() != self.team {
+            return Err(NetError::Rejected(ActionError::NotYourTurn));
+        }
+        let outcome = self.game.apply(command).map_err(NetError::Rejected)?;
+        self.send_since(self.game.log().len() - 1)?;
+        Ok(outcome)
+    }
+
+    /// Let `play` apply the local player's commands to the game directly, for players such as
+    /// `Ai` that work on a `Game`, and then send every command it applied. Only runs during the
+    /// local team's phase.
+    pub fn play(&mut self, play: impl FnOnce(&mut Game)) -> Result<(), NetError> {
+        self.check_connected()?;
+        if self.game.turns().active_team() != self.team {
+            return Err(NetError::Rejected(ActionError::NotYourTurn));
+        }
+        let sent = self.game.log().len();
+        play(&mut self.game);
+        self.send_since(sent)
+    }
+
+    /// Apply the next command the other player sent, if there is one yet
+    pub fn poll(&mut self) -> Result<Option<(Command, CommandOutcome)>, NetError> {
+        self.check_connected()?;
+        let message = match self.incoming.try_recv() {
+            Ok(message) => message,
+            Err(TryRecvError::Empty) => return Ok(None),
+            Err(TryRecvError::Disconnected) => Err(anyhow!("Connection closed")),
+        };
+        self.receive(message).map(Some)
+    }
+
+    /// Wait for the other player's next command, and apply it
+    pub fn wait(&mut self) -> Result<(Command, CommandOutcome), NetError> {
+        self.check_connected()?;
+        let message = self
+            .incoming
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("Connection closed")));
+        self.receive(message)
+    }
+
+    fn receive(&mut self, message: Result<Message>) -> Result<(Command, CommandOutcome), NetError> {
+        let entry = match message {
+            Ok(Message::Command(entry)) => entry,
+            Ok(Message::Leave { reason }) => {
+                self.connected = false;
+                return Err(anyhow!("The other player left: {reason}").into());
+            }
+            Ok(message) => {
+                let reason = format!("Expected a command, but got {message:?}");
+                self.leave(&reason);
+                return Err(anyhow!(reason).into());
+            }
+            Err(e) => {
+                self.connected = false;
+                return Err(e.into());
+            }
+        };
+        let index = self.game.log().len();
+        let followed = match self.game.turns().active_team() == self.team {
+            true => Err(ReplayError::Rejected {
+                index,
+                error: ActionError::NotYourTurn,
+            }),
+            false => self.game.follow(index, &entry),
+        };
+        match followed {
+            Ok(outcome) => Ok((entry.command, outcome)),
+            Err(error) => {
+                self.leave(&format!("Desynced: {error:?}"));
+                Err(NetError::Desync(error))
+            }
+        }
+    }
+
+    fn check_connected(&self) -> Result<(), NetError> {
+        match self.connected {
+            true => Ok(()),
+            false => Err(anyhow!("No longer connected").into()),
+        }
+    }
+
+    /// Send every command logged from `start` onwards
+    fn send_since(&mut self, start: usize) -> Result<(), NetError> {
+        for entry in &self.game.log()[start..] {
+            if let Err(e) = send(&mut self.stream, &Message::Command(entry.clone())) {
+                self.connected = false;
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell the other player why the match is over, and stop
+    fn leave(&mut self, reason: &str) {
+        let reason = reason.to_string();
+        // The connection is being dropped either way, so there's nothing to do if this fails
+        let _ = send(&mut self.stream, &Message::Leave { reason });
+        let _ = self.stream.shutdown(Shutdown::Both);
+        self.connected = false;
+    }
+}
+
+impl Drop for NetGame {
+    fn drop(&mut self) {
+        // Stops the thread reading messages
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> Result<()> {
+    let mut line = serde_json::to_string(message).context("Could not write message")?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .context("Could not send message")
+}
+
+/// Read messages from `stream` on another thread until it closes
+fn read_messages(stream: &TcpStream) -> Result<Receiver<Result<Message>>> {
+    let reader = BufReader::new(stream.try_clone().context("Could not share connection")?);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let message = line
+                .context("Connection failed")
+                .and_then(|l| serde_json::from_str(&l).context("Could not read message"));
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                return;
+            }
+        }
+        let _ = sender.send(Err(anyhow!("The other player disconnected")));
+    });
+    Ok(receiver)
+}
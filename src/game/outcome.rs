@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+use crate::sector::{objective::ObjectiveStatus, Sector, Team};
+
+use super::turn::TurnState;
+
+/// How the match ended for one team
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Won,
+    Lost,
+    Drawn,
+}
+
+/// Work out which teams are finished, leaving out those still playing. A team loses when it has
+/// no units left or fails one of its objectives, and wins by completing any of its objectives.
+/// Everyone else loses to a winner, and teams that win at the same time draw instead.
+pub fn evaluate(sector: &Sector, turns: &TurnState) -> BTreeMap<Team, Outcome> {
+    let mut outcomes = BTreeMap::new();
+    let mut winners = Vec::new();
+    for team in turns.teams() {
+        let statuses = sector
+            .objectives()
+            .iter()
+            .filter(|o| o.team == *team)
+            .map(|o| o.status(sector, turns.turn()))
+            .collect::<Vec<_>>();
+        let eliminated = sector.units().all(|u| u.team() != *team);
+        if eliminated || statuses.contains(&ObjectiveStatus::Failed) {
+            outcomes.insert(*team, Outcome::Lost);
+        } else if statuses.contains(&ObjectiveStatus::Complete) {
+            winners.push(*team);
+        }
+    }
+    if winners.is_empty() {
+        return outcomes;
+    }
+    let winning = match winners.len() {
+        1 => Outcome::Won,
+        _ => Outcome::Drawn,
+    };
+    for team in turns.teams() {
+        let outcome = match winners.contains(team) {
+            true => winning,
+            false => Outcome::Lost,
+        };
+        outcomes.insert(*team, outcome);
+    }
+    outcomes
+}
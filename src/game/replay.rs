@@ -0,0 +1,210 @@
+use std::{fs, path::Path};
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{sector::archetype::UnitRegistry, terrain::generate_terrain};
+
+use super::{
+    history::Command,
+    lockstep::{LoggedCommand, ReplayError},
+    scenario::Scenario,
+    Game,
+};
+
+/// Version written into every replay. Replays only play back the same way with the same rules,
+/// so there's no migrating old ones: bump this whenever a change would make them play out
+/// differently.
+pub const REPLAY_VERSION: u32 = 6;
+
+/// A whole match: everything needed to generate and set it up again, and every command played
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    version: u32,
+    scenario: Scenario,
+    // What the sector's terrain was generated from
+    terrain_seed: u64,
+    // What the game's own randomness was drawn from
+    seed: u64,
+    log: Vec<LoggedCommand>,
+}
+
+impl Replay {
+    /// A replay of a match that hasn't started yet
+    pub fn new(scenario: Scenario, terrain_seed: u64, seed: u64) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            scenario,
+            terrain_seed,
+            seed,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Replay> {
+        let replay: Replay = serde_json::from_str(json).context("Could not parse replay")?;
+        replay.validate()?;
+        Ok(replay)
+    }
+
+    /// Check a replay that has just been deserialized: that it's from this version, and that its
+    /// scenario is one that could have been loaded
+    pub(super) fn validate(&self) -> Result<()> {
+        ensure!(
+            self.version == REPLAY_VERSION,
+            "Replay is from version {}, and only version {REPLAY_VERSION} can be played",
+            self.version
+        );
+        self.scenario.validate()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Could not write replay as JSON")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay> {
+        let path = path.as_ref();
+        let json =
+            fs::read_to_string(path).with_context(|| format!("Could not read replay {path:?}"))?;
+        Replay::from_json(&json).with_context(|| format!("Could not load replay {path:?}"))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_json()?)
+            .with_context(|| format!("Could not write replay to {path:?}"))
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn terrain_seed(&self) -> u64 {
+        self.terrain_seed
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn log(&self) -> &[LoggedCommand] {
+        &self.log
+    }
+
+    /// Generate the sector and set the match up, before any commands have been played
+    pub fn start(&self, unit_types: &UnitRegistry) -> Result<Game> {
+        let sector = generate_terrain(
+            self.scenario.size(),
+            self.scenario.name().to_string(),
+            self.terrain_seed,
+        );
+        self.scenario.start(sector, unit_types, self.seed)
+    }
+
+    /// Take every command played so far in `game`, which has to have been started from this
+    /// replay and not loaded from a save since
+    pub fn record(&mut self, game: &Game) {
+        self.log = game.log().to_vec();
+    }
+}
+
+/// Plays a replay back a phase at a time, in either direction. Every command is checked against
+/// the checksum it was recorded with, so a replay that no longer plays out the same way is caught
+/// at the command where it first goes wrong.
+pub struct ReplayPlayer {
+    replay: Replay,
+    game: Game,
+    // How many of the replay's commands have been applied to `game`
+    applied: usize,
+    // Which phase `game` is in, counting from 0 at the start of the match
+    phase: usize,
+    // The game as a binary save at the start of every phase reached so far, along with how many
+    // commands had been applied by then. Stepping back loads one of these instead of playing the
+    // whole match through again.
+    phase_starts: Vec<(usize, Vec<u8>)>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay, unit_types: &UnitRegistry) -> Result<Self> {
+        let game = replay.start(unit_types)?;
+        let start = game.to_binary()?;
+        Ok(Self {
+            replay,
+            game,
+            applied: 0,
+            phase: 0,
+            phase_starts: vec![(0, start)],
+        })
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn phase(&self) -> usize {
+        self.phase
+    }
+
+    /// How many of the replay's commands have been played
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    /// Whether every command in the replay has been played
+    pub fn is_finished(&self) -> bool {
+        self.applied == self.replay.log.len()
+    }
+
+    /// Play commands up to the start of the next phase, or the end of the replay if it stops
+    /// partway through one. Returns whether anything was played. A command that doesn't match its
+    /// checksum is still played, so the game can be compared against what the replay expected.
+    pub fn step_forward(&mut self) -> Result<bool, ReplayError> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+        while let Some(entry) = self.replay.log.get(self.applied) {
+            let followed = self.game.follow(self.applied, entry);
+            if let Err(error @ ReplayError::Rejected { .. }) = followed {
+                return Err(error);
+            }
+            self.applied += 1;
+            let ended_turn = entry.command == Command::EndTurn;
+            if ended_turn {
+                self.phase += 1;
+                if self.phase == self.phase_starts.len() {
+                    let start = self.game.to_binary().expect("Games can always be saved");
+                    self.phase_starts.push((self.applied, start));
+                }
+            }
+            followed?;
+            if ended_turn {
+                break;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Go back to the start of the current phase, or to the start of the previous one if already
+    /// there. Returns whether anything changed.
+    pub fn step_back(&mut self) -> bool {
+        let phase = match self.applied > self.phase_starts[self.phase].0 {
+            true => self.phase,
+            false if self.phase > 0 => self.phase - 1,
+            false => return false,
+        };
+        self.load(phase);
+        true
+    }
+
+    /// Jump to the start of `phase`, which has to have been reached before
+    fn load(&mut self, phase: usize) {
+        let (applied, start) = &self.phase_starts[phase];
+        self.game = Game::from_binary(start).expect("This build saved the game itself");
+        self.applied = *applied;
+        self.phase = phase;
+    }
+}
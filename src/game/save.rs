@@ -0,0 +1,345 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    juno::pathfinding::{Movement, Topology},
+    sector::Sector,
+};
+
+use super::{
+    history::History,
+    trigger::Trigger,
+    turn::{TurnHooks, TurnState},
+    Game,
+};
+
+/// Version written into every save. Bump this whenever the saved data changes shape, and add a
+/// step to `migrate_json` that brings the previous version up to date.
+///
+/// 1. First version
+/// 2. Units have a zone of control rule
+/// 3. Units have status effects, and can inflict one with their attacks
+/// 4. Sectors have objectives
+/// 5. Units have a footprint, and can cover more than one tile
+/// 6. Sectors have a movement topology
+/// 7. Sectors have structures, and teams have resources
+/// 8. Sectors have a roster of units to recruit, and structures count their recruits
+/// 9. Games have triggers
+/// 10. Sectors have the seed their terrain was generated from
+/// 11. Triggers remember which units are in their region
+pub const SAVE_VERSION: u32 = 11;
+
+// Binary saves start with these bytes, followed by the version as a little-endian u32
+const BINARY_MAGIC: &[u8; 4] = b"PCSV";
+
+/// How a save is written to disk. JSON is easy to read and edit while debugging, and binary is
+/// much smaller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveFormat {
+    Json,
+    Binary,
+}
+
+/// Everything needed to carry on a game exactly where it was left. Turn hooks are closures, so
+/// they aren't saved and have to be registered again after loading. The undo history and command
+/// log aren't saved either.
+#[derive(Serialize)]
+pub(super) struct SaveRef<'a> {
+    version: u32,
+    seed: u64,
+    turns: &'a TurnState,
+    // The full state of the generator, so that rolls after loading match those that would have
+    // happened without saving
+    rng: &'a ChaCha8Rng,
+    sector: &'a Sector,
+    // Along with which have fired, since that can't be worked out again
+    triggers: &'a [Trigger],
+}
+
+#[derive(Deserialize)]
+struct SaveData {
+    version: u32,
+    seed: u64,
+    turns: TurnState,
+    rng: ChaCha8Rng,
+    sector: Sector,
+    triggers: Vec<Trigger>,
+}
+
+impl Game {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(&self.save_ref()).context("Could not write save as JSON")
+    }
+
+    pub fn from_json(json: &str) -> Result<Game> {
+        let save = serde_json::from_str::<Value>(json).context("Save file isn't valid JSON")?;
+        Game::from_value(save)
+    }
+
+    /// Save as a JSON value, for saves that are part of a larger file
+    pub(super) fn to_value(&self) -> Result<Value> {
+        serde_json::to_value(self.save_ref()).context("Could not write save as JSON")
+    }
+
+    /// Load a save from a JSON value, from any version
+    pub(super) fn from_value(save: Value) -> Result<Game> {
+        let save = serde_json::from_value(migrate_json(save)?)
+            .context("Save file doesn't match the current format")?;
+        Game::from_save(save)
+    }
+
+    pub fn to_binary(&self) -> Result<Vec<u8>> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend(SAVE_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &self.save_ref())
+            .context("Could not write save as binary")?;
+        Ok(bytes)
+    }
+
+    /// Binary saves can't be picked apart field by field the way JSON can, so only saves from the
+    /// current version can be loaded. Older games can be carried forward through JSON.
+    pub fn from_binary(bytes: &[u8]) -> Result<Game> {
+        ensure!(
+            bytes.len() >= 8 && bytes.starts_with(BINARY_MAGIC),
+            "Not a binary save file"
+        );
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        check_version(version)?;
+        ensure!(
+            version == SAVE_VERSION,
+            "Binary save is from version {version}, and only version {SAVE_VERSION} can be loaded. \
+            Load it with the version that made it and save as JSON instead."
+        );
+        let save = bincode::deserialize::<SaveData>(&bytes[8..])
+            .context("Binary save file is corrupted")?;
+        Game::from_save(save)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: SaveFormat) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = match format {
+            SaveFormat::Json => self.to_json()?.into_bytes(),
+            SaveFormat::Binary => self.to_binary()?,
+        };
+        fs::write(path, bytes).with_context(|| format!("Could not write save to {path:?}"))
+    }
+
+    /// Load a save in either format, telling them apart by the binary header
+    pub fn load(path: impl AsRef<Path>) -> Result<Game> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("Could not read save {path:?}"))?;
+        let game = match bytes.starts_with(BINARY_MAGIC) {
+            true => Game::from_binary(&bytes),
+            false => std::str::from_utf8(&bytes)
+                .context("Save file is neither binary nor JSON")
+                .and_then(Game::from_json),
+        };
+        game.with_context(|| format!("Could not load save {path:?}"))
+    }
+
+    pub(super) fn save_ref(&self) -> SaveRef<'_> {
+        SaveRef {
+            version: SAVE_VERSION,
+            seed: self.seed,
+            turns: &self.turns,
+            rng: &self.rng,
+            sector: &self.sector,
+            triggers: &self.triggers,
+        }
+    }
+
+    fn from_save(save: SaveData) -> Result<Game> {
+        let SaveData {
+            version,
+            seed,
+            turns,
+            rng,
+            mut sector,
+            triggers,
+        } = save;
+        check_version(version)?;
+        ensure!(
+            turns.phase() < turns.teams().len(),
+            "Save has no team for phase {}",
+            turns.phase()
+        );
+        sector.restore()?;
+        let mut game = Game {
+            sector,
+            turns,
+            hooks: TurnHooks::default(),
+            history: History::default(),
+            outcomes: BTreeMap::new(),
+            rng,
+            seed,
+            log: Vec::new(),
+            triggers,
+            dialogue: Vec::new(),
+        };
+        game.watch_regions();
+        game.update_outcomes();
+        Ok(game)
+    }
+}
+
+fn check_version(version: u32) -> Result<()> {
+    ensure!(
+        version <= SAVE_VERSION,
+        "Save is from version {version}, which is newer than this build understands \
+        (up to version {SAVE_VERSION})"
+    );
+    ensure!(version > 0, "Save has an invalid version of 0");
+    Ok(())
+}
+
+/// Bring a JSON save from an earlier version up to `SAVE_VERSION`, one version at a time
+fn migrate_json(mut save: Value) -> Result<Value> {
+    let version = save
+        .get("version")
+        .and_then(Value::as_u64)
+        .context("Save file has no version")?;
+    let mut version = u32::try_from(version).context("Save file version is too large")?;
+    check_version(version)?;
+    while version < SAVE_VERSION {
+        match version {
+            1 => migrate_v1(&mut save)?,
+            2 => migrate_v2(&mut save)?,
+            3 => migrate_v3(&mut save)?,
+            4 => migrate_v4(&mut save)?,
+            5 => migrate_v5(&mut save)?,
+            6 => migrate_v6(&mut save)?,
+            7 => migrate_v7(&mut save)?,
+            8 => migrate_v8(&mut save)?,
+            9 => migrate_v9(&mut save)?,
+            10 => migrate_v10(&mut save)?,
+            _ => bail!("Don't know how to migrate saves from version {version}"),
+        }
+        version += 1;
+        save["version"] = version.into();
+    }
+    Ok(save)
+}
+
+/// Version 2 added zones of control, which units from before then didn't have
+fn migrate_v1(save: &mut Value) -> Result<()> {
+    for unit in saved_units(save)? {
+        unit.insert("zone_of_control".to_string(), "none".into());
+    }
+    Ok(())
+}
+
+/// Version 3 added status effects, and units from before then had none
+fn migrate_v2(save: &mut Value) -> Result<()> {
+    for unit in saved_units(save)? {
+        unit.insert("inflicts".to_string(), Value::Null);
+        unit.insert("effects".to_string(), Value::Array(Vec::new()));
+    }
+    Ok(())
+}
+
+/// Version 4 added objectives, which sectors from before then didn't have
+fn migrate_v3(save: &mut Value) -> Result<()> {
+    let sector = save
+        .get_mut("sector")
+        .and_then(Value::as_object_mut)
+        .context("Save has no sector")?;
+    sector.insert("objectives".to_string(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 5 added footprints, and every unit from before then covered a single tile
+fn migrate_v4(save: &mut Value) -> Result<()> {
+    for unit in saved_units(save)? {
+        unit.insert("footprint".to_string(), serde_json::json!({"x": 1, "y": 1}));
+    }
+    Ok(())
+}
+
+/// Version 6 made movement configurable, and sectors from before then only moved cardinally
+fn migrate_v5(save: &mut Value) -> Result<()> {
+    let sector = save
+        .get_mut("sector")
+        .and_then(Value::as_object_mut)
+        .context("Save has no sector")?;
+    let topology = serde_json::to_value(Topology::from(Movement::Cardinal))?;
+    sector.insert("topology".to_string(), topology);
+    Ok(())
+}
+
+/// Version 7 added structures and resources, and sectors from before then had neither
+fn migrate_v6(save: &mut Value) -> Result<()> {
+    let sector = save
+        .get_mut("sector")
+        .and_then(Value::as_object_mut)
+        .context("Save has no sector")?;
+    sector.insert("structures".to_string(), Value::Array(Vec::new()));
+    sector.insert("resources".to_string(), Value::Object(Map::new()));
+    Ok(())
+}
+
+/// Version 8 added recruitment, which nothing could do before then
+fn migrate_v7(save: &mut Value) -> Result<()> {
+    let sector = save
+        .get_mut("sector")
+        .and_then(Value::as_object_mut)
+        .context("Save has no sector")?;
+    sector.insert("roster".to_string(), Value::Array(Vec::new()));
+    let structures = sector
+        .get_mut("structures")
+        .and_then(Value::as_array_mut)
+        .context("Save has no structures")?;
+    for structure in structures {
+        let structure = structure
+            .as_object_mut()
+            .context("Save has an invalid structure")?;
+        structure.insert("recruited".to_string(), 0.into());
+    }
+    Ok(())
+}
+
+/// Version 9 added triggers, which games from before then didn't have
+fn migrate_v8(save: &mut Value) -> Result<()> {
+    let save = save.as_object_mut().context("Save isn't a JSON object")?;
+    save.insert("triggers".to_string(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Version 10 saved the terrain seed, which is lost for sectors from before then
+fn migrate_v9(save: &mut Value) -> Result<()> {
+    let sector = save
+        .get_mut("sector")
+        .and_then(Value::as_object_mut)
+        .context("Save has no sector")?;
+    sector.insert("terrain_seed".to_string(), Value::Null);
+    Ok(())
+}
+
+/// Version 11 remembered which units were in each trigger's region. Triggers from before then
+/// start watching their regions once the game is loaded.
+fn migrate_v10(save: &mut Value) -> Result<()> {
+    let triggers = save
+        .get_mut("triggers")
+        .and_then(Value::as_array_mut)
+        .context("Save has no triggers")?;
+    for trigger in triggers {
+        let trigger = trigger
+            .as_object_mut()
+            .context("Save has an invalid trigger")?;
+        trigger.insert("occupants".to_string(), Value::Null);
+    }
+    Ok(())
+}
+
+/// Every unit in a JSON save, for migrations that change what's stored on them
+fn saved_units(save: &mut Value) -> Result<Vec<&mut Map<String, Value>>> {
+    save.pointer_mut("/sector/units")
+        .and_then(Value::as_object_mut)
+        .context("Save has no units")?
+        .values_mut()
+        .map(|unit| unit.as_object_mut().context("Save has an invalid unit"))
+        .collect()
+}
@@ -0,0 +1,344 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use cgmath::Vector2;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    juno::pathfinding::Topology,
+    sector::{
+        archetype::UnitRegistry,
+        objective::{Goal, Objective},
+        structure::StructureKind,
+        Sector, Team,
+    },
+};
+
+use super::{
+    trigger::{Condition, Trigger, TriggerAction},
+    Game,
+};
+
+/// How a match starts: the teams taking part in turn order, the units they start with, the
+/// structures there are to fight over, what each team is trying to do, and anything scripted to
+/// happen along the way. The terrain is
+/// generated separately, at the scenario's size.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Scenario {
+    name: String,
+    size: Vector2<u32>,
+    teams: Vec<Team>,
+    units: Vec<ScenarioUnit>,
+    // Escort objectives refer to units by their index in `units`
+    #[serde(default)]
+    objectives: Vec<Objective>,
+    // How units move in this scenario
+    #[serde(default)]
+    topology: Topology,
+    #[serde(default)]
+    structures: Vec<ScenarioStructure>,
+    // IDs of the unit types that teams can recruit
+    #[serde(default)]
+    roster: Vec<String>,
+    // Conditions refer to units and structures by their index in `units` and `structures`
+    #[serde(default)]
+    triggers: Vec<Trigger<String>>,
+}
+
+/// A unit that a team starts with
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScenarioUnit {
+    #[serde(rename = "type")]
+    pub unit_type: String,
+    pub team: Team,
+    /// Where the unit would like to start. Since terrain is generated, it's placed on the closest
+    /// free tile that it can stand on.
+    pub pos: Vector2<u32>,
+}
+
+/// A structure in the sector, and who owns it from the start if anyone does
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScenarioStructure {
+    pub kind: StructureKind,
+    /// Where the structure would like to be. It goes on the closest passable tile without another
+    /// structure, or a random one if this isn't given.
+    #[serde(default)]
+    pub pos: Option<Vector2<u32>>,
+    #[serde(default)]
+    pub owner: Option<Team>,
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Scenario> {
+        let scenario: Scenario = serde_json::from_str(json).context("Could not parse scenario")?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Scenario> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Could not read scenario {path:?}"))?;
+        Scenario::from_json(&json).with_context(|| format!("Could not load scenario {path:?}"))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+
+    pub fn units(&self) -> &[ScenarioUnit] {
+        &self.units
+    }
+
+    pub fn objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn structures(&self) -> &[ScenarioStructure] {
+        &self.structures
+    }
+
+    pub fn roster(&self) -> &[String] {
+        &self.roster
+    }
+
+    pub fn triggers(&self) -> &[Trigger<String>] {
+        &self.triggers
+    }
+
+    pub(super) fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.teams.is_empty(),
+            "Scenario {} has no teams",
+            self.name
+        );
+        let mut teams = HashSet::new();
+        for team in &self.teams {
+            ensure!(
+                teams.insert(team),
+                "Scenario {} has team {} more than once",
+                self.name,
+                team.id()
+            );
+        }
+        for unit in &self.units {
+            ensure!(
+                teams.contains(&unit.team),
+                "Scenario {} has a unit for team {}, which isn't playing",
+                self.name,
+                unit.team.id()
+            );
+        }
+        for structure in &self.structures {
+            if let Some(owner) = structure.owner {
+                ensure!(
+                    teams.contains(&owner),
+                    "Scenario {} has a structure for team {}, which isn't playing",
+                    self.name,
+                    owner.id()
+                );
+            }
+        }
+        for objective in &self.objectives {
+            ensure!(
+                teams.contains(&objective.team),
+                "Scenario {} has an objective for team {}, which isn't playing",
+                self.name,
+                objective.team.id()
+            );
+            if let Goal::Escort { unit, .. } = objective.goal {
+                let Some(escorted) = self.units.get(unit.id() as usize) else {
+                    bail!(
+                        "Scenario {} escorts unit {}, which doesn't exist",
+                        self.name,
+                        unit.id()
+                    );
+                };
+                ensure!(
+                    escorted.team == objective.team,
+                    "Scenario {} has team {} escorting another team's unit",
+                    self.name,
+                    objective.team.id()
+                );
+            }
+        }
+        for trigger in &self.triggers {
+            self.validate_trigger(trigger, &teams)?;
+        }
+        Ok(())
+    }
+
+    fn validate_trigger(&self, trigger: &Trigger<String>, teams: &HashSet<&Team>) -> Result<()> {
+        let check_team = |team: &Team| {
+            ensure!(
+                teams.contains(team),
+                "Scenario {} has a trigger for team {}, which isn't playing",
+                self.name,
+                team.id()
+            );
+            Ok(())
+        };
+        let check_region = |min: Vector2<u32>, max: Vector2<u32>| {
+            ensure!(
+                min.x <= max.x && min.y <= max.y,
+                "Scenario {} has a trigger with a region's corners the wrong way around",
+                self.name
+            );
+            Ok(())
+        };
+        match trigger.condition {
+            Condition::UnitEntersRegion { min, max, team } => {
+                check_region(min, max)?;
+                team.iter().try_for_each(check_team)?;
+            }
+            Condition::TurnReached { .. } => {}
+            Condition::UnitDies { unit } => ensure!(
+                (unit.id() as usize) < self.units.len(),
+                "Scenario {} has a trigger waiting on unit {}, which doesn't exist",
+                self.name,
+                unit.id()
+            ),
+            Condition::StructureCaptured { structure, team } => {
+                ensure!(
+                    structure < self.structures.len(),
+                    "Scenario {} has a trigger waiting on structure {structure}, which doesn't \
+                    exist",
+                    self.name
+                );
+                team.iter().try_for_each(check_team)?;
+            }
+        }
+        for action in &trigger.actions {
+            match action {
+                TriggerAction::SpawnUnits { units } => {
+                    units.iter().try_for_each(|u| check_team(&u.team))?
+                }
+                TriggerAction::ChangeTiles { min, max, .. } => check_region(*min, *max)?,
+                TriggerAction::RevealMap { team } => check_team(team)?,
+                TriggerAction::ShowDialogue(_) => {}
+                TriggerAction::EndGame { winner } => winner.iter().try_for_each(check_team)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the scenario up on `sector`, which should be empty, and start playing it. Structures
+    /// without a position are scattered once the units are in place, away from them.
+    pub fn start(&self, mut sector: Sector, unit_types: &UnitRegistry, seed: u64) -> Result<Game> {
+        sector.set_topology(self.topology);
+        let roster =
+            self.roster
+                .iter()
+                .map(|id| {
+                    unit_types.get(id).cloned().with_context(|| {
+                        format!("{} recruits {id}, which doesn't exist", self.name)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+        sector.set_roster(roster)?;
+        let mut ids = Vec::new();
+        for unit in &self.units {
+            let spawned = unit_types.spawn(&unit.unit_type, unit.pos, unit.team, &sector)?;
+            let id = sector.place_unit(spawned, unit.pos).map_err(|e| {
+                anyhow!("Could not place {} in {}: {e:?}", unit.unit_type, self.name)
+            })?;
+            ids.push(id);
+        }
+        // Structures already in the sector come before the scenario's
+        let first_structure = sector.structures().len();
+        // Has its own generator, so that where structures go doesn't change any rolls in the game
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for structure in &self.structures {
+            let (kind, owner) = (structure.kind, structure.owner);
+            match structure.pos {
+                Some(pos) => sector.place_structure(kind, owner, pos),
+                None => sector.scatter_structure(kind, owner, &mut rng),
+            }
+            .with_context(|| format!("Could not set up {}", self.name))?;
+        }
+        for objective in &self.objectives {
+            let mut objective = *objective;
+            if let Goal::Escort { unit, .. } = &mut objective.goal {
+                *unit = *ids.get(unit.id() as usize).with_context(|| {
+                    format!(
+                        "{} escorts unit {}, which doesn't exist",
+                        self.name,
+                        unit.id()
+                    )
+                })?;
+            }
+            sector.add_objective(objective)?;
+        }
+        let triggers = self
+            .triggers
+            .iter()
+            .map(|trigger| {
+                let mut trigger = trigger
+                    .resolve(unit_types)
+                    .with_context(|| format!("Could not set up a trigger in {}", self.name))?;
+                match &mut trigger.condition {
+                    Condition::UnitDies { unit } => {
+                        *unit = *ids.get(unit.id() as usize).with_context(|| {
+                            format!(
+                                "{} has a trigger waiting on unit {}, which doesn't exist",
+                                self.name,
+                                unit.id()
+                            )
+                        })?
+                    }
+                    Condition::StructureCaptured { structure, .. } => *structure += first_structure,
+                    _ => {}
+                }
+                check_trigger_tiles(&trigger, &sector)
+                    .with_context(|| format!("Could not set up a trigger in {}", self.name))?;
+                Ok(trigger)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut game = Game::new(sector, self.teams.clone(), seed);
+        game.triggers = triggers;
+        game.watch_regions();
+        Ok(game)
+    }
+}
+
+/// Make sure that every tile `trigger` mentions is in `sector`
+fn check_trigger_tiles(trigger: &Trigger, sector: &Sector) -> Result<()> {
+    let mut positions = Vec::new();
+    if let Condition::UnitEntersRegion { max, .. } = trigger.condition {
+        positions.push(max);
+    }
+    for action in &trigger.actions {
+        match action {
+            TriggerAction::SpawnUnits { units } => positions.extend(units.iter().map(|u| u.pos)),
+            TriggerAction::ChangeTiles { max, .. } => positions.push(*max),
+            _ => {}
+        }
+    }
+    for pos in positions {
+        ensure!(
+            pos.x < sector.width() && pos.y < sector.height(),
+            "{pos:?} is outside of sector {}",
+            sector.name()
+        );
+    }
+    Ok(())
+}
+
+/// The skirmish played by default, built into the game from `assets/scenarios/skirmish.json`
+pub fn load_skirmish_scenario() -> Result<Scenario> {
+    Scenario::from_json(include_str!("../../assets/scenarios/skirmish.json"))
+}
@@ -0,0 +1,1669 @@
+use std::{
+    cell::RefCell,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    rc::Rc,
+    thread::{self, JoinHandle},
+};
+
+use cgmath::Vector2;
+
+use crate::{
+    ai::{Ai, UtilityWeights},
+    juno::{
+        directions::manhattan_distance,
+        pathfinding::{Movement, Topology},
+    },
+    sector::{
+        archetype::load_unit_types_json,
+        objective::{Goal, Objective},
+        status::{StatusEffect, StatusKind},
+        structure::{Structure, StructureKind},
+        tests::{spawn, spawn_with_stats, test_sector, test_stats},
+        visibility::Visibility,
+        ActionError, Team, Terrain, Unit, UnitId, ZoneOfControl,
+    },
+    terrain::generate_terrain,
+};
+
+use super::{
+    campaign::{load_frontier_campaign, Campaign, CampaignMap, CAMPAIGN_VERSION},
+    history::{Command, CommandOutcome},
+    lockstep::{LoggedCommand, ReplayError},
+    net::{Message, NetError, NetGame, PROTOCOL_VERSION},
+    outcome::Outcome,
+    replay::{Replay, ReplayPlayer, REPLAY_VERSION},
+    save::{SaveFormat, SAVE_VERSION},
+    scenario::{load_skirmish_scenario, Scenario},
+    trigger::{Condition, Dialogue, Trigger, TriggerAction},
+    Game,
+};
+
+/// Game with a unit for each team in opposite corners, returned in team order
+fn two_team_game() -> (Game, UnitId, UnitId) {
+    let mut sector = test_sector(Vector2::new(6, 6), &[]);
+    let first = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let second = spawn(&mut sector, Vector2::new(5, 5), 3., Team::new(1));
+    let game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    (game, first, second)
+}
+
+#[test]
+pub fn test_turn_order() {
+    let (mut game, _, _) = two_team_game();
+    assert_eq!(game.turns().turn(), 1);
+    assert_eq!(game.turns().active_team(), Team::new(0));
+    game.end_turn();
+    assert_eq!(game.turns().turn(), 1);
+    assert_eq!(game.turns().active_team(), Team::new(1));
+    game.end_turn();
+    assert_eq!(game.turns().turn(), 2);
+    assert_eq!(game.turns().active_team(), Team::new(0));
+}
+
+#[test]
+pub fn test_only_active_team_moves() {
+    let (mut game, first, second) = two_team_game();
+    assert_eq!(
+        game.move_unit(second, Vector2::new(5, 4)),
+        Err(ActionError::NotYourTurn)
+    );
+    assert!(game.move_unit(first, Vector2::new(0, 1)).is_ok());
+    game.end_turn();
+    assert_eq!(
+        game.move_unit(first, Vector2::new(0, 2)),
+        Err(ActionError::NotYourTurn)
+    );
+    assert!(game.move_unit(second, Vector2::new(5, 4)).is_ok());
+}
+
+#[test]
+pub fn test_movement_resets_each_turn() {
+    let (mut game, first, _) = two_team_game();
+    game.move_unit(first, Vector2::new(0, 3)).unwrap();
+    let unit = game.sector().unit(first).unwrap();
+    assert_eq!(unit.movement(), 0.);
+    game.end_turn();
+    // Still spent during the other team's phase
+    let unit = game.sector().unit(first).unwrap();
+    assert_eq!(unit.movement(), 0.);
+    game.end_turn();
+    let unit = game.sector().unit(first).unwrap();
+    assert_eq!(unit.movement(), unit.max_movement());
+    assert_eq!(unit.actions(), unit.max_actions());
+    assert!(game.move_unit(first, Vector2::new(3, 3)).is_ok());
+}
+
+#[test]
+pub fn test_turn_hooks() {
+    let (mut game, _, _) = two_team_game();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let start_calls = calls.clone();
+    game.on_turn_start(move |_, turns| {
+        start_calls
+            .borrow_mut()
+            .push(("start", turns.turn(), turns.active_team()))
+    });
+    let end_calls = calls.clone();
+    game.on_turn_end(move |_, turns| {
+        end_calls
+            .borrow_mut()
+            .push(("end", turns.turn(), turns.active_team()))
+    });
+    game.end_turn();
+    game.end_turn();
+    assert_eq!(
+        *calls.borrow(),
+        vec![
+            ("end", 1, Team::new(0)),
+            ("start", 1, Team::new(1)),
+            ("end", 1, Team::new(1)),
+            ("start", 2, Team::new(0)),
+        ]
+    );
+}
+
+#[test]
+pub fn test_only_active_team_attacks() {
+    let mut sector = test_sector(Vector2::new(2, 1), &[]);
+    let first = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let second = spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(1));
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    assert_eq!(game.attack(second, first), Err(ActionError::NotYourTurn));
+    assert!(game.attack(first, second).is_ok());
+    game.end_turn();
+    game.end_turn();
+    // Actions are restored for the next turn
+    assert!(game.attack(first, second).is_ok());
+}
+
+/// Game partway through its second turn, with some damage dealt and the map partly explored
+fn game_in_progress() -> (Game, UnitId, UnitId) {
+    let mut sector = test_sector(Vector2::new(6, 6), &[]);
+    let first = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let second = spawn(&mut sector, Vector2::new(3, 3), 3., Team::new(1));
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 42);
+    game.move_unit(first, Vector2::new(2, 1)).unwrap();
+    game.end_turn();
+    game.move_unit(second, Vector2::new(2, 2)).unwrap();
+    game.attack(second, first).unwrap();
+    (game, first, second)
+}
+
+fn assert_same_game(a: &Game, b: &Game) {
+    assert_eq!(a.turns(), b.turns());
+    assert_eq!(a.seed(), b.seed());
+    let (a, b) = (a.sector(), b.sector());
+    assert_eq!(a.name(), b.name());
+    assert_eq!(a.terrain_seed(), b.terrain_seed());
+    assert_eq!(a.size(), b.size());
+    for (x, y) in a.units().zip(b.units()) {
+        assert_eq!(x.id(), y.id());
+        assert_eq!(x.pos(), y.pos());
+        assert_eq!(x.health(), y.health());
+        assert_eq!(x.movement(), y.movement());
+        assert_eq!(x.actions(), y.actions());
+        assert_eq!(b.unit_at_tile(y.pos()).map(|u| u.id()), Some(y.id()));
+    }
+    assert_eq!(a.units().count(), b.units().count());
+    for tile in a.tiles() {
+        for team in [Team::new(0), Team::new(1)] {
+            assert_eq!(
+                a.visibility(team, tile.pos()),
+                b.visibility(team, tile.pos())
+            );
+        }
+    }
+    assert_eq!(a.structures(), b.structures());
+    for team in [Team::new(0), Team::new(1)] {
+        assert_eq!(a.resources(team), b.resources(team));
+    }
+}
+
+#[test]
+pub fn test_save_round_trip() {
+    let (game, _, _) = game_in_progress();
+    let from_json = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_same_game(&game, &from_json);
+    let binary = game.to_binary().unwrap();
+    let from_binary = Game::from_binary(&binary).unwrap();
+    assert_same_game(&game, &from_binary);
+    assert!(binary.len() < game.to_json().unwrap().len());
+}
+
+#[test]
+pub fn test_save_keeps_terrain_seed() {
+    let sector = generate_terrain(Vector2::new(10, 10), "Generated".to_string(), 7);
+    let game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    let from_json = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_eq!(from_json.sector().terrain_seed(), Some(7));
+    let from_binary = Game::from_binary(&game.to_binary().unwrap()).unwrap();
+    assert_eq!(from_binary.sector().terrain_seed(), Some(7));
+    // Sectors that weren't generated don't have one
+    let (game, _, _) = game_in_progress();
+    assert_eq!(game.sector().terrain_seed(), None);
+}
+
+#[test]
+pub fn test_loaded_game_plays_on_identically() {
+    let (mut game, first, second) = game_in_progress();
+    let mut loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    for game in [&mut game, &mut loaded] {
+        game.end_turn();
+        game.attack(first, second).unwrap();
+    }
+    assert_same_game(&game, &loaded);
+    // Navigation is rebuilt on load
+    let unit = loaded.sector().unit(first).unwrap();
+    assert_eq!(
+        unit.can_reach_tile(Vector2::new(2, 1), loaded.sector()),
+        Some(true)
+    );
+}
+
+#[test]
+pub fn test_save_versions() {
+    let (game, _, _) = game_in_progress();
+    let with_version = |version: &str| {
+        let mut save = serde_json::from_str::<serde_json::Value>(&game.to_json().unwrap()).unwrap();
+        save["version"] = serde_json::from_str(version).unwrap();
+        Game::from_json(&save.to_string())
+    };
+    let newer = with_version(&(SAVE_VERSION + 1).to_string()).err().unwrap();
+    assert!(newer.to_string().contains("newer"));
+    assert!(with_version("0").is_err());
+    assert!(with_version("null").is_err());
+
+    let mut binary = game.to_binary().unwrap();
+    binary[4..8].copy_from_slice(&(SAVE_VERSION + 1).to_le_bytes());
+    assert!(Game::from_binary(&binary).is_err());
+    assert!(Game::from_binary(b"not a save").is_err());
+    assert!(Game::from_json("{}").is_err());
+}
+
+#[test]
+pub fn test_migrate_save_from_version_1() {
+    let (game, first, _) = game_in_progress();
+    let mut save = serde_json::from_str::<serde_json::Value>(&game.to_json().unwrap()).unwrap();
+    save["version"] = 1.into();
+    save.as_object_mut().unwrap().remove("triggers");
+    for unit in save["sector"]["units"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let unit = unit.as_object_mut().unwrap();
+        for field in ["zone_of_control", "inflicts", "effects", "footprint"] {
+            unit.remove(field);
+        }
+    }
+    let sector = save["sector"].as_object_mut().unwrap();
+    for field in [
+        "objectives",
+        "topology",
+        "structures",
+        "resources",
+        "roster",
+        "terrain_seed",
+    ] {
+        sector.remove(field);
+    }
+    let loaded = Game::from_json(&save.to_string()).unwrap();
+    assert_same_game(&game, &loaded);
+    assert_eq!(
+        loaded.sector().unit(first).unwrap().zone_of_control(),
+        ZoneOfControl::None
+    );
+}
+
+#[test]
+pub fn test_load_rejects_overlapping_units() {
+    let (game, first, second) = game_in_progress();
+    let mut save = serde_json::from_str::<serde_json::Value>(&game.to_json().unwrap()).unwrap();
+    let first_pos = save["sector"]["units"][first.id().to_string()]["pos"].clone();
+    save["sector"]["units"][second.id().to_string()]["pos"] = first_pos;
+    assert!(Game::from_json(&save.to_string()).is_err());
+}
+
+#[test]
+pub fn test_save_to_file() {
+    let (game, _, _) = game_in_progress();
+    let dir = std::env::temp_dir();
+    for (name, format) in [("json", SaveFormat::Json), ("bin", SaveFormat::Binary)] {
+        let path = dir.join(format!("calamity_test_save_{}.{name}", std::process::id()));
+        game.save(&path, format).unwrap();
+        let loaded = Game::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_same_game(&game, &loaded);
+    }
+}
+
+#[test]
+pub fn test_undo_redo_move() {
+    let (mut game, first, _) = two_team_game();
+    let path = game.move_unit(first, Vector2::new(0, 2)).unwrap();
+    game.move_unit(first, Vector2::new(1, 2)).unwrap();
+    assert_eq!(
+        game.undo(),
+        Ok(Command::Move {
+            unit: first,
+            to: Vector2::new(1, 2)
+        })
+    );
+    game.undo().unwrap();
+    let unit = game.sector().unit(first).unwrap();
+    assert_eq!(unit.pos(), Vector2::new(0, 0));
+    assert_eq!(unit.movement(), 3.);
+    assert_eq!(game.sector().unit_id_at(Vector2::new(0, 0)), Some(first));
+    assert!(game.sector().unit_at_tile(Vector2::new(1, 2)).is_none());
+    assert_eq!(game.undo(), Err(ActionError::NothingToUndo));
+
+    assert_eq!(game.redo(), Ok(CommandOutcome::Moved(path)));
+    assert!(game.history().can_redo());
+    game.redo().unwrap();
+    assert_eq!(game.redo(), Err(ActionError::NothingToRedo));
+    let unit = game.sector().unit(first).unwrap();
+    assert_eq!(unit.pos(), Vector2::new(1, 2));
+    assert_eq!(unit.movement(), 0.);
+
+    // A new command means the undone ones can't be redone
+    game.undo().unwrap();
+    game.move_unit(first, Vector2::new(0, 1)).unwrap();
+    assert!(!game.history().can_redo());
+}
+
+#[test]
+pub fn test_undo_only_current_phase() {
+    let (mut game, first, _) = two_team_game();
+    game.move_unit(first, Vector2::new(0, 1)).unwrap();
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(game.undo(), Err(ActionError::NothingToUndo));
+}
+
+#[test]
+pub fn test_attacks_are_irreversible() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let first = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let second = spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(1));
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    game.apply(Command::Attack {
+        attacker: first,
+        target: second,
+    })
+    .unwrap();
+    assert!(!game.history().can_undo());
+    assert_eq!(game.undo(), Err(ActionError::Irreversible));
+}
+
+#[test]
+pub fn test_revealing_moves_are_irreversible() {
+    // A wall of forest hides the enemy until the unit steps into it
+    let mut sector = test_sector(Vector2::new(5, 3), &[]);
+    for y in 0..3 {
+        sector
+            .tile_mut(Vector2::new(2, y))
+            .unwrap()
+            .contents_mut()
+            .set_terrain(Terrain::Forest);
+    }
+    let first = spawn(&mut sector, Vector2::new(0, 1), 4., Team::new(0));
+    let second = spawn(&mut sector, Vector2::new(4, 1), 3., Team::new(1));
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    game.move_unit(first, Vector2::new(1, 1)).unwrap();
+    assert!(game.history().can_undo());
+    assert!(!game.sector().can_see(Team::new(0), Vector2::new(4, 1)));
+    // Units can see out of the forest they're standing in
+    game.move_unit(first, Vector2::new(2, 0)).unwrap();
+    assert!(game.sector().can_see(Team::new(0), Vector2::new(4, 1)));
+    assert!(game.sector().unit(second).is_some());
+    assert_eq!(game.undo(), Err(ActionError::Irreversible));
+}
+
+#[test]
+pub fn test_exploring_moves_are_irreversible() {
+    // The far end of the corridor is out of sight until the unit walks down it
+    let mut sector = test_sector(Vector2::new(16, 1), &[]);
+    let first = spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    spawn(&mut sector, Vector2::new(15, 0), 3., Team::new(1));
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    assert_eq!(
+        game.sector().visibility(Team::new(0), Vector2::new(11, 0)),
+        Visibility::Unseen
+    );
+    game.move_unit(first, Vector2::new(1, 0)).unwrap();
+    assert!(game.sector().can_see(Team::new(0), Vector2::new(11, 0)));
+    assert!(!game.sector().can_see(Team::new(0), Vector2::new(15, 0)));
+    assert_eq!(game.undo(), Err(ActionError::Irreversible));
+    // Walking back over tiles it has already seen can be undone
+    game.move_unit(first, Vector2::new(0, 0)).unwrap();
+    assert!(game.history().can_undo());
+}
+
+#[test]
+pub fn test_status_effects_tick_each_phase() {
+    let (mut game, first, second) = two_team_game();
+    let slowed = StatusEffect::new(StatusKind::Slowed, 1);
+    game.sector.apply_effect(second, slowed).unwrap();
+    game.sector.apply_effect(first, slowed).unwrap();
+    // The first team's effect counts down as its phase ends, and the second team's lasts through
+    // its next phase
+    game.end_turn();
+    assert!(game.sector().unit(first).unwrap().effects().is_empty());
+    assert_eq!(game.sector().unit(second).unwrap().movement(), 1.5);
+    // Effects are saved
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_eq!(loaded.sector().unit(second).unwrap().effects(), &[slowed]);
+    let loaded = Game::from_binary(&game.to_binary().unwrap()).unwrap();
+    assert_eq!(loaded.sector().unit(second).unwrap().max_movement(), 1.5);
+    game.end_turn();
+    assert!(game.sector().unit(second).unwrap().effects().is_empty());
+    assert_eq!(game.sector().unit(first).unwrap().movement(), 3.);
+}
+
+/// Game where both teams have a single unit next to each other, with objectives for each
+fn game_with_objectives(objectives: &[Objective], health: u32) -> (Game, UnitId, UnitId) {
+    let mut sector = test_sector(Vector2::new(6, 6), &[]);
+    let mut stats = test_stats(3.);
+    stats.max_health = health;
+    let first = spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    let second = spawn_with_stats(&mut sector, Vector2::new(1, 0), stats, Team::new(1));
+    for objective in objectives {
+        sector.add_objective(*objective).unwrap();
+    }
+    let game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    (game, first, second)
+}
+
+#[test]
+pub fn test_eliminating_enemies_wins() {
+    let eliminate = Objective {
+        team: Team::new(0),
+        goal: Goal::Eliminate,
+    };
+    let (mut game, first, second) = game_with_objectives(&[eliminate], 1);
+    assert!(!game.is_over());
+    assert_eq!(game.outcome(Team::new(0)), None);
+    game.attack(first, second).unwrap();
+    assert!(game.is_over());
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Won));
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Lost));
+    // Nothing more can happen once the match is over
+    assert_eq!(
+        game.move_unit(first, Vector2::new(0, 1)),
+        Err(ActionError::GameOver)
+    );
+    assert_eq!(game.apply(Command::EndTurn), Err(ActionError::GameOver));
+    game.end_turn();
+    assert_eq!(game.turns().active_team(), Team::new(0));
+}
+
+#[test]
+pub fn test_eliminated_team_loses() {
+    // Without an objective of its own, the first team doesn't win by eliminating the second
+    let survive = Objective {
+        team: Team::new(1),
+        goal: Goal::Survive { turns: 5 },
+    };
+    let (mut game, first, second) = game_with_objectives(&[survive], 1);
+    game.attack(first, second).unwrap();
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Lost));
+    assert_eq!(game.outcome(Team::new(0)), None);
+    assert!(!game.is_over());
+}
+
+#[test]
+pub fn test_survive_and_draw() {
+    let survive = |team, turns| Objective {
+        team: Team::new(team),
+        goal: Goal::Survive { turns },
+    };
+    let (mut game, _, _) = game_with_objectives(&[survive(1, 2)], 10);
+    for _ in 0..3 {
+        game.end_turn();
+    }
+    assert_eq!(game.turns().turn(), 2);
+    assert!(!game.is_over());
+    game.end_turn();
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Won));
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Lost));
+    // Teams that finish their objectives at the same time draw
+    let (mut game, _, _) = game_with_objectives(&[survive(0, 1), survive(1, 1)], 10);
+    game.end_turn();
+    game.end_turn();
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Drawn));
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Drawn));
+}
+
+#[test]
+pub fn test_escort() {
+    let (mut game, first, second) = game_with_objectives(&[], 10);
+    let escort = Objective {
+        team: Team::new(0),
+        goal: Goal::Escort {
+            unit: first,
+            to: Vector2::new(0, 3),
+        },
+    };
+    game.sector.add_objective(escort).unwrap();
+    // Reaching the tile wins straight away, and can't be undone
+    game.move_unit(first, Vector2::new(0, 3)).unwrap();
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Won));
+    assert_eq!(game.undo(), Err(ActionError::Irreversible));
+    // Losing the escorted unit loses the match
+    let (mut game, first, _) = game_with_objectives(&[], 1);
+    game.sector.add_objective(escort).unwrap();
+    game.end_turn();
+    game.attack(second, first).unwrap();
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Lost));
+}
+
+#[test]
+pub fn test_hold_region_wins() {
+    let hold = Objective {
+        team: Team::new(1),
+        goal: Goal::HoldRegion {
+            min: Vector2::new(4, 4),
+            max: Vector2::new(5, 5),
+            turns: 2,
+            held: 0,
+        },
+    };
+    let (mut game, _, second) = game_with_objectives(&[hold], 10);
+    let steps = [Vector2::new(4, 0), Vector2::new(4, 3), Vector2::new(4, 4)];
+    for to in steps {
+        game.end_turn();
+        game.move_unit(second, to).unwrap();
+        game.end_turn();
+    }
+    assert!(!game.is_over());
+    game.end_turn();
+    game.end_turn();
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Won));
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Lost));
+    // Progress is saved
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_eq!(loaded.sector().objectives(), game.sector().objectives());
+    assert!(loaded.is_over());
+}
+
+#[test]
+pub fn test_scenario() {
+    let json = r#"{
+        "name": "Test scenario",
+        "size": {"x": 6, "y": 6},
+        "teams": [1, 0],
+        "units": [
+            {"type": "infantry", "team": 0, "pos": {"x": 0, "y": 0}},
+            {"type": "infantry", "team": 0, "pos": {"x": 0, "y": 0}},
+            {"type": "cavalry", "team": 1, "pos": {"x": 2, "y": 2}}
+        ],
+        "objectives": [
+            {"team": 0, "goal": {"escort": {"unit": 1, "to": {"x": 5, "y": 5}}}},
+            {"team": 1, "goal": {"survive": {"turns": 10}}}
+        ]
+    }"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    let mut sector = test_sector(Vector2::new(6, 6), &[Vector2::new(1, 0)]);
+    sector
+        .tile_mut(Vector2::new(2, 2))
+        .unwrap()
+        .contents_mut()
+        .set_terrain(Terrain::Mountain);
+    let registry = load_unit_types_json().unwrap();
+    let spare = spawn(&mut sector, Vector2::new(5, 0), 1., Team::new(0));
+    let game = scenario.start(sector, &registry, 3).unwrap();
+    assert_eq!(game.turns().teams(), &[Team::new(1), Team::new(0)]);
+    assert_eq!(game.seed(), 3);
+    let units = game
+        .sector()
+        .units()
+        .filter(|u| u.id() != spare)
+        .map(|u| (u.archetype().unwrap(), u.pos()))
+        .collect::<Vec<_>>();
+    // Units that can't start where they asked to go to the closest place they can stand
+    assert_eq!(
+        units,
+        vec![
+            ("infantry", Vector2::new(0, 0)),
+            ("infantry", Vector2::new(0, 1)),
+            ("cavalry", Vector2::new(2, 1)),
+        ]
+    );
+    // Escorts refer to units by where they are in the scenario
+    let escorted = game.sector().unit_at_tile(Vector2::new(0, 1)).unwrap().id();
+    assert!(matches!(
+        game.sector().objectives()[0].goal,
+        Goal::Escort { unit, .. } if unit == escorted
+    ));
+
+    let invalid = |from: &str, to: &str| Scenario::from_json(&json.replacen(from, to, 1));
+    assert!(invalid(r#""teams": [1, 0]"#, r#""teams": []"#).is_err());
+    assert!(invalid(r#""teams": [1, 0]"#, r#""teams": [1, 1]"#).is_err());
+    assert!(invalid(r#""team": 1, "goal""#, r#""team": 2, "goal""#).is_err());
+    assert!(invalid(r#""unit": 1"#, r#""unit": 3"#).is_err());
+    assert!(invalid(r#""unit": 1"#, r#""unit": 2"#).is_err());
+    load_skirmish_scenario().unwrap();
+}
+
+#[test]
+pub fn test_scenario_topology() {
+    let json = r#"{
+        "name": "Diagonal scenario",
+        "size": {"x": 4, "y": 4},
+        "teams": [0],
+        "units": [],
+        "topology": {"movement": "alternating_diagonal", "cut_corners": false}
+    }"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    let topology = Topology {
+        movement: Movement::AlternatingDiagonal,
+        cut_corners: false,
+    };
+    assert_eq!(scenario.topology(), topology);
+    let sector = test_sector(Vector2::new(4, 4), &[]);
+    let game = scenario
+        .start(sector, &load_unit_types_json().unwrap(), 0)
+        .unwrap();
+    assert_eq!(game.sector().topology(), topology);
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_eq!(loaded.sector().topology(), topology);
+    // Scenarios without one keep to cardinal movement
+    assert_eq!(
+        load_skirmish_scenario().unwrap().topology(),
+        Topology::from(Movement::Cardinal)
+    );
+}
+
+#[test]
+pub fn test_scenario_structures() {
+    let json = r#"{
+        "name": "Structure scenario",
+        "size": {"x": 6, "y": 6},
+        "teams": [0, 1],
+        "units": [
+            {"type": "infantry", "team": 0, "pos": {"x": 1, "y": 1}},
+            {"type": "infantry", "team": 1, "pos": {"x": 5, "y": 5}}
+        ],
+        "structures": [
+            {"kind": "village", "pos": {"x": 1, "y": 1}},
+            {"kind": "fort", "pos": {"x": 1, "y": 1}, "owner": 1},
+            {"kind": "shrine"}
+        ]
+    }"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    let registry = load_unit_types_json().unwrap();
+    let start = |seed| {
+        let sector = test_sector(Vector2::new(6, 6), &[]);
+        scenario.start(sector, &registry, seed).unwrap()
+    };
+    let mut game = start(0);
+    let structures = game.sector().structures().to_vec();
+    // Structures that can't go where they asked to go to the closest free tile
+    assert_eq!(structures[0].pos, Vector2::new(1, 1));
+    assert_eq!(manhattan_distance(structures[1].pos, Vector2::new(1, 1)), 1);
+    assert_eq!(structures[1].owner, Some(Team::new(1)));
+    // Scattered structures keep clear of units, and land in the same place every time
+    assert!(game.sector().unit_at_tile(structures[2].pos).is_none());
+    assert_eq!(start(0).sector().structures(), structures.as_slice());
+
+    // Captured at the end of the phase, and paying out from the start of the next
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(
+        game.sector()
+            .structure_at(Vector2::new(1, 1))
+            .unwrap()
+            .owner,
+        Some(Team::new(0))
+    );
+    assert_eq!(game.sector().resources(Team::new(0)), 0);
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(game.sector().resources(Team::new(0)), 2);
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_same_game(&game, &loaded);
+    assert_eq!(game.checksum(), loaded.checksum());
+
+    let invalid = json.replacen(r#""owner": 1"#, r#""owner": 2"#, 1);
+    assert!(Scenario::from_json(&invalid).is_err());
+    // Needs a passable tile to go on
+    let water = (0..6)
+        .flat_map(|x| (0..6).map(move |y| Vector2::new(x, y)))
+        .filter(|pos| *pos != Vector2::new(1, 1) && *pos != Vector2::new(5, 5))
+        .collect::<Vec<_>>();
+    let sector = test_sector(Vector2::new(6, 6), &water);
+    assert!(scenario.start(sector, &registry, 0).is_err());
+}
+
+#[test]
+pub fn test_recruit_command() {
+    let json = r#"{
+        "name": "Recruit scenario",
+        "size": {"x": 8, "y": 8},
+        "teams": [0, 1],
+        "units": [
+            {"type": "infantry", "team": 0, "pos": {"x": 0, "y": 0}},
+            {"type": "infantry", "team": 1, "pos": {"x": 7, "y": 7}}
+        ],
+        "structures": [
+            {"kind": "fort", "pos": {"x": 2, "y": 2}, "owner": 0},
+            {"kind": "village", "pos": {"x": 0, "y": 5}, "owner": 0}
+        ],
+        "roster": ["infantry", "archer"]
+    }"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    let registry = load_unit_types_json().unwrap();
+    let start = || {
+        let sector = test_sector(Vector2::new(8, 8), &[]);
+        scenario.start(sector, &registry, 0).unwrap()
+    };
+    let mut game = start();
+    let recruit = |unit_type: &str, to| Command::Recruit {
+        structure: Vector2::new(2, 2),
+        unit_type: unit_type.to_string(),
+        to,
+    };
+    // The village has paid out once, at the start of the first phase
+    assert_eq!(game.sector().resources(Team::new(0)), 2);
+    assert_eq!(
+        game.apply(recruit("infantry", Vector2::new(2, 3))),
+        Err(ActionError::NotEnoughResources)
+    );
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(
+        game.apply(recruit("infantry", Vector2::new(2, 3))),
+        Err(ActionError::NoRecruiter)
+    );
+    game.apply(Command::EndTurn).unwrap();
+
+    let Ok(CommandOutcome::Recruited(unit)) = game.apply(recruit("infantry", Vector2::new(2, 3)))
+    else {
+        panic!("Should be able to recruit");
+    };
+    assert_eq!(game.sector().resources(Team::new(0)), 0);
+    game.apply(Command::Undo).unwrap();
+    assert!(game.sector().unit(unit).is_none());
+    assert_eq!(game.sector().resources(Team::new(0)), 4);
+    game.apply(Command::Redo).unwrap();
+    assert_eq!(game.sector().resources(Team::new(0)), 0);
+    assert_eq!(
+        game.sector()
+            .unit_at_tile(Vector2::new(2, 3))
+            .unwrap()
+            .archetype(),
+        Some("infantry")
+    );
+
+    // The roster is saved, and the whole match plays out the same way again
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_same_game(&game, &loaded);
+    assert_eq!(loaded.sector().roster().len(), 2);
+    let mut replayed = start();
+    replayed.replay(game.log()).unwrap();
+    assert_eq!(replayed.checksum(), game.checksum());
+
+    let unknown = json.replacen(r#""archer""#, r#""dragon""#, 1);
+    let sector = test_sector(Vector2::new(8, 8), &[]);
+    assert!(Scenario::from_json(&unknown)
+        .unwrap()
+        .start(sector, &registry, 0)
+        .is_err());
+}
+
+#[test]
+pub fn test_load_rejects_stacked_structures() {
+    let (game, first, _) = two_team_game();
+    let pos = game.sector().unit(first).unwrap().pos();
+    let mut save = serde_json::from_str::<serde_json::Value>(&game.to_json().unwrap()).unwrap();
+    let village = serde_json::to_value(Structure::new(StructureKind::Village, pos)).unwrap();
+    save["sector"]["structures"] = serde_json::json!([village]);
+    assert!(Game::from_json(&save.to_string()).is_ok());
+    save["sector"]["structures"] = serde_json::json!([village, village]);
+    assert!(Game::from_json(&save.to_string()).is_err());
+}
+
+#[test]
+pub fn test_scenario_triggers() {
+    let json = r#"{
+        "name": "Trigger scenario",
+        "size": {"x": 10, "y": 10},
+        "teams": [0, 1],
+        "units": [
+            {"type": "infantry", "team": 0, "pos": {"x": 0, "y": 0}},
+            {"type": "infantry", "team": 1, "pos": {"x": 9, "y": 9}},
+            {"type": "infantry", "team": 1, "pos": {"x": 9, "y": 7}}
+        ],
+        "structures": [
+            {"kind": "village", "pos": {"x": 2, "y": 0}}
+        ],
+        "triggers": [
+            {
+                "condition": {"unit_enters_region": {
+                    "min": {"x": 2, "y": 0}, "max": {"x": 3, "y": 9}, "team": 0
+                }},
+                "actions": [
+                    {"show_dialogue": {"speaker": "Scout", "text": "The river's rising!"}},
+                    {"reveal_map": {"team": 0}},
+                    {"change_tiles": {
+                        "min": {"x": 5, "y": 0}, "max": {"x": 5, "y": 2}, "terrain": "water"
+                    }}
+                ]
+            },
+            {
+                "condition": {"structure_captured": {"structure": 0, "team": 0}},
+                "actions": [{"show_dialogue": {"text": "The village is ours."}}]
+            },
+            {
+                "condition": {"turn_reached": {"turn": 2}},
+                "actions": [{"spawn_units": {"units": [
+                    {"type": "cavalry", "team": 1, "pos": {"x": 9, "y": 0}}
+                ]}}]
+            },
+            {
+                "condition": {"unit_dies": {"unit": 2}},
+                "actions": [{"end_game": {"winner": 0}}]
+            }
+        ]
+    }"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    let registry = load_unit_types_json().unwrap();
+    let start = || {
+        let sector = test_sector(Vector2::new(10, 10), &[]);
+        scenario.start(sector, &registry, 0).unwrap()
+    };
+    let mut game = start();
+    let infantry = game.sector().unit_at_tile(Vector2::new(0, 0)).unwrap().id();
+    // Units are referred to by where they are in the scenario
+    let guard = game.sector().unit_at_tile(Vector2::new(9, 7)).unwrap().id();
+    assert!(matches!(
+        game.triggers()[3].condition,
+        Condition::UnitDies { unit } if unit == guard
+    ));
+    let far = Vector2::new(9, 9);
+    assert_eq!(
+        game.sector().visibility(Team::new(0), far),
+        Visibility::Unseen
+    );
+
+    let to = Vector2::new(2, 0);
+    game.apply(Command::Move { unit: infantry, to }).unwrap();
+    assert_eq!(
+        game.dialogue(),
+        &[Dialogue {
+            speaker: Some("Scout".to_string()),
+            text: "The river's rising!".to_string(),
+        }]
+    );
+    // The whole map is known, but enemies out of sight stay hidden
+    assert_eq!(
+        game.sector().visibility(Team::new(0), far),
+        Visibility::Explored
+    );
+    assert_eq!(game.sector().visible_units(Team::new(0)).count(), 1);
+    for y in 0..3 {
+        let tile = game.sector().tile(Vector2::new(5, y)).unwrap();
+        assert_eq!(tile.contents().terrain(), Terrain::Water);
+    }
+    assert_eq!(game.apply(Command::Undo), Err(ActionError::Irreversible));
+
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(game.dialogue().len(), 2);
+    game.apply(Command::EndTurn).unwrap();
+    let spawned = game.sector().unit_at_tile(Vector2::new(9, 0)).unwrap();
+    assert_eq!(spawned.archetype(), Some("cavalry"));
+    assert_eq!(spawned.team(), Team::new(1));
+    // Triggers only ever fire once
+    let to = Vector2::new(3, 0);
+    game.apply(Command::Move { unit: infantry, to }).unwrap();
+    assert_eq!(game.dialogue().len(), 2);
+    assert_eq!(game.sector().units().count(), 4);
+    let fired = |game: &Game| game.triggers().iter().map(|t| t.fired).collect::<Vec<_>>();
+    assert_eq!(fired(&game), vec![true, true, true, false]);
+
+    // Which triggers have fired is saved, and the whole match plays out the same way again
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_same_game(&game, &loaded);
+    assert_eq!(fired(&loaded), fired(&game));
+    let mut replayed = start();
+    replayed.replay(game.log()).unwrap();
+    assert_eq!(replayed.checksum(), game.checksum());
+    assert_eq!(replayed.dialogue(), game.dialogue());
+
+    let invalid = |from: &str, to: &str| Scenario::from_json(&json.replacen(from, to, 1));
+    assert!(invalid(r#""max": {"x": 3, "y": 9}"#, r#""max": {"x": 1, "y": 9}"#).is_err());
+    assert!(invalid(r#""structure": 0"#, r#""structure": 1"#).is_err());
+    assert!(invalid(r#""unit": 2"#, r#""unit": 3"#).is_err());
+    assert!(invalid(
+        r#""reveal_map": {"team": 0}"#,
+        r#""reveal_map": {"team": 2}"#
+    )
+    .is_err());
+    assert!(invalid(r#""winner": 0"#, r#""winner": 2"#).is_err());
+    // Unit types and tiles can only be checked against the registry and sector
+    for (from, to) in [
+        (r#""type": "cavalry""#, r#""type": "dragon""#),
+        (r#""x": 9, "y": 0"#, r#""x": 10, "y": 0"#),
+    ] {
+        let scenario = invalid(from, to).unwrap();
+        let sector = test_sector(Vector2::new(10, 10), &[]);
+        assert!(scenario.start(sector, &registry, 0).is_err());
+    }
+}
+
+#[test]
+pub fn test_triggers_end_the_game() {
+    let (mut game, first, second) = game_with_objectives(&[], 1);
+    game.triggers = vec![Trigger {
+        condition: Condition::UnitDies { unit: second },
+        actions: vec![TriggerAction::EndGame { winner: None }],
+        fired: false,
+        occupants: None,
+    }];
+    game.apply(Command::Attack {
+        attacker: first,
+        target: second,
+    })
+    .unwrap();
+    // Teams that had already finished keep their outcome
+    assert!(game.is_over());
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Drawn));
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Lost));
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_eq!(loaded.outcomes(), game.outcomes());
+
+    let (mut game, _, _) = two_team_game();
+    game.triggers = vec![Trigger {
+        condition: Condition::TurnReached { turn: 2 },
+        actions: vec![TriggerAction::EndGame {
+            winner: Some(Team::new(1)),
+        }],
+        fired: false,
+        occupants: None,
+    }];
+    game.apply(Command::EndTurn).unwrap();
+    assert!(!game.is_over());
+    game.apply(Command::EndTurn).unwrap();
+    assert_eq!(game.outcome(Team::new(0)), Some(Outcome::Lost));
+    assert_eq!(game.outcome(Team::new(1)), Some(Outcome::Won));
+    assert_eq!(game.apply(Command::EndTurn), Err(ActionError::GameOver));
+    // Worked out again from the triggers after loading
+    let loaded = Game::from_json(&game.to_json().unwrap()).unwrap();
+    assert_eq!(loaded.outcomes(), game.outcomes());
+}
+
+#[test]
+pub fn test_region_triggers_fire_on_entry() {
+    // The first team's unit starts in the region, so it has to leave and come back
+    let (mut game, first, _) = two_team_game();
+    game.triggers = vec![Trigger {
+        condition: Condition::UnitEntersRegion {
+            min: Vector2::new(0, 0),
+            max: Vector2::new(1, 1),
+            team: Some(Team::new(0)),
+        },
+        actions: vec![TriggerAction::ShowDialogue(Dialogue {
+            speaker: None,
+            text: "Back again".to_string(),
+        })],
+        fired: false,
+        occupants: None,
+    }];
+    game.watch_regions();
+    let move_to = |x, y| Command::Move {
+        unit: first,
+        to: Vector2::new(x, y),
+    };
+    game.apply(move_to(1, 1)).unwrap();
+    assert!(game.dialogue().is_empty());
+    // Which units are in the region is saved
+    let mut game = Game::from_json(&game.to_json().unwrap()).unwrap();
+    game.apply(move_to(1, 2)).unwrap();
+    assert!(game.dialogue().is_empty());
+    game.apply(Command::EndTurn).unwrap();
+    game.apply(Command::EndTurn).unwrap();
+    game.apply(move_to(0, 1)).unwrap();
+    assert_eq!(game.dialogue().len(), 1);
+    assert!(game.triggers()[0].fired);
+}
+
+#[test]
+pub fn test_trigger_tiles_keep_units_standing() {
+    let registry = load_unit_types_json().unwrap();
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    for (unit_type, pos, team) in [
+        ("cavalry", Vector2::new(0, 0), Team::new(0)),
+        ("infantry", Vector2::new(1, 0), Team::new(0)),
+        ("infantry", Vector2::new(3, 3), Team::new(1)),
+    ] {
+        let unit = registry.spawn(unit_type, pos, team, &sector).unwrap();
+        sector.add_unit(unit).unwrap();
+    }
+    let mut game = Game::new(sector, vec![Team::new(0), Team::new(1)], 0);
+    game.triggers = vec![Trigger {
+        condition: Condition::TurnReached { turn: 1 },
+        actions: vec![TriggerAction::ChangeTiles {
+            min: Vector2::new(0, 0),
+            max: Vector2::new(2, 0),
+            terrain: Terrain::Mountain,
+            sprite: None,
+        }],
+        fired: false,
+        occupants: None,
+    }];
+    game.apply(Command::EndTurn).unwrap();
+    let terrain = |x| {
+        let tile = game.sector().tile(Vector2::new(x, 0)).unwrap();
+        tile.contents().terrain()
+    };
+    // Cavalry can't stand on mountains, but infantry can
+    assert_eq!(terrain(0), Terrain::Grass);
+    assert_eq!(terrain(1), Terrain::Mountain);
+    assert_eq!(terrain(2), Terrain::Mountain);
+}
+
+/// Small battle with a few units on each side, and the same terrain every time
+fn skirmish(seed: u64) -> Game {
+    let mut sector = test_sector(Vector2::new(8, 8), &[Vector2::new(3, 4)]);
+    sector
+        .tile_mut(Vector2::new(4, 3))
+        .unwrap()
+        .contents_mut()
+        .set_terrain(Terrain::Forest);
+    for x in 0..3 {
+        spawn(&mut sector, Vector2::new(x, 0), 3., Team::new(0));
+        spawn(&mut sector, Vector2::new(7 - x, 7), 3., Team::new(1));
+    }
+    Game::new(sector, vec![Team::new(0), Team::new(1)], seed)
+}
+
+/// Have two AIs play `turns` turns of `game`
+fn play_ais(game: &mut Game, turns: u32) {
+    let mut ais = game
+        .turns()
+        .teams()
+        .iter()
+        .map(|team| Ai::new(*team, UtilityWeights::default(), team.id() as u64))
+        .collect::<Vec<_>>();
+    for _ in 0..turns {
+        for ai in &mut ais {
+            ai.take_turn(game);
+        }
+    }
+}
+
+#[test]
+pub fn test_checksum() {
+    // Games built separately but in the same way match
+    let (mut first, _, _) = game_in_progress();
+    let (second, _, _) = game_in_progress();
+    assert_eq!(first.checksum(), second.checksum());
+    // Saving doesn't lose anything that matters
+    let from_json = Game::from_json(&first.to_json().unwrap()).unwrap();
+    let from_binary = Game::from_binary(&first.to_binary().unwrap()).unwrap();
+    assert_eq!(from_json.checksum(), first.checksum());
+    assert_eq!(from_binary.checksum(), first.checksum());
+    // Every command that changes something changes the checksum
+    let before = first.checksum();
+    first.apply(Command::EndTurn).unwrap();
+    assert_ne!(first.checksum(), before);
+    assert_eq!(first.log().last().unwrap().checksum, first.checksum());
+    // Rejected commands change nothing, and aren't logged
+    let before = (first.checksum(), first.log().len());
+    assert!(first.apply(Command::Undo).is_err());
+    assert_eq!((first.checksum(), first.log().len()), before);
+}
+
+#[test]
+pub fn test_replay() {
+    let mut game = skirmish(5);
+    let unit = game.sector().unit_at_tile(Vector2::new(0, 0)).unwrap().id();
+    let to = Vector2::new(0, 1);
+    for command in [Command::Move { unit, to }, Command::Undo, Command::Redo] {
+        game.apply(command).unwrap();
+    }
+    play_ais(&mut game, 6);
+    let log = game.log().to_vec();
+    assert!(log
+        .iter()
+        .any(|e| matches!(e.command, Command::Attack { .. })));
+    let mut replayed = skirmish(5);
+    assert_eq!(replayed.replay(&log), Ok(()));
+    assert_eq!(replayed.checksum(), game.checksum());
+    assert_eq!(replayed.log(), game.log());
+}
+
+#[test]
+pub fn test_replay_detects_desync() {
+    let mut game = skirmish(5);
+    play_ais(&mut game, 3);
+    let mut log = game.log().to_vec();
+    // A different seed rolls differently
+    let mut replayed = skirmish(6);
+    assert!(matches!(
+        replayed.replay(&log),
+        Err(ReplayError::Desync { index: 0, .. })
+    ));
+    log[4].checksum ^= 1;
+    let mut replayed = skirmish(5);
+    assert_eq!(
+        replayed.replay(&log),
+        Err(ReplayError::Desync {
+            index: 4,
+            expected: log[4].checksum,
+            actual: log[4].checksum ^ 1,
+        })
+    );
+    // Commands that can't be applied
+    let mut replayed = skirmish(5);
+    let enemy = replayed
+        .sector()
+        .unit_at_tile(Vector2::new(7, 7))
+        .unwrap()
+        .id();
+    let invalid = LoggedCommand {
+        command: Command::Move {
+            unit: enemy,
+            to: Vector2::new(7, 6),
+        },
+        checksum: 0,
+    };
+    assert_eq!(
+        replayed.replay(&[invalid]),
+        Err(ReplayError::Rejected {
+            index: 0,
+            error: ActionError::NotYourTurn
+        })
+    );
+}
+
+/// Small scenario for two teams, to be played on generated terrain
+fn generated_scenario() -> Scenario {
+    let json = r#"{
+        "name": "Replay test",
+        "size": {"x": 20, "y": 20},
+        "teams": [0, 1],
+        "units": [
+            {"type": "infantry", "team": 0, "pos": {"x": 8, "y": 8}},
+            {"type": "archer", "team": 0, "pos": {"x": 7, "y": 8}},
+            {"type": "infantry", "team": 1, "pos": {"x": 11, "y": 11}},
+            {"type": "archer", "team": 1, "pos": {"x": 12, "y": 11}}
+        ]
+    }"#;
+    Scenario::from_json(json).unwrap()
+}
+
+/// Recording of a short match between two computer players on generated terrain
+fn recorded_match(phases: u32) -> Replay {
+    let mut replay = Replay::new(generated_scenario(), 4, 9);
+    let mut game = replay.start(&load_unit_types_json().unwrap()).unwrap();
+    play_ais(&mut game, phases / 2);
+    if phases % 2 == 1 {
+        game.apply(Command::EndTurn).unwrap();
+    }
+    replay.record(&game);
+    replay
+}
+
+#[test]
+pub fn test_replay_file() {
+    let replay = recorded_match(4);
+    assert_eq!(
+        replay
+            .log()
+            .iter()
+            .filter(|e| e.command == Command::EndTurn)
+            .count(),
+        4
+    );
+    let loaded = Replay::from_json(&replay.to_json().unwrap()).unwrap();
+    assert_eq!(loaded.log(), replay.log());
+    assert_eq!(
+        (loaded.terrain_seed(), loaded.seed()),
+        (replay.terrain_seed(), replay.seed())
+    );
+    // Playing the loaded replay through ends up at the same game
+    let mut game = loaded.start(&load_unit_types_json().unwrap()).unwrap();
+    assert_eq!(game.replay(loaded.log()), Ok(()));
+    assert_eq!(game.checksum(), replay.log().last().unwrap().checksum);
+
+    let path = std::env::temp_dir().join("project_calamity_test_replay.json");
+    replay.save(&path).unwrap();
+    assert_eq!(Replay::load(&path).unwrap().log(), replay.log());
+    std::fs::remove_file(&path).unwrap();
+    let json = replay.to_json().unwrap().replacen(
+        &format!(r#""version":{REPLAY_VERSION}"#),
+        &format!(r#""version":{}"#, REPLAY_VERSION + 1),
+        1,
+    );
+    assert!(Replay::from_json(&json).is_err());
+}
+
+#[test]
+pub fn test_replay_rejects_invalid_scenario() {
+    let mut json = serde_json::to_value(recorded_match(0)).unwrap();
+    json["scenario"]["objectives"] = serde_json::json!([
+        {"team": 0, "goal": {"escort": {"unit": 9, "to": {"x": 0, "y": 0}}}}
+    ]);
+    assert!(Replay::from_json(&json.to_string()).is_err());
+    // Even if it gets past loading, it can't be started
+    let replay = serde_json::from_value::<Replay>(json).unwrap();
+    assert!(replay.start(&load_unit_types_json().unwrap()).is_err());
+}
+
+#[test]
+pub fn test_replay_rejects_invalid_trigger() {
+    let mut json = serde_json::to_value(recorded_match(0)).unwrap();
+    json["scenario"]["triggers"] = serde_json::json!([
+        {"condition": {"unit_dies": {"unit": 9}}, "actions": []}
+    ]);
+    assert!(Replay::from_json(&json.to_string()).is_err());
+    let replay = serde_json::from_value::<Replay>(json).unwrap();
+    assert!(replay.start(&load_unit_types_json().unwrap()).is_err());
+}
+
+#[test]
+pub fn test_replay_player() {
+    let replay = recorded_match(3);
+    // Checksums at the start of every phase, and at the very end
+    let mut phase_starts = vec![];
+    for (i, entry) in replay.log().iter().enumerate() {
+        if entry.command == Command::EndTurn {
+            phase_starts.push((i + 1, entry.checksum));
+        }
+    }
+    let units = load_unit_types_json().unwrap();
+    let mut player = ReplayPlayer::new(replay.clone(), &units).unwrap();
+    let start = player.game().checksum();
+    assert!(!player.step_back());
+    for (phase, (applied, checksum)) in phase_starts.iter().enumerate() {
+        assert_eq!(player.step_forward(), Ok(true));
+        assert_eq!(player.phase(), phase + 1);
+        assert_eq!(player.applied(), *applied);
+        assert_eq!(player.game().checksum(), *checksum);
+    }
+    assert!(player.is_finished());
+    assert_eq!(player.step_forward(), Ok(false));
+
+    // Back through every phase to the start, then forward again
+    for (applied, checksum) in phase_starts.iter().rev().skip(1) {
+        assert!(player.step_back());
+        assert_eq!(player.applied(), *applied);
+        assert_eq!(player.game().checksum(), *checksum);
+    }
+    assert!(player.step_back());
+    assert_eq!((player.phase(), player.applied()), (0, 0));
+    assert_eq!(player.game().checksum(), start);
+    while player.step_forward().unwrap() {}
+    assert_eq!(player.game().checksum(), phase_starts[2].1);
+
+    // A replay that stops partway through a phase steps back to that phase's start first
+    let full = recorded_match(4);
+    let cut = phase_starts[1].0 + 1;
+    assert_ne!(full.log()[cut - 1].command, Command::EndTurn);
+    let mut game = full.start(&units).unwrap();
+    game.replay(&full.log()[..cut]).unwrap();
+    let mut partial = full.clone();
+    partial.record(&game);
+    let mut player = ReplayPlayer::new(partial, &units).unwrap();
+    while player.step_forward().unwrap() {}
+    assert_eq!((player.phase(), player.applied()), (2, cut));
+    assert!(player.step_back());
+    assert_eq!((player.phase(), player.applied()), (2, cut - 1));
+    assert!(player.step_back());
+    assert_eq!(player.phase(), 1);
+}
+
+#[test]
+pub fn test_replay_player_desync() {
+    let mut replay = recorded_match(2);
+    let first_end = replay
+        .log()
+        .iter()
+        .position(|e| e.command == Command::EndTurn)
+        .unwrap();
+    let json = replay.to_json().unwrap();
+    let checksum = replay.log()[first_end].checksum;
+    let json = json.replacen(
+        &format!(r#""checksum":{checksum}"#),
+        &format!(r#""checksum":{}"#, checksum ^ 1),
+        1,
+    );
+    replay = Replay::from_json(&json).unwrap();
+    let mut player = ReplayPlayer::new(replay, &load_unit_types_json().unwrap()).unwrap();
+    assert_eq!(
+        player.step_forward(),
+        Err(ReplayError::Desync {
+            index: first_end,
+            expected: checksum ^ 1,
+            actual: checksum,
+        })
+    );
+    // The mismatched command is still played
+    assert_eq!((player.phase(), player.applied()), (1, first_end + 1));
+    assert_eq!(player.game().checksum(), checksum);
+}
+
+/// Host a match of `generated_scenario` on another thread, and run `host` there once someone
+/// joins. Returns the address to join.
+fn host_match<T: Send + 'static>(
+    host: impl FnOnce(anyhow::Result<NetGame>) -> T + Send + 'static,
+) -> (SocketAddr, JoinHandle<T>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let replay = Replay::new(generated_scenario(), 4, 9);
+        host(NetGame::host(
+            &listener,
+            replay,
+            &load_unit_types_json().unwrap(),
+        ))
+    });
+    (address, handle)
+}
+
+/// Play a network match with an `Ai` for the local team, until `turns` turns are over
+fn play_network(net: &mut NetGame, turns: u32) {
+    let team = net.team();
+    let mut ai = Ai::new(team, UtilityWeights::default(), team.id() as u64);
+    while net.game().turns().turn() <= turns && !net.game().is_over() {
+        match net.game().turns().active_team() == team {
+            true => net
+                .play(|game| {
+                    ai.take_turn(game);
+                })
+                .unwrap(),
+            false => {
+                net.wait().unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_network_match() {
+    let (address, host) = host_match(|net| {
+        let mut net = net.unwrap();
+        play_network(&mut net, 3);
+        (net.replay().log().to_vec(), net.game().checksum())
+    });
+    let mut net = NetGame::join(address, &load_unit_types_json().unwrap()).unwrap();
+    assert_eq!(net.team(), Team::new(1));
+    assert_eq!(net.replay().seed(), 9);
+    assert!(matches!(
+        net.apply(Command::EndTurn),
+        Err(NetError::Rejected(ActionError::NotYourTurn))
+    ));
+    play_network(&mut net, 3);
+    let (log, checksum) = host.join().unwrap();
+    assert_eq!(net.game().turns().turn(), 4);
+    assert_eq!(net.replay().log(), log);
+    assert_eq!(net.game().checksum(), checksum);
+}
+
+/// Connection to a host, sending messages by hand. Can also stand in for the host.
+struct RawClient {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl RawClient {
+    fn connect(address: SocketAddr) -> Self {
+        let stream = TcpStream::connect(address).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Self { stream, reader }
+    }
+
+    /// Wait for a player to join, to play the host's side of the connection by hand
+    fn accept(listener: &TcpListener) -> Self {
+        let (stream, _) = listener.accept().unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Self { stream, reader }
+    }
+
+    /// Join the match, returning the game it starts with
+    fn join(address: SocketAddr) -> (Self, Game) {
+        let mut client = RawClient::connect(address);
+        client.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+        });
+        let Message::Setup { replay, .. } = client.receive() else {
+            panic!("Host didn't set the match up");
+        };
+        let game = replay.start(&load_unit_types_json().unwrap()).unwrap();
+        (client, game)
+    }
+
+    fn send(&mut self, message: &Message) {
+        writeln!(self.stream, "{}", serde_json::to_string(message).unwrap()).unwrap();
+    }
+
+    fn receive(&mut self) -> Message {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+#[test]
+pub fn test_network_validates_commands() {
+    // A command sent out of turn, even one that would work
+    let (address, host) = host_match(|net| {
+        let mut net = net.unwrap();
+        (net.wait().err(), net.is_connected())
+    });
+    let (mut client, mut game) = RawClient::join(address);
+    game.apply(Command::EndTurn).unwrap();
+    client.send(&Message::Command(game.log()[0].clone()));
+    let (error, connected) = host.join().unwrap();
+    assert!(matches!(
+        error,
+        Some(NetError::Desync(ReplayError::Rejected {
+            index: 0,
+            error: ActionError::NotYourTurn
+        }))
+    ));
+    assert!(!connected);
+    assert!(matches!(client.receive(), Message::Leave { .. }));
+
+    // A command that plays out differently
+    let (address, host) = host_match(|net| {
+        let mut net = net.unwrap();
+        net.apply(Command::EndTurn).unwrap();
+        net.wait().err()
+    });
+    let (mut client, mut game) = RawClient::join(address);
+    let Message::Command(ended) = client.receive() else {
+        panic!("Host didn't end its turn");
+    };
+    game.replay(&[ended]).unwrap();
+    game.apply(Command::EndTurn).unwrap();
+    let mut wrong = game.log()[1].clone();
+    wrong.checksum ^= 1;
+    client.send(&Message::Command(wrong.clone()));
+    assert!(matches!(
+        host.join().unwrap(),
+        Some(NetError::Desync(ReplayError::Desync { index: 1, expected, .. }))
+            if expected == wrong.checksum
+    ));
+
+    // Players on different versions
+    let (address, host) = host_match(|net| net.err());
+    let mut client = RawClient::connect(address);
+    client.send(&Message::Hello {
+        version: PROTOCOL_VERSION + 1,
+    });
+    assert!(host.join().unwrap().is_some());
+    assert!(matches!(client.receive(), Message::Leave { .. }));
+}
+
+#[test]
+pub fn test_network_rejects_invalid_setup() {
+    // A host whose scenario escorts a unit that doesn't exist
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let host = thread::spawn(move || {
+        let mut host = RawClient::accept(&listener);
+        assert!(matches!(host.receive(), Message::Hello { .. }));
+        let mut replay = serde_json::to_value(Replay::new(generated_scenario(), 4, 9)).unwrap();
+        replay["scenario"]["objectives"] = serde_json::json!([
+            {"team": 1, "goal": {"escort": {"unit": 9, "to": {"x": 0, "y": 0}}}}
+        ]);
+        host.send(&Message::Setup {
+            replay: serde_json::from_value(replay).unwrap(),
+            team: Team::new(1),
+            checksum: 0,
+        });
+        host.receive()
+    });
+    assert!(NetGame::join(address, &load_unit_types_json().unwrap()).is_err());
+    assert!(matches!(host.join().unwrap(), Message::Leave { .. }));
+}
+
+/// Campaign of three sectors in a row. The first has no enemies, so it's won straight away.
+fn test_campaign_map() -> CampaignMap {
+    let json = r#"{
+        "name": "Test campaign",
+        "player": 0,
+        "army": ["infantry", "archer"],
+        "sectors": [
+            {
+                "scenario": {
+                    "name": "Start",
+                    "size": {"x": 16, "y": 16},
+                    "teams": [0, 1],
+                    "units": [],
+                    "objectives": [{"team": 0, "goal": "eliminate"}]
+                },
+                "entry": {"x": 1, "y": 1},
+                "links": ["Middle"]
+            },
+            {
+                "scenario": {
+                    "name": "Middle",
+                    "size": {"x": 16, "y": 16},
+                    "teams": [0, 1],
+                    "units": [{"type": "infantry", "team": 1, "pos": {"x": 12, "y": 12}}],
+                    "objectives": [{"team": 0, "goal": "eliminate"}]
+                },
+                "entry": {"x": 2, "y": 13},
+                "links": ["Start", "End"]
+            },
+            {
+                "scenario": {
+                    "name": "End",
+                    "size": {"x": 16, "y": 16},
+                    "teams": [0, 1],
+                    "units": [{"type": "infantry", "team": 1, "pos": {"x": 8, "y": 8}}],
+                    "objectives": [{"team": 0, "goal": "eliminate"}]
+                },
+                "entry": {"x": 14, "y": 1},
+                "links": ["Middle"]
+            }
+        ]
+    }"#;
+    CampaignMap::from_json(json).unwrap()
+}
+
+fn player_units(game: &Game) -> Vec<&Unit> {
+    game.sector()
+        .units()
+        .filter(|u| u.team() == Team::new(0))
+        .collect()
+}
+
+/// Win the campaign's current sector by taking every enemy out of it
+fn win_sector(campaign: &mut Campaign) {
+    let game = campaign.game_mut();
+    let enemies = game
+        .sector()
+        .units()
+        .filter(|u| u.team() != Team::new(0))
+        .map(|u| u.id())
+        .collect::<Vec<_>>();
+    for id in enemies {
+        game.sector.remove_unit(id);
+    }
+    game.update_outcomes();
+    assert!(campaign.can_travel());
+}
+
+#[test]
+pub fn test_campaign_travel() {
+    let unit_types = load_unit_types_json().unwrap();
+    let mut campaign = Campaign::new(test_campaign_map(), &unit_types, 3).unwrap();
+    assert_eq!(campaign.current(), "Start");
+    assert!(campaign.visited("Start"));
+    assert!(!campaign.visited("Middle"));
+    assert!(campaign.can_travel());
+    assert_eq!(player_units(campaign.game()).len(), 2);
+    for unit in player_units(campaign.game()) {
+        assert!(manhattan_distance(unit.pos(), Vector2::new(1, 1)) <= 2);
+    }
+
+    // Only linked sectors can be reached
+    assert!(campaign.travel("End", &unit_types).is_err());
+    assert_eq!(campaign.current(), "Start");
+
+    // Units keep their effects on the way
+    let archer = player_units(campaign.game())
+        .into_iter()
+        .find(|u| u.archetype() == Some("archer"))
+        .unwrap()
+        .id();
+    let slowed = StatusEffect::new(StatusKind::Slowed, 2);
+    campaign
+        .game_mut()
+        .sector
+        .apply_effect(archer, slowed)
+        .unwrap();
+    campaign.travel("Middle", &unit_types).unwrap();
+    assert_eq!(campaign.current(), "Middle");
+    assert!(campaign.visited("Start"));
+    assert!(!campaign.visited("End"));
+    assert_eq!(player_units(campaign.game()).len(), 2);
+    let archer = player_units(campaign.game())
+        .into_iter()
+        .find(|u| u.archetype() == Some("archer"))
+        .unwrap();
+    assert_eq!(archer.effects(), &[slowed]);
+    for unit in player_units(campaign.game()) {
+        assert!(manhattan_distance(unit.pos(), Vector2::new(2, 13)) <= 2);
+    }
+
+    // The sector has to be won before moving on
+    assert!(!campaign.can_travel());
+    assert!(campaign.travel("End", &unit_types).is_err());
+    assert_eq!(campaign.current(), "Middle");
+    assert_eq!(player_units(campaign.game()).len(), 2);
+
+    // The first sector is as it was left, without the player's units
+    win_sector(&mut campaign);
+    campaign.travel("Start", &unit_types).unwrap();
+    assert_eq!(campaign.current(), "Start");
+    assert_eq!(campaign.game().sector().units().count(), 2);
+    assert!(campaign.visited("Middle"));
+}
+
+#[test]
+pub fn test_campaign_is_seeded() {
+    let unit_types = load_unit_types_json().unwrap();
+    let play = |seed| {
+        let mut campaign = Campaign::new(test_campaign_map(), &unit_types, seed).unwrap();
+        campaign.travel("Middle", &unit_types).unwrap();
+        campaign
+    };
+    let (a, b) = (play(5), play(5));
+    assert_eq!(a.seeds("End"), b.seeds("End"));
+    assert_eq!(a.game().checksum(), b.game().checksum());
+    assert_ne!(a.seeds("Middle"), play(6).seeds("Middle"));
+}
+
+#[test]
+pub fn test_campaign_save() {
+    let unit_types = load_unit_types_json().unwrap();
+    let mut campaign = Campaign::new(test_campaign_map(), &unit_types, 3).unwrap();
+    campaign.travel("Middle", &unit_types).unwrap();
+    let json = campaign.to_json().unwrap();
+    let mut loaded = Campaign::from_json(&json).unwrap();
+    assert_eq!(loaded.current(), "Middle");
+    assert!(loaded.visited("Start"));
+    assert!(!loaded.visited("End"));
+    assert_eq!(loaded.seeds("End"), campaign.seeds("End"));
+    assert_same_game(loaded.game(), campaign.game());
+
+    // The loaded campaign carries on the same way
+    win_sector(&mut campaign);
+    win_sector(&mut loaded);
+    campaign.travel("End", &unit_types).unwrap();
+    loaded.travel("End", &unit_types).unwrap();
+    assert_eq!(loaded.game().checksum(), campaign.game().checksum());
+
+    let mut save: serde_json::Value = serde_json::from_str(&json).unwrap();
+    save["version"] = (CAMPAIGN_VERSION + 1).into();
+    assert!(Campaign::from_json(&save.to_string()).is_err());
+    let mut save: serde_json::Value = serde_json::from_str(&json).unwrap();
+    save["current"] = "Nowhere".into();
+    assert!(Campaign::from_json(&save.to_string()).is_err());
+}
+
+#[test]
+pub fn test_campaign_map_validation() {
+    let valid: serde_json::Value = serde_json::to_value(test_campaign_map()).unwrap();
+    let invalid = |change: fn(&mut serde_json::Value)| {
+        let mut map = valid.clone();
+        change(&mut map);
+        CampaignMap::from_json(&map.to_string()).is_err()
+    };
+    assert!(!invalid(|_| {}));
+    assert!(invalid(|m| m["sectors"] = serde_json::json!([])));
+    assert!(invalid(|m| m["army"] = serde_json::json!([])));
+    assert!(invalid(
+        |m| m["sectors"][1]["scenario"]["name"] = "Start".into()
+    ));
+    assert!(invalid(|m| m["sectors"][0]["entry"]["x"] = 16.into()));
+    assert!(invalid(
+        |m| m["sectors"][0]["scenario"]["teams"] = serde_json::json!([1])
+    ));
+    assert!(invalid(
+        |m| m["sectors"][0]["links"] = serde_json::json!(["Nowhere"])
+    ));
+    assert!(load_frontier_campaign().is_ok());
+}
@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use cgmath::Vector2;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::sector::{
+    archetype::{UnitRegistry, UnitType},
+    Sector, Team, Terrain, Unit, UnitId,
+};
+
+use super::{outcome::Outcome, turn::TurnState, Game};
+
+/// Something scripted to happen during a match, once its condition is met. Triggers are checked
+/// after every command, and each one only ever fires once.
+///
+/// Units to spawn are given by `U`: unit type IDs in scenario files, and the types themselves
+/// once the scenario has started.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Trigger<U = UnitType> {
+    pub condition: Condition,
+    /// Carried out in order when the trigger fires
+    pub actions: Vec<TriggerAction<U>>,
+    #[serde(default)]
+    pub fired: bool,
+    /// Units that were in the region when a `UnitEntersRegion` condition was last checked, so
+    /// that only units moving in set it off. Nothing until the game starts watching the region.
+    #[serde(default)]
+    pub occupants: Option<BTreeSet<UnitId>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// A unit comes to cover any tile in the region between `min` and `max` (inclusive), having
+    /// not covered any when the condition was last checked. Units that start the match in the
+    /// region don't count until they've left it. Only `team`'s units count, if it's given.
+    UnitEntersRegion {
+        min: Vector2<u32>,
+        max: Vector2<u32>,
+        #[serde(default)]
+        team: Option<Team>,
+    },
+    /// Turn `turn` has started
+    TurnReached { turn: u32 },
+    /// `unit` is no longer in the sector
+    UnitDies { unit: UnitId },
+    /// The `structure`th structure in the sector belongs to `team`, or to anyone if it isn't
+    /// given
+    StructureCaptured {
+        structure: usize,
+        #[serde(default)]
+        team: Option<Team>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerAction<U = UnitType> {
+    /// Bring in new units, each on the closest free tile to where it's meant to go
+    SpawnUnits { units: Vec<Reinforcement<U>> },
+    /// Turn the region between `min` and `max` (inclusive) into `terrain`, drawn with `sprite` if
+    /// it's given. Tiles are left alone if the unit or structure on them couldn't stand on the new
+    /// terrain.
+    ChangeTiles {
+        min: Vector2<u32>,
+        max: Vector2<u32>,
+        terrain: Terrain,
+        #[serde(default)]
+        sprite: Option<Vector2<u32>>,
+    },
+    /// Let `team` see the lay of the whole sector. Units stay hidden unless they're in sight.
+    RevealMap { team: Team },
+    /// Say something to the players
+    ShowDialogue(Dialogue),
+    /// Finish the match, with `winner` winning and everyone else losing. Everyone draws if there's
+    /// no winner.
+    EndGame {
+        #[serde(default)]
+        winner: Option<Team>,
+    },
+}
+
+/// A unit brought in by a trigger
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reinforcement<U = UnitType> {
+    #[serde(rename = "type")]
+    pub unit_type: U,
+    pub team: Team,
+    pub pos: Vector2<u32>,
+}
+
+/// A line of dialogue, along with who says it if anyone
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Dialogue {
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+impl Condition {
+    /// Whether the condition holds right now. Units entering a region can only be told apart from
+    /// units that were already there by `Trigger::check`, so for those this is whether any unit
+    /// is in the region at all.
+    pub fn is_met(&self, sector: &Sector, turns: &TurnState) -> bool {
+        match *self {
+            Condition::UnitEntersRegion { .. } => self
+                .occupants(sector)
+                .is_some_and(|units| !units.is_empty()),
+            Condition::TurnReached { turn } => turns.turn() >= turn,
+            Condition::UnitDies { unit } => sector.unit(unit).is_none(),
+            Condition::StructureCaptured { structure, team } => sector
+                .structures()
+                .get(structure)
+                .and_then(|s| s.owner)
+                .is_some_and(|owner| team.is_none_or(|t| owner == t)),
+        }
+    }
+
+    /// The units in the region of a `UnitEntersRegion` condition, or nothing for other conditions
+    fn occupants(&self, sector: &Sector) -> Option<BTreeSet<UnitId>> {
+        let Condition::UnitEntersRegion { min, max, team } = *self else {
+            return None;
+        };
+        let units = sector
+            .units()
+            .filter(|u| team.is_none_or(|t| u.team() == t))
+            .filter(|u| {
+                u.tiles()
+                    .any(|p| (min.x..=max.x).contains(&p.x) && (min.y..=max.y).contains(&p.y))
+            })
+            .map(|u| u.id())
+            .collect();
+        Some(units)
+    }
+}
+
+impl Trigger {
+    /// Whether the trigger's condition has been met, remembering which units are in its region
+    /// for the next check
+    fn check(&mut self, sector: &Sector, turns: &TurnState) -> bool {
+        let Some(occupants) = self.condition.occupants(sector) else {
+            return self.condition.is_met(sector, turns);
+        };
+        let entered = self
+            .occupants
+            .as_ref()
+            .is_some_and(|before| !occupants.is_subset(before));
+        self.occupants = Some(occupants);
+        entered
+    }
+}
+
+impl Trigger<String> {
+    /// Look up the unit types this trigger spawns
+    pub fn resolve(&self, unit_types: &UnitRegistry) -> Result<Trigger> {
+        let actions = self
+            .actions
+            .iter()
+            .map(|action| {
+                Ok(match action {
+                    TriggerAction::SpawnUnits { units } => TriggerAction::SpawnUnits {
+                        units: units
+                            .iter()
+                            .map(|unit| {
+                                let unit_type =
+                                    unit_types.get(&unit.unit_type).cloned().with_context(
+                                        || format!("No unit type with ID {}", unit.unit_type),
+                                    )?;
+                                Ok(Reinforcement {
+                                    unit_type,
+                                    team: unit.team,
+                                    pos: unit.pos,
+                                })
+                            })
+                            .collect::<Result<_>>()?,
+                    },
+                    TriggerAction::ChangeTiles {
+                        min,
+                        max,
+                        terrain,
+                        sprite,
+                    } => TriggerAction::ChangeTiles {
+                        min: *min,
+                        max: *max,
+                        terrain: *terrain,
+                        sprite: *sprite,
+                    },
+                    TriggerAction::RevealMap { team } => TriggerAction::RevealMap { team: *team },
+                    TriggerAction::ShowDialogue(dialogue) => {
+                        TriggerAction::ShowDialogue(dialogue.clone())
+                    }
+                    TriggerAction::EndGame { winner } => TriggerAction::EndGame { winner: *winner },
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Trigger {
+            condition: self.condition,
+            actions,
+            fired: self.fired,
+            occupants: self.occupants.clone(),
+        })
+    }
+}
+
+/// How the match ended for each team because of triggers, or nothing if none have ended it. If
+/// more than one has, the earliest of them in the list decides.
+pub fn outcomes(triggers: &[Trigger], turns: &TurnState) -> BTreeMap<Team, Outcome> {
+    let ended = triggers.iter().filter(|t| t.fired).find_map(|t| {
+        t.actions.iter().find_map(|a| match a {
+            TriggerAction::EndGame { winner } => Some(*winner),
+            _ => None,
+        })
+    });
+    let Some(winner) = ended else {
+        return BTreeMap::new();
+    };
+    turns
+        .teams()
+        .iter()
+        .map(|team| {
+            let outcome = match winner {
+                Some(winner) if winner == *team => Outcome::Won,
+                Some(_) => Outcome::Lost,
+                None => Outcome::Drawn,
+            };
+            (*team, outcome)
+        })
+        .collect()
+}
+
+impl Game {
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    /// Every line of dialogue shown since the game was started or loaded
+    pub fn dialogue(&self) -> &[Dialogue] {
+        &self.dialogue
+    }
+
+    /// Start watching the regions of any triggers waiting for units to enter them, so that units
+    /// already there don't set them off
+    pub(super) fn watch_regions(&mut self) {
+        for trigger in self.triggers.iter_mut().filter(|t| t.occupants.is_none()) {
+            trigger.occupants = trigger.condition.occupants(&self.sector);
+        }
+    }
+
+    /// Fire every trigger whose condition has been met, until none are left, returning whether
+    /// any fired. Triggers can set each other off.
+    pub(super) fn run_triggers(&mut self) -> bool {
+        let mut fired = false;
+        while let Some(i) = self
+            .triggers
+            .iter_mut()
+            .position(|t| !t.fired && t.check(&self.sector, &self.turns))
+        {
+            self.triggers[i].fired = true;
+            for action in self.triggers[i].actions.clone() {
+                self.perform(action);
+            }
+            fired = true;
+        }
+        fired
+    }
+
+    fn perform(&mut self, action: TriggerAction) {
+        match action {
+            TriggerAction::SpawnUnits { units } => {
+                for unit in units {
+                    let spawned =
+                        Unit::from_type(&unit.unit_type, unit.pos, unit.team, &self.sector)
+                            .map_err(|e| format!("{e:?}"))
+                            .and_then(|u| {
+                                self.sector
+                                    .place_unit(u, unit.pos)
+                                    .map_err(|e| format!("{e:?}"))
+                            });
+                    if let Err(e) = spawned {
+                        warn!(
+                            "Could not spawn {} near {:?}: {e}",
+                            unit.unit_type.id(),
+                            unit.pos
+                        );
+                    }
+                }
+            }
+            TriggerAction::ChangeTiles {
+                min,
+                max,
+                terrain,
+                sprite,
+            } => {
+                for x in min.x..=max.x {
+                    for y in min.y..=max.y {
+                        let pos = Vector2::new(x, y);
+                        let Some(mut changed) = self.sector.tile(pos).map(|t| t.contents().clone())
+                        else {
+                            continue;
+                        };
+                        changed.set_terrain(terrain);
+                        if let Some(sprite) = sprite {
+                            changed.set_atlas_position(sprite);
+                        }
+                        // Whatever is here has to be able to stay here
+                        let stranded = self
+                            .sector
+                            .unit_at_tile(pos)
+                            .is_some_and(|u| u.movement_cost(&changed).is_none())
+                            || (self.sector.structure_at(pos).is_some() && !changed.passable());
+                        if stranded {
+                            continue;
+                        }
+                        if let Some(tile) = self.sector.tile_mut(pos) {
+                            *tile.contents_mut() = changed;
+                        }
+                    }
+                }
+                // Anything that blocks sight now may have changed
+                self.sector.refresh_visibility();
+            }
+            TriggerAction::RevealMap { team } => self.sector.reveal_map(team),
+            TriggerAction::ShowDialogue(dialogue) => self.dialogue.push(dialogue),
+            // Picked up by `update_outcomes`
+            TriggerAction::EndGame { .. } => {}
+        }
+    }
+}
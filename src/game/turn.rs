@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sector::{Sector, Team};
+
+/// Whose phase it is, and how many turns have passed. Every team takes one phase per turn, in the
+/// order they were given.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TurnState {
+    teams: Vec<Team>,
+    phase: usize,
+    turn: u32,
+}
+
+impl TurnState {
+    pub fn new(teams: Vec<Team>) -> Self {
+        assert!(!teams.is_empty(), "Need at least one team to take turns");
+        Self {
+            teams,
+            phase: 0,
+            turn: 1,
+        }
+    }
+
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+
+    pub fn active_team(&self) -> Team {
+        self.teams[self.phase]
+    }
+
+    /// Index of the active team within `teams`
+    pub fn phase(&self) -> usize {
+        self.phase
+    }
+
+    /// Current turn, starting from 1
+    pub fn turn(&self) -> u32 {
+        self.turn
+    }
+
+    /// Hand over to the next team, moving onto the next turn once every team has had a phase
+    pub fn advance(&mut self) {
+        self.phase += 1;
+        if self.phase >= self.teams.len() {
+            self.phase = 0;
+            self.turn += 1;
+        }
+    }
+}
+
+/// Called with the sector and turn state for effects and AI that run at phase boundaries
+pub type TurnHook = Box<dyn FnMut(&mut Sector, &TurnState)>;
+
+#[derive(Default)]
+pub struct TurnHooks {
+    start: Vec<TurnHook>,
+    end: Vec<TurnHook>,
+}
+
+impl TurnHooks {
+    pub fn add_start(&mut self, hook: TurnHook) {
+        self.start.push(hook);
+    }
+
+    pub fn add_end(&mut self, hook: TurnHook) {
+        self.end.push(hook);
+    }
+
+    pub fn run_start(&mut self, sector: &mut Sector, turns: &TurnState) {
+        self.start.iter_mut().for_each(|hook| hook(sector, turns));
+    }
+
+    pub fn run_end(&mut self, sector: &mut Sector, turns: &TurnState) {
+        self.end.iter_mut().for_each(|hook| hook(sector, turns));
+    }
+}
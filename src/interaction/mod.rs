@@ -2,15 +2,25 @@ pub mod camera_position;
 
 use std::collections::HashMap;
 
+use anyhow::Result;
 use cgmath::Vector2;
+use log::{info, warn};
 use winit::{event::MouseButton, keyboard::KeyCode};
 
 use crate::{
+    ai::{Ai, UtilityWeights},
+    game::{
+        campaign::Campaign,
+        history::Command,
+        net::NetGame,
+        replay::{Replay, ReplayPlayer},
+        Game,
+    },
     juno::{
         directions::{self, i32_u32_cast, u32_i32_subtract},
         InputState,
     },
-    sector::Sector,
+    sector::{archetype::UnitRegistry, threat::ThreatMap, Sector, Team},
 };
 
 use self::camera_position::CameraPosition;
@@ -18,22 +28,150 @@ use self::camera_position::CameraPosition;
 pub struct GameData {
     camera_position: CameraPosition,
     screen_size: Vector2<u32>,
-    loaded_sector: Sector,
+    session: Session,
+    // Team controlled by the local player, which decides what is shown through the fog of war
+    player_team: Team,
+    // Whether to highlight the tiles that visible enemies could attack next turn
+    show_threats: bool,
+    // What's highlighted while threats are shown. Working it out means finding where every
+    // visible enemy could go, so it's only rebuilt when the game may have changed.
+    threats: Option<ThreatMap>,
     selected_tile: Option<Vector2<u32>>,
     key_map: KeyMap,
+    // Lines of the game's dialogue that have been shown to the player so far
+    shown_dialogue: usize,
+}
+
+/// What's being shown in the window
+pub enum Session {
+    /// A match being played, with every team but the player's played by the computer
+    Playing {
+        game: Game,
+        opponents: Vec<Ai>,
+        // How the match started, for recording it
+        replay: Replay,
+    },
+    /// A match against another player over the network
+    Online(NetGame),
+    /// A recorded match being stepped through
+    Watching(ReplayPlayer),
+    /// A campaign, with every sector's other teams played by the computer
+    Campaign {
+        campaign: Campaign,
+        opponents: Vec<Ai>,
+        // For setting up sectors as they're travelled to
+        unit_types: UnitRegistry,
+    },
+}
+
+impl Session {
+    pub fn game(&self) -> &Game {
+        match self {
+            Session::Playing { game, .. } => game,
+            Session::Online(net) => net.game(),
+            Session::Watching(player) => player.game(),
+            Session::Campaign { campaign, .. } => campaign.game(),
+        }
+    }
+
+    pub fn campaign(&self) -> Option<&Campaign> {
+        match self {
+            Session::Campaign { campaign, .. } => Some(campaign),
+            _ => None,
+        }
+    }
+
+    /// The match as a replay: everything played so far, or the replay being watched. Campaign
+    /// sectors start with units brought from other sectors, so they can't be recorded this way.
+    pub fn recording(&self) -> Option<Replay> {
+        match self {
+            Session::Playing { game, replay, .. } => {
+                let mut replay = replay.clone();
+                replay.record(game);
+                Some(replay)
+            }
+            Session::Online(net) => Some(net.replay()),
+            Session::Watching(player) => Some(player.replay().clone()),
+            Session::Campaign { .. } => None,
+        }
+    }
 }
 
 impl GameData {
-    pub fn new_default(screen_size: Vector2<u32>, loaded_sector: Sector) -> Self {
+    /// Start the match in `replay` against the computer, which plays every team but the first
+    pub fn new_default(
+        screen_size: Vector2<u32>,
+        replay: Replay,
+        unit_types: &UnitRegistry,
+    ) -> Result<Self> {
+        let game = replay.start(unit_types)?;
+        let player_team = game.turns().teams()[0];
+        let session = Session::Playing {
+            opponents: opponents(&game, player_team),
+            game,
+            replay,
+        };
+        Ok(Self::new(screen_size, session, player_team))
+    }
+
+    /// Play a campaign against the computer
+    pub fn new_campaign(
+        screen_size: Vector2<u32>,
+        mut campaign: Campaign,
+        unit_types: UnitRegistry,
+    ) -> Self {
+        let player_team = campaign.map().player();
+        let mut opponents = opponents(campaign.game(), player_team);
+        play_opponents(campaign.game_mut(), &mut opponents, player_team);
+        let session = Session::Campaign {
+            campaign,
+            opponents,
+            unit_types,
+        };
+        Self::new(screen_size, session, player_team)
+    }
+
+    /// Play against another player over the network
+    pub fn new_online(screen_size: Vector2<u32>, net: NetGame) -> Self {
+        let player_team = net.team();
+        Self::new(screen_size, Session::Online(net), player_team)
+    }
+
+    /// Watch a replay, seeing what the first team could see
+    pub fn new_replay(screen_size: Vector2<u32>, player: ReplayPlayer) -> Self {
+        let player_team = player.game().turns().teams()[0];
+        Self::new(screen_size, Session::Watching(player), player_team)
+    }
+
+    fn new(screen_size: Vector2<u32>, session: Session, player_team: Team) -> Self {
         Self {
             camera_position: CameraPosition::default(),
             screen_size,
-            loaded_sector,
+            session,
+            player_team,
+            show_threats: false,
+            threats: None,
             selected_tile: None,
             key_map: KeyMap::default(),
+            shown_dialogue: 0,
         }
     }
 
+    pub fn show_threats(&self) -> bool {
+        self.show_threats
+    }
+
+    /// Tiles that visible enemies of the player could attack next turn, while they're shown
+    pub fn threats(&self) -> Option<&ThreatMap> {
+        self.threats.as_ref()
+    }
+
+    fn refresh_threats(&mut self) {
+        self.threats = self
+            .show_threats
+            .then(|| self.sector().threat_map(self.player_team));
+    }
+
     pub fn camera_position(&self) -> &CameraPosition {
         &self.camera_position
     }
@@ -47,7 +185,19 @@ impl GameData {
     }
 
     pub fn sector(&self) -> &Sector {
-        &self.loaded_sector
+        self.game().sector()
+    }
+
+    pub fn player_team(&self) -> Team {
+        self.player_team
+    }
+
+    pub fn game(&self) -> &Game {
+        self.session.game()
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
     }
 
     pub fn selected_tile(&self) -> &Option<Vector2<u32>> {
@@ -69,8 +219,14 @@ impl GameData {
 }
 
 pub fn handle_inputs(data: &mut GameData, inputs: &InputState) {
+    let mut changed = match &mut data.session {
+        Session::Online(net) => receive_commands(net),
+        _ => false,
+    };
     let actions = data.key_map.actions(inputs);
     for action in actions {
+        // Everything but moving the selection could change the game, or what's shown of it
+        changed |= !matches!(action, InputAction::SelectAdjacentTile(_));
         match action {
             InputAction::SelectAdjacentTile(dir) => {
                 if let Some(prev_sel) = data.selected_tile {
@@ -93,11 +249,177 @@ pub fn handle_inputs(data: &mut GameData, inputs: &InputState) {
                     data.selected_tile = i32_u32_cast(selected_position);
                 }
             }
+            InputAction::EndTurn => play(&mut data.session, data.player_team, Command::EndTurn),
+            InputAction::ToggleThreats => data.show_threats = !data.show_threats,
+            InputAction::Undo => play(&mut data.session, data.player_team, Command::Undo),
+            InputAction::Redo => play(&mut data.session, data.player_team, Command::Redo),
+            InputAction::Travel(index) => {
+                if travel(&mut data.session, data.player_team, *index) {
+                    // Every sector is a game of its own, with its own dialogue
+                    data.shown_dialogue = 0;
+                }
+            }
+            InputAction::StepForward => {
+                let Session::Watching(player) = &mut data.session else {
+                    continue;
+                };
+                match player.step_forward() {
+                    Ok(true) => info!("Phase {} of the replay", player.phase()),
+                    Ok(false) => info!("Already at the end of the replay"),
+                    Err(e) => warn!("Replay no longer plays out the same way: {e:?}"),
+                }
+            }
+            InputAction::StepBack => {
+                let Session::Watching(player) = &mut data.session else {
+                    continue;
+                };
+                match player.step_back() {
+                    true => info!("Phase {} of the replay", player.phase()),
+                    false => info!("Already at the start of the replay"),
+                }
+            }
             InputAction::SelectSpecificTile(_) => todo!(),
             InputAction::PanScreen(_) => todo!(),
             InputAction::ChangeZoom(_) => todo!(),
         }
     }
+    if changed {
+        data.refresh_threats();
+    }
+    show_dialogue(data);
+}
+
+/// Show any dialogue that triggers have come out with since the last frame. Stepping back through
+/// a replay takes back lines that were already shown, and they aren't shown again.
+fn show_dialogue(data: &mut GameData) {
+    let dialogue = data.session.game().dialogue();
+    let shown = data.shown_dialogue.min(dialogue.len());
+    for line in &dialogue[shown..] {
+        match &line.speaker {
+            Some(speaker) => info!("{speaker}: {}", line.text),
+            None => info!("{}", line.text),
+        }
+    }
+    data.shown_dialogue = dialogue.len();
+}
+
+/// Apply one of the player's commands, and let the computer take its turns afterwards
+fn play(session: &mut Session, player_team: Team, command: Command) {
+    let applied = match session {
+        Session::Playing { game, .. } => game.apply(command.clone()).map_err(|e| format!("{e:?}")),
+        Session::Online(net) => net.apply(command.clone()).map_err(|e| format!("{e:?}")),
+        Session::Watching(_) => Err("Watching a replay".to_string()),
+        Session::Campaign { campaign, .. } => campaign
+            .game_mut()
+            .apply(command.clone())
+            .map_err(|e| format!("{e:?}")),
+    };
+    if let Err(e) = applied {
+        info!("Can't apply {command:?}: {e}");
+        return;
+    }
+    match session {
+        Session::Playing {
+            game, opponents, ..
+        } => play_opponents(game, opponents, player_team),
+        Session::Campaign {
+            campaign,
+            opponents,
+            ..
+        } => play_opponents(campaign.game_mut(), opponents, player_team),
+        Session::Online(_) | Session::Watching(_) => {}
+    }
+    if session.game().is_over() {
+        log_outcomes(session.game());
+    }
+    if let Session::Campaign { campaign, .. } = session {
+        log_campaign(campaign);
+    }
+}
+
+/// Take the player's units to the `index`th sector they can travel to, and start playing there,
+/// returning whether they went
+fn travel(session: &mut Session, player_team: Team, index: usize) -> bool {
+    let Session::Campaign {
+        campaign,
+        opponents,
+        unit_types,
+    } = session
+    else {
+        return false;
+    };
+    let Some(to) = campaign.destinations().get(index).cloned() else {
+        info!("There's no sector {} to travel to", index + 1);
+        return false;
+    };
+    if let Err(e) = campaign.travel(&to, unit_types) {
+        info!("Can't travel to {to}: {e:?}");
+        return false;
+    }
+    info!("Travelled to {to}");
+    *opponents = self::opponents(campaign.game(), player_team);
+    play_opponents(campaign.game_mut(), opponents, player_team);
+    log_campaign(campaign);
+    true
+}
+
+/// Tell the player where they can go next, or that the campaign is over
+fn log_campaign(campaign: &Campaign) {
+    if campaign.is_lost() {
+        info!("The campaign is lost");
+    } else if campaign.can_travel() {
+        for (i, sector) in campaign.destinations().iter().enumerate() {
+            info!("Press {} to travel to {sector}", i + 1);
+        }
+    }
+}
+
+/// Computer players for every team in `game` other than the player's
+fn opponents(game: &Game, player_team: Team) -> Vec<Ai> {
+    game.turns()
+        .teams()
+        .iter()
+        .filter(|team| **team != player_team)
+        .map(|team| {
+            let seed = game.seed().wrapping_add(team.id() as u64);
+            Ai::new(*team, UtilityWeights::default(), seed)
+        })
+        .collect()
+}
+
+/// Let the computer take its turns until it's the player's turn again, or the match is over
+fn play_opponents(game: &mut Game, opponents: &mut [Ai], player_team: Team) {
+    while game.turns().active_team() != player_team && !game.is_over() {
+        let team = game.turns().active_team();
+        match opponents.iter_mut().find(|ai| ai.team() == team) {
+            Some(ai) => ai.take_turn(game),
+            None => break,
+        };
+    }
+}
+
+/// Apply every command the other player has sent since the last frame, returning whether there
+/// were any
+fn receive_commands(net: &mut NetGame) -> bool {
+    let mut received = false;
+    while net.is_connected() {
+        match net.poll() {
+            Ok(Some(_)) if net.game().is_over() => {
+                received = true;
+                log_outcomes(net.game());
+            }
+            Ok(Some(_)) => received = true,
+            Ok(None) => break,
+            Err(e) => warn!("Network match ended: {e:?}"),
+        }
+    }
+    received
+}
+
+fn log_outcomes(game: &Game) {
+    for (team, outcome) in game.outcomes() {
+        info!("Team {} {outcome:?}", team.id());
+    }
 }
 
 #[derive(Debug, Eq, Hash, PartialEq)]
@@ -112,6 +434,14 @@ pub enum InputAction {
     SelectSpecificTile(Vector2<u32>),
     PanScreen(Vector2<f32>),
     ChangeZoom(f32),
+    EndTurn,
+    ToggleThreats,
+    Undo,
+    Redo,
+    StepForward,
+    StepBack,
+    /// Travel to one of the sectors linked to the current one, by its position in the list
+    Travel(usize),
 }
 
 pub struct KeyMap {
@@ -150,6 +480,26 @@ impl Default for KeyMap {
             ButtonInput::Key(KeyCode::KeyL),
             InputAction::SelectAdjacentTile(directions::RIGHT),
         );
+        keys.insert(ButtonInput::Key(KeyCode::Enter), InputAction::EndTurn);
+        keys.insert(ButtonInput::Key(KeyCode::KeyT), InputAction::ToggleThreats);
+        keys.insert(ButtonInput::Key(KeyCode::KeyU), InputAction::Undo);
+        keys.insert(ButtonInput::Key(KeyCode::KeyR), InputAction::Redo);
+        keys.insert(ButtonInput::Key(KeyCode::Period), InputAction::StepForward);
+        keys.insert(ButtonInput::Key(KeyCode::Comma), InputAction::StepBack);
+        let digits = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        for (i, key) in digits.into_iter().enumerate() {
+            keys.insert(ButtonInput::Key(key), InputAction::Travel(i));
+        }
         Self { keys }
     }
 }
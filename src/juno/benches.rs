@@ -0,0 +1,59 @@
+extern crate test;
+
+use cgmath::Vector2;
+use test::{black_box, Bencher};
+
+use super::{pathfinding::Movement, tests::cost_grid};
+
+const SIZE: Vector2<u32> = Vector2::new(256, 256);
+
+/// Walls every few columns of a `size` grid, with a gap at alternating ends, so that searches
+/// from one corner to the other have to cover most of it
+pub fn serpentine_walls(size: Vector2<u32>) -> Vec<Vector2<u32>> {
+    (0..size.x)
+        .step_by(4)
+        .filter(|x| x + 2 < size.x)
+        .flat_map(|x| {
+            let gap = if (x / 4) % 2 == 0 { size.y - 1 } else { 0 };
+            (0..size.y)
+                .filter(move |y| *y != gap)
+                .map(move |y| Vector2::new(x + 2, y))
+        })
+        .collect()
+}
+
+fn bench_find_path(b: &mut Bencher, movement: Movement) {
+    let grid = cost_grid(SIZE, &serpentine_walls(SIZE));
+    b.iter(|| {
+        let path = grid.find_path(
+            Vector2::new(0, 0),
+            SIZE - Vector2::new(1, 1),
+            movement,
+            None,
+            |t| *t.contents(),
+        );
+        black_box(path.unwrap())
+    });
+}
+
+#[bench]
+fn bench_find_path_cardinal(b: &mut Bencher) {
+    bench_find_path(b, Movement::Cardinal);
+}
+
+#[bench]
+fn bench_find_path_diagonal(b: &mut Bencher) {
+    bench_find_path(b, Movement::Diagonal);
+}
+
+#[bench]
+fn bench_path_costs(b: &mut Bencher) {
+    let grid = cost_grid(SIZE, &serpentine_walls(SIZE));
+    b.iter(|| {
+        black_box(
+            grid.path_costs(Vector2::new(0, 0), Movement::Cardinal, None, |t| {
+                *t.contents()
+            }),
+        )
+    });
+}
@@ -15,6 +15,17 @@ pub fn cardinal() -> Iter<'static, Vector2<i32>> {
     [DOWN, LEFT, RIGHT, UP].iter()
 }
 
+pub fn diagonal() -> Iter<'static, Vector2<i32>> {
+    [DOWN_LEFT, DOWN_RIGHT, UP_LEFT, UP_RIGHT].iter()
+}
+
+pub fn all() -> Iter<'static, Vector2<i32>> {
+    [
+        DOWN, DOWN_LEFT, DOWN_RIGHT, LEFT, RIGHT, UP, UP_LEFT, UP_RIGHT,
+    ]
+    .iter()
+}
+
 pub fn u32_i32_subtract(v1: Vector2<u32>, v2: Vector2<i32>) -> Option<Vector2<u32>> {
     let v1_x = v1.x as i32;
     let v1_y = v1.y as i32;
@@ -40,3 +51,33 @@ pub fn i32_u32_cast(v: Vector2<i32>) -> Option<Vector2<u32>> {
 pub fn u32_i32_cast(v: Vector2<u32>) -> Vector2<i32> {
     Vector2::new(v.x as i32, v.y as i32)
 }
+
+/// Every tile on the straight line between `from` and `to` (inclusive), using Bresenham's
+/// algorithm
+pub fn line(from: Vector2<u32>, to: Vector2<u32>) -> Vec<Vector2<u32>> {
+    let (from, to) = (u32_i32_cast(from), u32_i32_cast(to));
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step = Vector2::new((to.x - from.x).signum(), (to.y - from.y).signum());
+    let mut error = dx + dy;
+    let mut current = from;
+    let mut tiles = vec![Vector2::new(from.x as u32, from.y as u32)];
+    while current != to {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            current.x += step.x;
+        }
+        if doubled <= dx {
+            error += dx;
+            current.y += step.y;
+        }
+        tiles.push(Vector2::new(current.x as u32, current.y as u32));
+    }
+    tiles
+}
+
+/// Number of cardinal steps between two tiles
+pub fn manhattan_distance(from: Vector2<u32>, to: Vector2<u32>) -> u32 {
+    from.x.abs_diff(to.x) + from.y.abs_diff(to.y)
+}
@@ -4,10 +4,11 @@ use std::{
 };
 
 use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
 
-use super::directions;
+use super::directions::{self, i32_u32_cast, u32_i32_cast};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Grid<T> {
     size: Vector2<u32>,
     grid: Vec<GridItem<T>>,
@@ -43,6 +44,34 @@ impl<T: Clone> Grid<T> {
         self.size
     }
 
+    pub fn contains(&self, pos: Vector2<u32>) -> bool {
+        pos.x < self.width() && pos.y < self.height()
+    }
+
+    /// Shift `pos` by `offset`, returning `None` if the result falls outside of the grid
+    pub fn offset(&self, pos: Vector2<u32>, offset: Vector2<i32>) -> Option<Vector2<u32>> {
+        i32_u32_cast(u32_i32_cast(pos) + offset).filter(|p| self.contains(*p))
+    }
+
+    /// Whether every tile has been pushed, each in the position matching its index. Grids
+    /// that have been deserialized should be checked with this before they're used.
+    pub fn is_complete(&self) -> bool {
+        self.grid.len() == (self.width() * self.height()) as usize
+            && self
+                .grid
+                .iter()
+                .enumerate()
+                .all(|(i, item)| item.pos == self.from_index(i))
+    }
+
+    pub fn index(&self, pos: Vector2<u32>) -> usize {
+        (pos.y * self.width() + pos.x) as usize
+    }
+
+    pub fn from_index(&self, index: usize) -> Vector2<u32> {
+        Vector2::new(index as u32 % self.width(), index as u32 / self.width())
+    }
+
     pub fn tile(&self, pos: Vector2<u32>) -> Option<&GridItem<T>> {
         self.grid.get((pos.y * self.width() + pos.x) as usize)
     }
@@ -194,7 +223,7 @@ impl<T: Clone> Grid<T> {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GridItem<T> {
     pos: Vector2<u32>,
     contents: T,
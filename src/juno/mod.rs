@@ -1,6 +1,12 @@
 pub mod directions;
 pub mod grid;
+pub mod pathfinding;
 pub mod renderer;
+pub mod shadowcast;
+#[cfg(test)]
+pub mod tests;
+#[cfg(all(test, feature = "bench"))]
+pub mod benches;
 
 use cgmath::Vector2;
 use renderer::{renderer::Renderer, testing::TextureAtlasHandle};
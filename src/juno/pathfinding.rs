@@ -0,0 +1,403 @@
+use std::{cmp::Ordering, collections::BinaryHeap, f32::consts::SQRT_2, slice::Iter};
+
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    directions,
+    grid::{Grid, GridItem},
+};
+
+/// Which neighbours a path is allowed to step to from any given tile, and what it costs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Movement {
+    #[default]
+    Cardinal,
+    /// Cardinal and diagonal steps. A diagonal step costs √2 times the cost of the tile entered.
+    Diagonal,
+    /// Cardinal and diagonal steps, where diagonal steps alternate between costing the same as
+    /// the tile entered and double, starting with the cheaper one
+    AlternatingDiagonal,
+}
+
+impl Movement {
+    /// Every direction a single step can go in
+    pub fn directions(&self) -> Iter<'static, Vector2<i32>> {
+        match self {
+            Movement::Cardinal => directions::cardinal(),
+            Movement::Diagonal | Movement::AlternatingDiagonal => directions::all(),
+        }
+    }
+
+    /// Number of different costs a diagonal step can have depending on the path so far, which
+    /// searches need to keep apart
+    fn layers(&self) -> usize {
+        match self {
+            Movement::AlternatingDiagonal => 2,
+            Movement::Cardinal | Movement::Diagonal => 1,
+        }
+    }
+
+    /// Cost multiplier for taking `step` from `layer`, and the layer it leads to
+    fn step(&self, step: Vector2<i32>, layer: usize) -> (f32, usize) {
+        if step.x == 0 || step.y == 0 {
+            return (1., layer);
+        }
+        match self {
+            Movement::Cardinal => (1., layer),
+            Movement::Diagonal => (SQRT_2, layer),
+            Movement::AlternatingDiagonal => ((layer + 1) as f32, 1 - layer),
+        }
+    }
+
+    /// Lowest possible number of steps between two tiles, weighted the same way as `step`
+    pub fn distance(&self, from: Vector2<u32>, to: Vector2<u32>) -> f32 {
+        let dx = from.x.abs_diff(to.x);
+        let dy = from.y.abs_diff(to.y);
+        let (straight, diagonal) = (dx.max(dy) - dx.min(dy), dx.min(dy));
+        match self {
+            Movement::Cardinal => (dx + dy) as f32,
+            Movement::Diagonal => straight as f32 + SQRT_2 * diagonal as f32,
+            Movement::AlternatingDiagonal => (straight + diagonal + diagonal / 2) as f32,
+        }
+    }
+}
+
+/// Everything about how paths get from tile to tile. Each ruleset picks one, and uses it for
+/// every unit's pathfinding and navigation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Topology {
+    pub movement: Movement,
+    /// Whether a diagonal step can squeeze past a blocked tile on either side of it. Without
+    /// this, both of the tiles it passes between have to be enterable.
+    pub cut_corners: bool,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Movement::default().into()
+    }
+}
+
+impl From<Movement> for Topology {
+    fn from(movement: Movement) -> Self {
+        Self {
+            movement,
+            cut_corners: true,
+        }
+    }
+}
+
+/// A route between two tiles, including both the starting and the final tile
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    tiles: Vec<Vector2<u32>>,
+    /// Total cost of getting to each tile along the way, starting with nothing for the first
+    costs: Vec<f32>,
+}
+
+impl Path {
+    pub fn new(tiles: Vec<Vector2<u32>>, costs: Vec<f32>) -> Self {
+        Self { tiles, costs }
+    }
+
+    pub fn tiles(&self) -> &[Vector2<u32>] {
+        &self.tiles
+    }
+
+    pub fn costs(&self) -> &[f32] {
+        &self.costs
+    }
+
+    pub fn cost(&self) -> f32 {
+        self.costs.last().copied().unwrap_or(0.)
+    }
+
+    /// Just the first `steps` steps of the path, costing as much as they did along the way
+    pub fn prefix(&self, steps: usize) -> Path {
+        Path::new(self.tiles[..=steps].to_vec(), self.costs[..=steps].to_vec())
+    }
+
+    /// The same path walked the other way, with each step costing what it did going forwards
+    pub fn reversed(&self) -> Path {
+        let total = self.cost();
+        Path::new(
+            self.tiles.iter().rev().copied().collect(),
+            self.costs.iter().rev().map(|c| total - c).collect(),
+        )
+    }
+
+    pub fn start(&self) -> Vector2<u32> {
+        self.tiles[0]
+    }
+
+    pub fn end(&self) -> Vector2<u32> {
+        self.tiles[self.tiles.len() - 1]
+    }
+
+    /// Number of steps taken, which is one less than the number of tiles
+    pub fn steps(&self) -> usize {
+        self.tiles.len() - 1
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// A* search from `start` to `goal`. `cost` gives the cost of entering a tile, or `None` if
+    /// the tile can't be entered at all, which also counts as blocked for cutting corners. The
+    /// starting tile is never checked, so a unit standing on it doesn't block itself. Paths which
+    /// would cost more than `max_cost` are discarded.
+    pub fn find_path<F>(
+        &self,
+        start: Vector2<u32>,
+        goal: Vector2<u32>,
+        topology: impl Into<Topology>,
+        max_cost: Option<f32>,
+        cost: F,
+    ) -> Option<Path>
+    where
+        F: Fn(&GridItem<T>) -> Option<f32>,
+    {
+        self.find_path_stopping(start, goal, topology, max_cost, cost, |_| false)
+    }
+
+    /// Same as `find_path`, except that paths can enter tiles for which `stops` returns true but
+    /// can't carry on past them. The starting tile is never a stop.
+    pub fn find_path_stopping<F, S>(
+        &self,
+        start: Vector2<u32>,
+        goal: Vector2<u32>,
+        topology: impl Into<Topology>,
+        max_cost: Option<f32>,
+        cost: F,
+        stops: S,
+    ) -> Option<Path>
+    where
+        F: Fn(&GridItem<T>) -> Option<f32>,
+        S: Fn(Vector2<u32>) -> bool,
+    {
+        if !self.contains(start) || !self.contains(goal) {
+            return None;
+        }
+        if start == goal {
+            return Some(Path::new(vec![start], vec![0.]));
+        }
+        // Scale the heuristic by the cheapest tile on the grid so that it never overestimates
+        let min_cost = self.tiles().filter_map(&cost).fold(f32::INFINITY, f32::min);
+        if !min_cost.is_finite() {
+            return None;
+        }
+        let max_cost = max_cost.unwrap_or(f32::INFINITY);
+        let topology = topology.into();
+        let movement = topology.movement;
+
+        // Searches through every tile in every layer, so `index` is for both at once
+        let len = (self.width() * self.height()) as usize;
+        let states = len * movement.layers();
+        let mut costs = vec![f32::INFINITY; states];
+        let mut previous = vec![None::<usize>; states];
+        let mut closed = vec![false; states];
+        let mut open = BinaryHeap::new();
+        costs[self.index(start)] = 0.;
+        open.push(OpenTile {
+            index: self.index(start),
+            estimate: movement.distance(start, goal) * min_cost,
+        });
+
+        while let Some(OpenTile { index, .. }) = open.pop() {
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+            let (layer, pos) = (index / len, self.from_index(index % len));
+            if pos == goal {
+                return Some(self.trace_path(&previous, &costs, index));
+            }
+            if pos != start && stops(pos) {
+                continue;
+            }
+            for (next, next_layer, step_cost) in self.steps(pos, layer, topology, &cost) {
+                let next_index = next_layer * len + self.index(next);
+                if closed[next_index] {
+                    continue;
+                }
+                let next_cost = costs[index] + step_cost;
+                if next_cost > max_cost || next_cost >= costs[next_index] {
+                    continue;
+                }
+                costs[next_index] = next_cost;
+                previous[next_index] = Some(index);
+                open.push(OpenTile {
+                    index: next_index,
+                    estimate: next_cost + movement.distance(next, goal) * min_cost,
+                });
+            }
+        }
+        None
+    }
+
+    /// Dijkstra flood fill out from `start`, giving the cheapest cost of reaching each tile (indexed
+    /// the same way as the grid), or `None` for tiles that can't be reached within `max_cost`.
+    pub fn path_costs<F>(
+        &self,
+        start: Vector2<u32>,
+        topology: impl Into<Topology>,
+        max_cost: Option<f32>,
+        cost: F,
+    ) -> Vec<Option<f32>>
+    where
+        F: Fn(&GridItem<T>) -> Option<f32>,
+    {
+        self.path_costs_stopping(start, topology, max_cost, cost, |_| false)
+    }
+
+    /// Same as `path_costs`, except that tiles for which `stops` returns true can be reached but
+    /// not moved on from. The starting tile is never a stop.
+    pub fn path_costs_stopping<F, S>(
+        &self,
+        start: Vector2<u32>,
+        topology: impl Into<Topology>,
+        max_cost: Option<f32>,
+        cost: F,
+        stops: S,
+    ) -> Vec<Option<f32>>
+    where
+        F: Fn(&GridItem<T>) -> Option<f32>,
+        S: Fn(Vector2<u32>) -> bool,
+    {
+        let len = (self.width() * self.height()) as usize;
+        let mut costs = vec![None::<f32>; len];
+        if !self.contains(start) {
+            return costs;
+        }
+        let max_cost = max_cost.unwrap_or(f32::INFINITY);
+        let topology = topology.into();
+        // The cheapest way to each tile in each layer, which isn't always the cheapest way to go
+        // on from it
+        let states = len * topology.movement.layers();
+        let mut state_costs = vec![None::<f32>; states];
+        let mut closed = vec![false; states];
+        let mut open = BinaryHeap::new();
+        state_costs[self.index(start)] = Some(0.);
+        open.push(OpenTile {
+            index: self.index(start),
+            estimate: 0.,
+        });
+
+        while let Some(OpenTile { index, estimate }) = open.pop() {
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+            let (layer, pos) = (index / len, self.from_index(index % len));
+            if pos != start && stops(pos) {
+                continue;
+            }
+            for (next, next_layer, step_cost) in self.steps(pos, layer, topology, &cost) {
+                let next_index = next_layer * len + self.index(next);
+                if closed[next_index] {
+                    continue;
+                }
+                let next_cost = estimate + step_cost;
+                if next_cost > max_cost || state_costs[next_index].is_some_and(|c| c <= next_cost) {
+                    continue;
+                }
+                state_costs[next_index] = Some(next_cost);
+                open.push(OpenTile {
+                    index: next_index,
+                    estimate: next_cost,
+                });
+            }
+        }
+        for (i, cost) in state_costs.into_iter().enumerate() {
+            let tile = &mut costs[i % len];
+            if let Some(cost) = cost {
+                *tile = Some(tile.map_or(cost, |c| c.min(cost)));
+            }
+        }
+        costs
+    }
+
+    /// Every step that can be taken from `pos` in `layer`, with the tile and layer it leads to
+    /// and what it costs
+    fn steps<F>(
+        &self,
+        pos: Vector2<u32>,
+        layer: usize,
+        topology: Topology,
+        cost: &F,
+    ) -> Vec<(Vector2<u32>, usize, f32)>
+    where
+        F: Fn(&GridItem<T>) -> Option<f32>,
+    {
+        let enterable = |step| {
+            self.offset(pos, step)
+                .and_then(|p| self.tile(p))
+                .and_then(cost)
+                .is_some()
+        };
+        topology
+            .movement
+            .directions()
+            .filter(|step| {
+                let diagonal = step.x != 0 && step.y != 0;
+                !diagonal
+                    || topology.cut_corners
+                    || (enterable(Vector2::new(step.x, 0)) && enterable(Vector2::new(0, step.y)))
+            })
+            .filter_map(|step| {
+                let next = self.offset(pos, *step)?;
+                let tile_cost = self.tile(next).and_then(cost)?;
+                let (multiplier, next_layer) = topology.movement.step(*step, layer);
+                Some((next, next_layer, tile_cost * multiplier))
+            })
+            .collect()
+    }
+
+    /// Follow `previous` back from `goal`, where both are indices of tiles in any layer, picking up
+    /// the cost of getting to each tile from `costs`
+    fn trace_path(&self, previous: &[Option<usize>], costs: &[f32], goal: usize) -> Path {
+        let len = (self.width() * self.height()) as usize;
+        let mut tiles = vec![self.from_index(goal % len)];
+        let mut path_costs = vec![costs[goal]];
+        let mut current = goal;
+        while let Some(prev) = previous[current] {
+            tiles.push(self.from_index(prev % len));
+            path_costs.push(costs[prev]);
+            current = prev;
+        }
+        tiles.reverse();
+        path_costs.reverse();
+        Path::new(tiles, path_costs)
+    }
+}
+
+/// Entry in the A* open set, ordered so that `BinaryHeap` pops the lowest estimate first
+struct OpenTile {
+    index: usize,
+    estimate: f32,
+}
+
+impl PartialEq for OpenTile {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenTile {}
+
+impl PartialOrd for OpenTile {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenTile {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
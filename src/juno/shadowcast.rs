@@ -0,0 +1,113 @@
+use cgmath::Vector2;
+
+// Transforms from octant-local (column, row) coordinates to grid offsets, one per octant
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, 1],
+    [0, 1, 1, 0],
+    [0, -1, 1, 0],
+    [-1, 0, 0, 1],
+    [-1, 0, 0, -1],
+    [0, -1, -1, 0],
+    [0, 1, -1, 0],
+    [1, 0, 0, -1],
+];
+
+/// Recursive shadowcasting out from `origin`, calling `reveal` for every tile within `radius`
+/// that can be seen. Tiles for which `blocks` returns true are revealed, but hide anything
+/// behind them. The origin is always revealed.
+pub fn field_of_view<B, R>(
+    origin: Vector2<u32>,
+    radius: u32,
+    size: Vector2<u32>,
+    blocks: B,
+    mut reveal: R,
+) where
+    B: Fn(Vector2<u32>) -> bool,
+    R: FnMut(Vector2<u32>),
+{
+    if origin.x >= size.x || origin.y >= size.y {
+        return;
+    }
+    reveal(origin);
+    let caster = Caster {
+        origin: Vector2::new(origin.x as i32, origin.y as i32),
+        radius: radius as i32,
+        size,
+    };
+    for transform in OCTANTS {
+        caster.cast(1, 1., 0., transform, &blocks, &mut reveal);
+    }
+}
+
+struct Caster {
+    origin: Vector2<i32>,
+    radius: i32,
+    size: Vector2<u32>,
+}
+
+impl Caster {
+    fn tile(&self, column: i32, row: i32, transform: [i32; 4]) -> Option<Vector2<u32>> {
+        let x = self.origin.x + column * transform[0] + row * transform[1];
+        let y = self.origin.y + column * transform[2] + row * transform[3];
+        if x < 0 || y < 0 || x >= self.size.x as i32 || y >= self.size.y as i32 {
+            return None;
+        }
+        Some(Vector2::new(x as u32, y as u32))
+    }
+
+    /// Scan rows outwards from `row`, between the slopes `start` and `end`, recursing whenever a
+    /// blocking tile splits the visible area in two
+    fn cast<B, R>(
+        &self,
+        row: i32,
+        mut start: f32,
+        end: f32,
+        transform: [i32; 4],
+        blocks: &B,
+        reveal: &mut R,
+    ) where
+        B: Fn(Vector2<u32>) -> bool,
+        R: FnMut(Vector2<u32>),
+    {
+        if start < end {
+            return;
+        }
+        let mut new_start = 0.;
+        for distance in row..=self.radius {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue;
+                }
+                if end > left_slope {
+                    break;
+                }
+                let tile = self.tile(dx, dy, transform);
+                if let Some(pos) = tile {
+                    if dx * dx + dy * dy <= self.radius * self.radius {
+                        reveal(pos);
+                    }
+                }
+                let blocking = tile.is_none_or(blocks);
+                if blocked {
+                    if blocking {
+                        new_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if blocking && distance < self.radius {
+                    blocked = true;
+                    self.cast(distance + 1, start, left_slope, transform, blocks, reveal);
+                    new_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
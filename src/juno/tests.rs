@@ -0,0 +1,239 @@
+use cgmath::Vector2;
+
+use super::{
+    grid::Grid,
+    pathfinding::{Movement, Topology},
+};
+
+/// Grid of tile costs, where `None` is a wall
+pub fn cost_grid(size: Vector2<u32>, walls: &[Vector2<u32>]) -> Grid<Option<f32>> {
+    let mut grid = Grid::new(size);
+    grid.fill(Some(1.));
+    for wall in walls {
+        *grid.tile_mut(*wall).unwrap().contents_mut() = None;
+    }
+    grid
+}
+
+#[test]
+pub fn test_find_path_straight_line() {
+    let grid = cost_grid(Vector2::new(5, 5), &[]);
+    let path = grid
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(4, 0),
+            Movement::Cardinal,
+            None,
+            |t| *t.contents(),
+        )
+        .unwrap();
+    assert_eq!(path.cost(), 4.);
+    assert_eq!(path.steps(), 4);
+    assert_eq!(path.start(), Vector2::new(0, 0));
+    assert_eq!(path.end(), Vector2::new(4, 0));
+}
+
+#[test]
+pub fn test_find_path_around_wall() {
+    // Wall along x = 2, with a single gap at the top
+    let walls = (0..4).map(|y| Vector2::new(2, y)).collect::<Vec<_>>();
+    let grid = cost_grid(Vector2::new(5, 5), &walls);
+    let path = grid
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(4, 0),
+            Movement::Cardinal,
+            None,
+            |t| *t.contents(),
+        )
+        .unwrap();
+    assert_eq!(path.cost(), 12.);
+    assert!(path.tiles().iter().all(|p| !walls.contains(p)));
+    assert!(path
+        .tiles()
+        .windows(2)
+        .all(|w| w[0].x.abs_diff(w[1].x) + w[0].y.abs_diff(w[1].y) == 1));
+}
+
+#[test]
+pub fn test_find_path_diagonal() {
+    let grid = cost_grid(Vector2::new(5, 5), &[]);
+    let path = grid
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(3, 3),
+            Movement::Diagonal,
+            None,
+            |t| *t.contents(),
+        )
+        .unwrap();
+    assert_eq!(path.steps(), 3);
+    assert!((path.cost() - 3. * std::f32::consts::SQRT_2).abs() < 1e-4);
+}
+
+#[test]
+pub fn test_find_path_prefers_cheap_tiles() {
+    let mut grid = cost_grid(Vector2::new(3, 3), &[]);
+    *grid.tile_mut(Vector2::new(1, 0)).unwrap().contents_mut() = Some(5.);
+    let path = grid
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(2, 0),
+            Movement::Cardinal,
+            None,
+            |t| *t.contents(),
+        )
+        .unwrap();
+    assert_eq!(path.cost(), 4.);
+    assert!(!path.tiles().contains(&Vector2::new(1, 0)));
+}
+
+#[test]
+pub fn test_find_path_max_cost() {
+    let grid = cost_grid(Vector2::new(5, 5), &[]);
+    let find = |max_cost| {
+        grid.find_path(
+            Vector2::new(0, 0),
+            Vector2::new(4, 0),
+            Movement::Cardinal,
+            Some(max_cost),
+            |t| *t.contents(),
+        )
+    };
+    assert!(find(3.).is_none());
+    assert!(find(4.).is_some());
+}
+
+#[test]
+pub fn test_find_path_unreachable() {
+    let walls = (0..5).map(|y| Vector2::new(2, y)).collect::<Vec<_>>();
+    let grid = cost_grid(Vector2::new(5, 5), &walls);
+    let path = grid.find_path(
+        Vector2::new(0, 0),
+        Vector2::new(4, 4),
+        Movement::Diagonal,
+        None,
+        |t| *t.contents(),
+    );
+    assert!(path.is_none());
+    // Goals outside the grid are never reachable
+    let path = grid.find_path(
+        Vector2::new(0, 0),
+        Vector2::new(5, 0),
+        Movement::Cardinal,
+        None,
+        |t| *t.contents(),
+    );
+    assert!(path.is_none());
+}
+
+#[test]
+pub fn test_find_path_stopping() {
+    let grid = cost_grid(Vector2::new(4, 3), &[]);
+    let stops = [Vector2::new(1, 0), Vector2::new(1, 1)];
+    let find = |goal| {
+        grid.find_path_stopping(
+            Vector2::new(0, 0),
+            goal,
+            Movement::Cardinal,
+            None,
+            |t| *t.contents(),
+            |pos| stops.contains(&pos),
+        )
+    };
+    // Stop tiles can be ended on, but not passed through
+    assert_eq!(find(Vector2::new(1, 0)).unwrap().cost(), 1.);
+    let path = find(Vector2::new(3, 0)).unwrap();
+    assert_eq!(path.cost(), 7.);
+    assert!(path.tiles()[1..path.tiles().len() - 1]
+        .iter()
+        .all(|p| !stops.contains(p)));
+    // Starting on a stop tile doesn't stop anything
+    let path = grid.find_path_stopping(
+        Vector2::new(1, 0),
+        Vector2::new(3, 0),
+        Movement::Cardinal,
+        None,
+        |t| *t.contents(),
+        |pos| stops.contains(&pos),
+    );
+    assert_eq!(path.unwrap().cost(), 2.);
+}
+
+#[test]
+pub fn test_path_costs_stopping() {
+    let grid = cost_grid(Vector2::new(3, 1), &[]);
+    let costs = grid.path_costs_stopping(
+        Vector2::new(0, 0),
+        Movement::Cardinal,
+        None,
+        |t| *t.contents(),
+        |pos| pos == Vector2::new(1, 0),
+    );
+    assert_eq!(costs, vec![Some(0.), Some(1.), None]);
+}
+
+#[test]
+pub fn test_find_path_alternating_diagonal() {
+    let grid = cost_grid(Vector2::new(5, 5), &[]);
+    let find = |goal| {
+        grid.find_path(
+            Vector2::new(0, 0),
+            goal,
+            Movement::AlternatingDiagonal,
+            None,
+            |t| *t.contents(),
+        )
+        .unwrap()
+    };
+    // Diagonal steps cost 1, 2, 1, 2...
+    assert_eq!(find(Vector2::new(1, 1)).cost(), 1.);
+    assert_eq!(find(Vector2::new(3, 3)).cost(), 4.);
+    assert_eq!(find(Vector2::new(4, 4)).cost(), 6.);
+    assert_eq!(find(Vector2::new(4, 2)).cost(), 5.);
+    let costs = grid.path_costs(
+        Vector2::new(0, 0),
+        Movement::AlternatingDiagonal,
+        None,
+        |t| *t.contents(),
+    );
+    assert_eq!(costs[grid.index(Vector2::new(2, 2))], Some(3.));
+    assert_eq!(costs[grid.index(Vector2::new(2, 1))], Some(2.));
+}
+
+#[test]
+pub fn test_find_path_corner_cutting() {
+    let grid = cost_grid(Vector2::new(3, 3), &[Vector2::new(1, 0)]);
+    let find = |cut_corners| {
+        let topology = Topology {
+            movement: Movement::Diagonal,
+            cut_corners,
+        };
+        grid.find_path(
+            Vector2::new(0, 0),
+            Vector2::new(2, 0),
+            topology,
+            None,
+            |t| *t.contents(),
+        )
+        .unwrap()
+    };
+    assert!((find(true).cost() - 2. * std::f32::consts::SQRT_2).abs() < 1e-4);
+    // Has to go around the wall instead of squeezing past its corners
+    let path = find(false);
+    assert_eq!(path.cost(), 4.);
+    assert!(path.tiles().windows(2).all(|w| {
+        let diagonal = w[0].x != w[1].x && w[0].y != w[1].y;
+        !diagonal || (w[0].x != 1 && w[1].x != 1)
+    }));
+    let costs = grid.path_costs(
+        Vector2::new(0, 0),
+        Topology {
+            movement: Movement::Diagonal,
+            cut_corners: false,
+        },
+        None,
+        |t| *t.contents(),
+    );
+    assert_eq!(costs[grid.index(Vector2::new(1, 1))], Some(2.));
+}
@@ -1,5 +1,8 @@
 // Prevailing note
 // Take screenshots of bugs for a "bug montage" to What Is Love - Haddaway (https://youtube.com/watch?v=SxQdbtjGEsc)
+#![cfg_attr(feature = "bench", feature(test))]
+pub mod ai;
+pub mod game;
 pub mod interaction;
 pub mod juno;
 pub mod sector;
@@ -7,11 +10,45 @@ pub mod terrain;
 pub mod terrain_old;
 
 use crate::juno::{renderer::quad::TexturedQuad, JunoApp};
+use ai::{Ai, UtilityWeights};
 use cgmath::Vector2;
+use game::{
+    campaign::{load_frontier_campaign, Campaign},
+    net::NetGame,
+    replay::{Replay, ReplayPlayer},
+    scenario::load_skirmish_scenario,
+};
 use interaction::{handle_inputs, GameData};
 use juno::renderer::{renderer::Renderer, testing::TextureAtlasHandle};
+use log::{error, info};
+use sector::{
+    archetype::{load_unit_types_json, UnitRegistry},
+    structure::StructureKind,
+    visibility::Visibility,
+};
 use simplelog::TermLogger;
+use std::net::TcpListener;
 
+// Where the last match played is recorded, unless another file is given with `--record`
+const REPLAY_PATH: &str = "last_replay.json";
+// Where a new campaign is saved when the window is closed
+const CAMPAIGN_PATH: &str = "campaign.json";
+// Network matches played without a window stop after this many turns if nobody has won by then
+const HEADLESS_TURNS: u32 = 50;
+// Teams are told apart by the colour of the overlays on their structures, which come in this many
+// colours along the third row of the overlay atlas
+const TEAM_COLOURS: u32 = 4;
+// Bare ground, drawn under every tile since sprites like trees don't cover the whole tile
+const GROUND_SPRITE: Vector2<u32> = Vector2::new(0, 0);
+
+/// Plays a skirmish against the computer by default. The other options are:
+///  - `--host <address>` to wait for another player to join at the address, and play them
+///  - `--join <address>` to join a player hosting at the address
+///  - `--replay <file>` to watch a replay instead
+///  - `--campaign` to start a new campaign, or `--continue <file>` to carry on with a saved one
+///  - `--headless` to do without a window. Network matches are played by the computer on both
+///    sides, and replays are checked to play out the way they were recorded.
+///  - `--record <file>` to choose where the match is recorded
 fn main() {
     TermLogger::init(
         log::LevelFilter::Info,
@@ -20,11 +57,99 @@ fn main() {
         simplelog::ColorChoice::Always,
     )
     .unwrap();
-    let sector =
-        terrain::generate_terrain(Vector2::new(72, 72), "New terrain test sector".to_string());
+    let unit_types = load_unit_types_json().unwrap();
     let screen_size = Vector2::new(1280, 720);
-    let mut game_data = GameData::new_default(screen_size, sector);
-    let mut app = JunoApp::new(screen_size.x, screen_size.y);
+    let args = std::env::args().collect::<Vec<_>>();
+    let headless = args.iter().any(|a| a == "--headless");
+    if let Some(path) = arg(&args, "--replay") {
+        let replay = Replay::load(path).unwrap();
+        let player = ReplayPlayer::new(replay, &unit_types).unwrap();
+        match headless {
+            true => play_headless(player),
+            false => {
+                run(GameData::new_replay(screen_size, player), &unit_types);
+            }
+        }
+        return;
+    }
+
+    let campaign = match arg(&args, "--continue") {
+        Some(path) => Some((Campaign::load(path).unwrap(), path)),
+        None if args.iter().any(|a| a == "--campaign") => {
+            let map = load_frontier_campaign().unwrap();
+            let campaign = Campaign::new(map, &unit_types, rand::random()).unwrap();
+            Some((campaign, CAMPAIGN_PATH))
+        }
+        None => None,
+    };
+    if let Some((campaign, path)) = campaign {
+        let game_data = GameData::new_campaign(screen_size, campaign, unit_types.clone());
+        let game_data = run(game_data, &unit_types);
+        let campaign = game_data
+            .session()
+            .campaign()
+            .expect("Still playing the campaign");
+        match campaign.save(path) {
+            Ok(()) => info!("Saved campaign to {path}"),
+            Err(e) => error!("{e:?}"),
+        }
+        return;
+    }
+
+    let net = if let Some(address) = arg(&args, "--host") {
+        let listener = TcpListener::bind(address).unwrap();
+        info!("Waiting for another player to join at {address}");
+        let replay = Replay::new(
+            load_skirmish_scenario().unwrap(),
+            rand::random(),
+            rand::random(),
+        );
+        Some(NetGame::host(&listener, replay, &unit_types).unwrap())
+    } else {
+        arg(&args, "--join").map(|address| NetGame::join(address, &unit_types).unwrap())
+    };
+    let (replay, finished) = match net {
+        Some(mut net) if headless => {
+            let finished = play_online_headless(&mut net);
+            (Some(net.replay()), finished)
+        }
+        Some(net) => {
+            let game_data = run(GameData::new_online(screen_size, net), &unit_types);
+            (game_data.session().recording(), true)
+        }
+        None => {
+            let replay = Replay::new(
+                load_skirmish_scenario().unwrap(),
+                rand::random(),
+                rand::random(),
+            );
+            let game_data = GameData::new_default(screen_size, replay, &unit_types).unwrap();
+            (run(game_data, &unit_types).session().recording(), true)
+        }
+    };
+    let path = arg(&args, "--record").unwrap_or(REPLAY_PATH);
+    match replay.map(|r| r.save(path)) {
+        Some(Ok(())) => info!("Saved replay to {path}"),
+        Some(Err(e)) => error!("{e:?}"),
+        None => {}
+    }
+    if !finished {
+        std::process::exit(1);
+    }
+}
+
+/// Value given after `flag` on the command line
+fn arg<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let i = args.iter().position(|a| a == flag)?;
+    Some(
+        args.get(i + 1)
+            .unwrap_or_else(|| panic!("{flag} needs a value")),
+    )
+}
+
+/// Show the game in a window until it's closed
+fn run(mut game_data: GameData, unit_types: &UnitRegistry) -> GameData {
+    let mut app = JunoApp::new(game_data.screen_size().x, game_data.screen_size().y);
     let punyworld = app
         .load_texture_atlas("punyworld-overworld-tileset.png", Vector2::new(16, 16))
         .unwrap();
@@ -34,19 +159,100 @@ fn main() {
 
     while app.update() {
         handle_inputs(&mut game_data, app.input_state());
-        render(app.renderer_mut(), &game_data, &punyworld, &overlays);
+        render(
+            app.renderer_mut(),
+            &game_data,
+            unit_types,
+            &punyworld,
+            &overlays,
+        );
         app.render();
     }
+    game_data
+}
+
+/// Play a replay all the way through, logging the checksum at the start of every phase. Exits
+/// with an error at the first command that doesn't play out the way it was recorded.
+fn play_headless(mut player: ReplayPlayer) {
+    loop {
+        match player.step_forward() {
+            Ok(true) => {
+                let game = player.game();
+                info!(
+                    "Phase {}: turn {}, team {}, checksum {:016x}",
+                    player.phase(),
+                    game.turns().turn(),
+                    game.turns().active_team().id(),
+                    game.checksum()
+                );
+            }
+            Ok(false) => break,
+            Err(e) => {
+                error!("Replay no longer plays out the same way: {e:?}");
+                std::process::exit(1);
+            }
+        }
+    }
+    info!("Replayed all {} commands", player.applied());
+    for (team, outcome) in player.game().outcomes() {
+        info!("Team {} {outcome:?}", team.id());
+    }
+}
+
+/// Play a network match with the computer playing the local team, until it's over or has gone
+/// on for `HEADLESS_TURNS` turns. Both players stop at the same point, since their games match.
+/// Returns whether the match got that far without desyncing or losing the connection.
+fn play_online_headless(net: &mut NetGame) -> bool {
+    let team = net.team();
+    let seed = net.game().seed().wrapping_add(team.id() as u64);
+    let mut ai = Ai::new(team, UtilityWeights::default(), seed);
+    while !net.game().is_over() && net.game().turns().turn() <= HEADLESS_TURNS {
+        let played = match net.game().turns().active_team() == team {
+            true => net.play(|game| {
+                ai.take_turn(game);
+            }),
+            false => net.wait().map(|_| ()),
+        };
+        if let Err(e) = played {
+            error!("Network match ended: {e:?}");
+            return false;
+        }
+    }
+    info!(
+        "Stopped on turn {} with checksum {:016x}",
+        net.game().turns().turn(),
+        net.game().checksum()
+    );
+    for (team, outcome) in net.game().outcomes() {
+        info!("Team {} {outcome:?}", team.id());
+    }
+    true
 }
 
 fn render(
     renderer: &mut Renderer,
     game_data: &GameData,
+    unit_types: &UnitRegistry,
     punyworld: &TextureAtlasHandle,
     overlays: &TextureAtlasHandle,
 ) {
     let edge_len = game_data.tile_edge_len();
-    for tile in game_data.sector().tiles() {
+    let sector = game_data.sector();
+    let team = game_data.player_team();
+    let threats = game_data.threats();
+    for tile in sector.tiles() {
+        let visibility = sector.visibility(team, tile.pos());
+        if visibility == Visibility::Unseen {
+            continue;
+        }
+        renderer.submit_textured_quad(TexturedQuad::new(
+            Vector2::new(
+                (tile.pos().x * edge_len) as i32,
+                (tile.pos().y * edge_len) as i32,
+            ),
+            Vector2::new(edge_len as i32, edge_len as i32),
+            punyworld.texture(GROUND_SPRITE.x, GROUND_SPRITE.y),
+        ));
         renderer.submit_textured_quad(TexturedQuad::new(
             Vector2::new(
                 (tile.pos().x * edge_len) as i32,
@@ -58,6 +264,69 @@ fn render(
                 tile.contents().atlas_position().y,
             ),
         ));
+        if let Some(structure) = sector.structure_at(tile.pos()) {
+            let sprite = structure_sprite(structure.kind);
+            renderer.submit_textured_quad(TexturedQuad::new(
+                Vector2::new(
+                    (tile.pos().x * edge_len) as i32,
+                    (tile.pos().y * edge_len) as i32,
+                ),
+                Vector2::new(edge_len as i32, edge_len as i32),
+                punyworld.texture(sprite.x, sprite.y),
+            ));
+            // Other teams' structures could have changed hands since they were last seen
+            let owner = structure
+                .owner
+                .filter(|o| *o == team || visibility == Visibility::Visible);
+            if let Some(owner) = owner {
+                renderer.submit_textured_quad(TexturedQuad::new(
+                    Vector2::new(
+                        (tile.pos().x * edge_len) as i32,
+                        (tile.pos().y * edge_len) as i32,
+                    ),
+                    Vector2::new(edge_len as i32, edge_len as i32),
+                    overlays.texture(owner.id() % TEAM_COLOURS, 2),
+                ));
+            }
+        }
+        if visibility == Visibility::Explored {
+            renderer.submit_textured_quad(TexturedQuad::new(
+                Vector2::new(
+                    (tile.pos().x * edge_len) as i32,
+                    (tile.pos().y * edge_len) as i32,
+                ),
+                Vector2::new(edge_len as i32, edge_len as i32),
+                overlays.texture(1, 0),
+            ));
+        }
+        if threats.is_some_and(|t| t.is_threatened(tile.pos())) {
+            renderer.submit_textured_quad(TexturedQuad::new(
+                Vector2::new(
+                    (tile.pos().x * edge_len) as i32,
+                    (tile.pos().y * edge_len) as i32,
+                ),
+                Vector2::new(edge_len as i32, edge_len as i32),
+                overlays.texture(1, 1),
+            ));
+        }
+    }
+
+    for unit in sector.visible_units(team) {
+        let Some(unit_type) = unit.archetype().and_then(|id| unit_types.get(id)) else {
+            continue;
+        };
+        // Large units are drawn stretched over every tile they cover
+        renderer.submit_textured_quad(TexturedQuad::new(
+            Vector2::new(
+                (unit.pos().x * edge_len) as i32,
+                (unit.pos().y * edge_len) as i32,
+            ),
+            Vector2::new(
+                (unit.footprint().x * edge_len) as i32,
+                (unit.footprint().y * edge_len) as i32,
+            ),
+            punyworld.texture(unit_type.sprite().x, unit_type.sprite().y),
+        ));
     }
 
     if let Some(sel) = game_data.selected_tile() {
@@ -68,3 +337,12 @@ fn render(
         ));
     }
 }
+
+/// Where each kind of structure is in the tileset
+fn structure_sprite(kind: StructureKind) -> Vector2<u32> {
+    match kind {
+        StructureKind::Village => Vector2::new(6, 26),
+        StructureKind::Fort => Vector2::new(12, 26),
+        StructureKind::Shrine => Vector2::new(2, 29),
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Context, Result};
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+
+use super::{status::StatusEffect, Sector, Team, TerrainCosts, Unit, UnitStats, ZoneOfControl};
+
+/// Template that units are spawned from, so that balancing can happen in `assets/units.json`
+/// instead of in code
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UnitType {
+    id: String,
+    name: String,
+    sprite: Vector2<u32>,
+    movement: f32,
+    health: u32,
+    attack: AttackInfo,
+    defense: u32,
+    sight: u32,
+    // Resources spent to recruit one. Units that can't be bought, such as those that only turn up
+    // in scenarios, can leave it out.
+    #[serde(default)]
+    cost: u32,
+    // Width and height in tiles, for units bigger than a single tile
+    #[serde(default = "single_tile")]
+    footprint: Vector2<u32>,
+    #[serde(default)]
+    terrain_costs: TerrainCosts,
+    #[serde(default)]
+    zone_of_control: ZoneOfControl,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct AttackInfo {
+    damage: u32,
+    range: u32,
+    // Status effect put on targets that survive the attack
+    #[serde(default)]
+    inflicts: Option<StatusEffect>,
+}
+
+impl UnitType {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Position of this unit's sprite in the overworld texture atlas
+    pub fn sprite(&self) -> Vector2<u32> {
+        self.sprite
+    }
+
+    pub fn footprint(&self) -> Vector2<u32> {
+        self.footprint
+    }
+
+    /// Resources spent to recruit one
+    pub fn cost(&self) -> u32 {
+        self.cost
+    }
+
+    pub fn terrain_costs(&self) -> &TerrainCosts {
+        &self.terrain_costs
+    }
+
+    pub fn zone_of_control(&self) -> ZoneOfControl {
+        self.zone_of_control
+    }
+
+    pub fn inflicts(&self) -> Option<StatusEffect> {
+        self.attack.inflicts
+    }
+
+    pub fn stats(&self) -> UnitStats {
+        UnitStats {
+            movement: self.movement,
+            max_health: self.health,
+            attack: self.attack.damage,
+            defense: self.defense,
+            range: self.attack.range,
+            sight: self.sight,
+        }
+    }
+
+    pub(super) fn validate(&self) -> Result<()> {
+        ensure!(!self.id.is_empty(), "Unit type has an empty ID");
+        ensure!(
+            !self.name.is_empty(),
+            "Unit type {} has an empty name",
+            self.id
+        );
+        ensure!(
+            self.movement.is_finite() && self.movement >= 0.,
+            "Unit type {} has invalid movement {}",
+            self.id,
+            self.movement
+        );
+        ensure!(self.health > 0, "Unit type {} has no health", self.id);
+        ensure!(
+            self.footprint.x > 0 && self.footprint.y > 0,
+            "Unit type {} doesn't cover any tiles",
+            self.id
+        );
+        ensure!(
+            self.attack.range > 0,
+            "Unit type {} has an attack range of 0",
+            self.id
+        );
+        if let Some(effect) = self.attack.inflicts {
+            ensure!(
+                effect.turns > 0 && effect.stacks > 0,
+                "Unit type {} inflicts {:?} for no turns or with no stacks",
+                self.id,
+                effect.kind
+            );
+        }
+        for (terrain, cost) in &self.terrain_costs {
+            if let Some(cost) = cost {
+                ensure!(
+                    cost.is_finite() && *cost > 0.,
+                    "Unit type {} has invalid movement cost {} for {:?}",
+                    self.id,
+                    cost,
+                    terrain
+                );
+            }
+        }
+        if let ZoneOfControl::ExtraCost(extra) = self.zone_of_control {
+            ensure!(
+                extra.is_finite() && extra >= 0.,
+                "Unit type {} has invalid zone of control cost {}",
+                self.id,
+                extra
+            );
+        }
+        Ok(())
+    }
+}
+
+fn single_tile() -> Vector2<u32> {
+    Vector2::new(1, 1)
+}
+
+/// Every unit type available to spawn, keyed by ID
+#[derive(Clone, Debug, Default)]
+pub struct UnitRegistry {
+    types: HashMap<String, UnitType>,
+}
+
+impl UnitRegistry {
+    /// Parse and validate a JSON list of unit types
+    pub fn from_json(json: &str) -> Result<Self> {
+        let types: Vec<UnitType> =
+            serde_json::from_str(json).context("Could not parse unit types")?;
+        let mut registry = Self::default();
+        for unit_type in types {
+            registry.register(unit_type)?;
+        }
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, unit_type: UnitType) -> Result<()> {
+        unit_type.validate()?;
+        if self.types.contains_key(&unit_type.id) {
+            bail!("Unit type {} is defined more than once", unit_type.id);
+        }
+        self.types.insert(unit_type.id.clone(), unit_type);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&UnitType> {
+        self.types.get(id)
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = &UnitType> {
+        self.types.values()
+    }
+
+    /// Create a unit of type `id` on `pos`, ready to be added to `sector`
+    pub fn spawn(&self, id: &str, pos: Vector2<u32>, team: Team, sector: &Sector) -> Result<Unit> {
+        let unit_type = self
+            .get(id)
+            .with_context(|| format!("No unit type with ID {}", id))?;
+        Unit::from_type(unit_type, pos, team, sector)
+    }
+}
+
+/// The unit types built into the game, from `assets/units.json`
+pub fn load_unit_types_json() -> Result<UnitRegistry> {
+    UnitRegistry::from_json(include_str!("../../assets/units.json"))
+}
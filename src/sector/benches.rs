@@ -0,0 +1,64 @@
+extern crate test;
+
+use cgmath::Vector2;
+use test::{black_box, Bencher};
+
+use crate::juno::{benches::serpentine_walls, pathfinding::Movement};
+
+use super::{
+    navigation::{self, NavigationBitmask},
+    tests::{spawn, test_sector},
+    Sector, Team, UnitId,
+};
+
+const SIZE: Vector2<u32> = Vector2::new(64, 64);
+
+/// Sector with serpentine walls and a unit in the corner that could move anywhere in it
+fn walled_sector() -> (Sector, UnitId) {
+    let mut sector = test_sector(SIZE, &serpentine_walls(SIZE));
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 10000., Team::new(0));
+    (sector, unit)
+}
+
+#[bench]
+fn bench_find_unit_path(b: &mut Bencher) {
+    let (sector, unit) = walled_sector();
+    b.iter(|| {
+        black_box(
+            sector
+                .find_unit_path(unit, SIZE - Vector2::new(1, 1), None)
+                .unwrap(),
+        )
+    });
+}
+
+#[bench]
+fn bench_movable_tiles(b: &mut Bencher) {
+    let (sector, unit) = walled_sector();
+    let unit = sector.unit(unit).unwrap();
+    b.iter(|| {
+        black_box(navigation::movable_tiles(
+            unit,
+            &sector,
+            unit.max_movement(),
+        ))
+    });
+}
+
+#[bench]
+fn bench_navigation(b: &mut Bencher) {
+    let (mut sector, unit) = walled_sector();
+    sector.set_topology(Movement::Diagonal.into());
+    let unit = sector.unit(unit).unwrap();
+    b.iter(|| black_box(NavigationBitmask::generate(unit, &sector).unwrap()));
+}
+
+#[bench]
+fn bench_threat_map(b: &mut Bencher) {
+    let mut sector = test_sector(SIZE, &[]);
+    for i in 0..8 {
+        spawn(&mut sector, Vector2::new(i * 8, 0), 6., Team::new(0));
+        spawn(&mut sector, Vector2::new(i * 8, 8), 6., Team::new(1));
+    }
+    b.iter(|| black_box(sector.threat_map(Team::new(0))));
+}
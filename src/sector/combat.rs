@@ -0,0 +1,152 @@
+use cgmath::Vector2;
+use rand::Rng;
+
+use crate::juno::directions;
+
+use super::{event::SectorEvent, footprint_tiles, ActionError, Sector, Unit, UnitId};
+
+// Damage is scaled by a random roll in this range, so that identical attacks don't always land
+// for the same amount
+const MIN_DAMAGE_ROLL: f32 = 0.8;
+const MAX_DAMAGE_ROLL: f32 = 1.2;
+
+/// Outcome of a single attack
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttackResult {
+    pub damage: u32,
+    pub killed: bool,
+}
+
+impl Sector {
+    /// Have `attacker` attack `target`, spending one of its actions. The target is removed from
+    /// the sector if the attack kills it, and otherwise gets any effect the attacker inflicts.
+    pub fn attack_unit<R: Rng>(
+        &mut self,
+        attacker: UnitId,
+        target: UnitId,
+        rng: &mut R,
+    ) -> Result<AttackResult, ActionError> {
+        self.check_attack(attacker, target)?;
+        let base_damage = {
+            let attacking_unit = self.unit(attacker).ok_or(ActionError::NoUnit)?;
+            let target_unit = self.unit(target).ok_or(ActionError::NoUnit)?;
+            self.base_damage(attacking_unit, target_unit)
+        };
+        let roll = rng.gen_range(MIN_DAMAGE_ROLL..=MAX_DAMAGE_ROLL);
+        let damage = ((base_damage as f32 * roll).round() as u32).max(1);
+
+        let inflicts = self.unit_mut(attacker).and_then(|unit| {
+            unit.actions -= 1;
+            unit.inflicts()
+        });
+        let target_unit = self.unit_mut(target).ok_or(ActionError::NoUnit)?;
+        target_unit.health = target_unit.health.saturating_sub(damage);
+        let killed = !target_unit.alive();
+        let (pos, team) = (target_unit.pos(), target_unit.team());
+        self.events.push(SectorEvent::UnitAttacked {
+            attacker,
+            target,
+            damage,
+        });
+        if killed {
+            self.remove_unit(target);
+            self.events.push(SectorEvent::UnitDied {
+                unit: target,
+                pos,
+                team,
+            });
+        } else if let Some(effect) = inflicts {
+            self.apply_effect(target, effect)?;
+        }
+        Ok(AttackResult { damage, killed })
+    }
+
+    /// Check whether `attacker` could attack `target` right now
+    pub fn check_attack(&self, attacker: UnitId, target: UnitId) -> Result<(), ActionError> {
+        let attacking_unit = self.unit(attacker).ok_or(ActionError::NoUnit)?;
+        let target_unit = self.unit(target).ok_or(ActionError::NoUnit)?;
+        if attacking_unit.team() == target_unit.team() {
+            return Err(ActionError::SameTeam);
+        }
+        if attacking_unit.actions() == 0 {
+            return Err(ActionError::NoActionsLeft);
+        }
+        self.check_attack_from(attacking_unit, attacking_unit.pos(), target_unit)
+    }
+
+    /// Check whether `attacker` would have `target` in range and in sight if it were standing on
+    /// `pos`, ignoring everything else about whether it could attack
+    pub fn check_attack_from(
+        &self,
+        attacker: &Unit,
+        pos: Vector2<u32>,
+        target: &Unit,
+    ) -> Result<(), ActionError> {
+        let (from, to) = attack_tiles(attacker, pos, target);
+        if !in_range(attacker, from, to) {
+            return Err(ActionError::OutOfRange);
+        }
+        if !self.can_see(attacker.team(), to) || !self.line_of_sight(from, to) {
+            return Err(ActionError::NoLineOfSight);
+        }
+        Ok(())
+    }
+
+    /// Damage that `attacker` would deal to `target` before the random roll, after taking into
+    /// account the target's defense and the terrain it stands on
+    pub fn base_damage(&self, attacker: &Unit, target: &Unit) -> u32 {
+        let terrain_bonus = self.terrain_defense(target.pos(), target.footprint());
+        mitigated_damage(attacker.attack(), target.defense() + terrain_bonus)
+    }
+
+    /// Defense bonus for a unit covering `footprint` tiles from `pos`. Large units get the best
+    /// bonus of any tile they cover.
+    pub fn terrain_defense(&self, pos: Vector2<u32>, footprint: Vector2<u32>) -> u32 {
+        footprint_tiles(pos, footprint)
+            .filter_map(|p| self.tile(p))
+            .map(|t| t.contents().terrain().defense_bonus())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether there is a clear line between two tiles. Only the tiles in between are checked, so
+    /// units can still see out of (and into) a forest they're standing in.
+    pub fn line_of_sight(&self, from: Vector2<u32>, to: Vector2<u32>) -> bool {
+        let elevation = self.tile(from).map_or(0, |t| t.contents().elevation());
+        let line = directions::line(from, to);
+        line.iter()
+            .skip(1)
+            .take(line.len().saturating_sub(2))
+            .all(|pos| !self.blocks_sight(elevation, *pos))
+    }
+}
+
+/// Damage from an attack against a defense, before the random roll. Every hit does some damage.
+pub fn mitigated_damage(attack: u32, defense: u32) -> u32 {
+    attack.saturating_sub(defense).max(1)
+}
+
+/// Most damage that an attack could do against a defense, with the best possible roll
+pub fn max_damage(attack: u32, defense: u32) -> u32 {
+    (mitigated_damage(attack, defense) as f32 * MAX_DAMAGE_ROLL).round() as u32
+}
+
+/// Where an attack by `attacker` standing on `pos` against `target` would go between. Large units
+/// attack from whichever of their tiles is closest to the target, and large targets are attacked
+/// on whichever of their tiles is closest to that.
+pub fn attack_tiles(
+    attacker: &Unit,
+    pos: Vector2<u32>,
+    target: &Unit,
+) -> (Vector2<u32>, Vector2<u32>) {
+    footprint_tiles(pos, attacker.footprint())
+        .map(|from| (from, target.closest_tile(from)))
+        .min_by_key(|(from, to)| directions::manhattan_distance(*from, *to))
+        .unwrap_or((pos, target.closest_tile(pos)))
+}
+
+/// Whether `target` is within range of `unit` if it were standing on `from`
+pub fn in_range(unit: &Unit, from: Vector2<u32>, target: Vector2<u32>) -> bool {
+    let distance = directions::manhattan_distance(from, target);
+    distance >= 1 && distance <= unit.stats().range
+}
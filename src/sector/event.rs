@@ -0,0 +1,65 @@
+use cgmath::Vector2;
+
+use crate::juno::pathfinding::Path;
+
+use super::{
+    status::{StatusEffect, StatusKind},
+    Team, UnitId,
+};
+
+/// Something that happened in a sector, queued up so that rendering and game logic can react to
+/// it. See `Sector::drain_events`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SectorEvent {
+    /// A unit moved from one tile to another, stepping over every tile in `path`
+    UnitMoved {
+        unit: UnitId,
+        from: Vector2<u32>,
+        to: Vector2<u32>,
+        path: Path,
+    },
+    /// A unit ran into the hidden enemy `enemy`, and was stopped in its tracks on `pos`
+    UnitAmbushed {
+        unit: UnitId,
+        pos: Vector2<u32>,
+        enemy: UnitId,
+    },
+    /// One unit hit another
+    UnitAttacked {
+        attacker: UnitId,
+        target: UnitId,
+        damage: u32,
+    },
+    /// A status effect was put on a unit, or stacked onto one of the same kind
+    EffectApplied { unit: UnitId, effect: StatusEffect },
+    /// A unit took damage from one of its status effects at the start of its phase
+    EffectDamaged {
+        unit: UnitId,
+        kind: StatusKind,
+        damage: u32,
+    },
+    /// A status effect ran out or was removed
+    EffectExpired { unit: UnitId, kind: StatusKind },
+    /// A unit was recruited from the structure on `from`
+    UnitRecruited {
+        unit: UnitId,
+        from: Vector2<u32>,
+        team: Team,
+    },
+    /// A recruit was taken back, and removed from the sector
+    RecruitReverted { unit: UnitId, from: Vector2<u32> },
+    /// A unit was healed by a structure at the start of its phase
+    UnitHealed { unit: UnitId, amount: u32 },
+    /// `team` captured the structure on `pos`, taking it from its previous owner if it had one
+    StructureCaptured {
+        pos: Vector2<u32>,
+        team: Team,
+        from: Option<Team>,
+    },
+    /// A unit was killed on `pos` and removed from the sector
+    UnitDied {
+        unit: UnitId,
+        pos: Vector2<u32>,
+        team: Team,
+    },
+}
@@ -0,0 +1,1010 @@
+pub mod archetype;
+pub mod combat;
+pub mod event;
+pub mod navigation;
+pub mod objective;
+pub mod recruit;
+pub mod status;
+pub mod structure;
+pub mod threat;
+pub mod visibility;
+
+use std::{
+    cell::RefCell,
+    collections::{btree_map, BTreeMap, HashMap},
+    slice::Iter,
+    vec::Drain,
+};
+
+use anyhow::{anyhow, bail, ensure};
+use cgmath::Vector2;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::juno::{
+    directions,
+    grid::{Grid, GridItem},
+    pathfinding::{Movement, Path, Topology},
+};
+
+use self::{
+    archetype::UnitType,
+    event::SectorEvent,
+    navigation::NavigationBitmask,
+    objective::Objective,
+    status::{Modifiers, StatusEffect},
+    structure::Structure,
+    visibility::VisibilityMap,
+};
+
+#[cfg(test)]
+pub mod tests;
+#[cfg(all(test, feature = "bench"))]
+mod benches;
+
+#[derive(Deserialize, Serialize)]
+pub struct Sector {
+    name: String,
+    tiles: Grid<Tile>,
+    // What the terrain was generated from, unless it was made some other way
+    terrain_seed: Option<u64>,
+    units: BTreeMap<UnitId, Unit>,
+    // Which unit is standing on each occupied tile, including every tile covered by a large
+    // unit. Kept in step with `units` by every method that adds, moves or removes a unit, and
+    // rebuilt by `restore` after loading.
+    #[serde(skip)]
+    occupancy: HashMap<Vector2<u32>, UnitId>,
+    next_unit_id: u32,
+    #[serde(skip)]
+    events: Vec<SectorEvent>,
+    // Ordered so that the saved state, and its checksum, is always the same
+    visibility: BTreeMap<Team, VisibilityMap>,
+    objectives: Vec<Objective>,
+    // How units get from tile to tile, which is up to the ruleset being played
+    topology: Topology,
+    // At most one on each tile
+    structures: Vec<Structure>,
+    // Income from structures that each team hasn't spent yet. Ordered for the same reason as
+    // `visibility`.
+    resources: BTreeMap<Team, u32>,
+    // Unit types that can be recruited here, kept with the sector so that loaded games and
+    // replays can recruit without the unit types they were started with
+    roster: Vec<UnitType>,
+    // Changed whenever the terrain, or the units and what each team can see of them, change, so
+    // that cached navigation can tell when it's out of date. Never saved, since a loaded sector
+    // shouldn't trust anything cached before it was saved.
+    #[serde(skip, default = "navigation::next_version")]
+    terrain_version: u64,
+    #[serde(skip, default = "navigation::next_version")]
+    units_version: u64,
+}
+
+impl Sector {
+    pub fn new(name: String, tiles: Grid<Tile>, units: Vec<Unit>) -> Self {
+        let mut sector = Self {
+            name,
+            tiles,
+            terrain_seed: None,
+            units: BTreeMap::new(),
+            occupancy: HashMap::new(),
+            next_unit_id: 0,
+            events: Vec::new(),
+            visibility: BTreeMap::new(),
+            objectives: Vec::new(),
+            topology: Topology::default(),
+            structures: Vec::new(),
+            resources: BTreeMap::new(),
+            roster: Vec::new(),
+            terrain_version: navigation::next_version(),
+            units_version: navigation::next_version(),
+        };
+        for unit in units {
+            let pos = unit.pos();
+            if let Err(e) = sector.insert_unit(unit) {
+                warn!("Could not place unit at {pos:?}: {e:?}");
+            }
+        }
+        sector.units_changed();
+        return sector;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.tiles.size().x
+    }
+
+    pub fn height(&self) -> u32 {
+        self.tiles.size().y
+    }
+
+    pub fn size(&self) -> Vector2<u32> {
+        self.tiles.size()
+    }
+
+    pub fn name(&self) -> &str {
+        return self.name.as_str();
+    }
+
+    /// The seed the terrain was generated from, if it was generated
+    pub fn terrain_seed(&self) -> Option<u64> {
+        self.terrain_seed
+    }
+
+    pub fn set_terrain_seed(&mut self, seed: Option<u64>) {
+        self.terrain_seed = seed;
+    }
+
+    /// Every unit in the sector, in order of ID
+    pub fn units(&self) -> btree_map::Values<'_, UnitId, Unit> {
+        self.units.values()
+    }
+
+    pub fn units_mut(&mut self) -> btree_map::ValuesMut<'_, UnitId, Unit> {
+        self.units.values_mut()
+    }
+
+    pub fn tile(&self, pos: Vector2<u32>) -> Option<&GridItem<Tile>> {
+        return self.tiles.tile(pos);
+    }
+
+    pub fn tile_mut(&mut self, pos: Vector2<u32>) -> Option<&mut GridItem<Tile>> {
+        // The tile may be about to change, and there's no telling afterwards
+        self.terrain_version = navigation::next_version();
+        return self.tiles.tile_mut(pos);
+    }
+
+    pub fn tiles(&self) -> Iter<'_, GridItem<Tile>> {
+        return self.tiles.tiles();
+    }
+
+    /// How units move between tiles, for their navigation, pathfinding and threat
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// Place a unit in the sector, giving it a new ID. IDs are never reused, even once the unit
+    /// they belonged to has been removed.
+    pub fn add_unit(&mut self, unit: Unit) -> Result<UnitId, ActionError> {
+        let id = self.insert_unit(unit)?;
+        self.units_changed();
+        Ok(id)
+    }
+
+    /// Place a unit where it can stand closest to `near`, with every tile it covers free, giving
+    /// it a new ID. Fails with `Occupied` if there's nowhere for it to go.
+    pub fn place_unit(
+        &mut self,
+        mut unit: Unit,
+        near: Vector2<u32>,
+    ) -> Result<UnitId, ActionError> {
+        unit.pos = self
+            .tiles()
+            .map(|t| t.pos())
+            .filter(|pos| {
+                footprint_tiles(*pos, unit.footprint).all(|p| {
+                    !self.occupancy.contains_key(&p)
+                        && self
+                            .tile(p)
+                            .is_some_and(|t| unit.movement_cost(t.contents()).is_some())
+                })
+            })
+            .min_by_key(|pos| directions::manhattan_distance(*pos, near))
+            .ok_or(ActionError::Occupied)?;
+        self.add_unit(unit)
+    }
+
+    fn insert_unit(&mut self, mut unit: Unit) -> Result<UnitId, ActionError> {
+        self.check_footprint(None, unit.pos, unit.footprint)?;
+        let id = UnitId(self.next_unit_id);
+        self.next_unit_id += 1;
+        unit.id = id;
+        for pos in unit.tiles() {
+            self.occupancy.insert(pos, id);
+        }
+        self.units.insert(id, unit);
+        Ok(id)
+    }
+
+    /// Check that a unit with `footprint` would fit on `pos`, with every tile it covers inside
+    /// the sector and not taken by any unit other than `id`
+    fn check_footprint(
+        &self,
+        id: Option<UnitId>,
+        pos: Vector2<u32>,
+        footprint: Vector2<u32>,
+    ) -> Result<(), ActionError> {
+        for covered in footprint_tiles(pos, footprint) {
+            if !self.tiles.contains(covered) {
+                return Err(ActionError::OutOfBounds);
+            }
+            if self
+                .occupancy
+                .get(&covered)
+                .is_some_and(|other| Some(*other) != id)
+            {
+                return Err(ActionError::Occupied);
+            }
+        }
+        Ok(())
+    }
+
+    /// Move a unit's entries in the occupancy index from `from` to wherever it is now
+    fn reoccupy(&mut self, id: UnitId, from: Vector2<u32>) {
+        let Some(unit) = self.units.get(&id) else {
+            return;
+        };
+        for pos in footprint_tiles(from, unit.footprint) {
+            self.occupancy.remove(&pos);
+        }
+        for pos in unit.tiles() {
+            self.occupancy.insert(pos, id);
+        }
+    }
+
+    /// Take a unit out of the sector, returning it if it was there
+    pub fn remove_unit(&mut self, id: UnitId) -> Option<Unit> {
+        let unit = self.units.remove(&id)?;
+        for pos in unit.tiles() {
+            self.occupancy.remove(&pos);
+        }
+        self.units_changed();
+        Some(unit)
+    }
+
+    pub fn unit(&self, id: UnitId) -> Option<&Unit> {
+        self.units.get(&id)
+    }
+
+    pub fn unit_mut(&mut self, id: UnitId) -> Option<&mut Unit> {
+        self.units.get_mut(&id)
+    }
+
+    /// ID of the unit standing on `pos`, or covering it, if there is one
+    pub fn unit_id_at(&self, pos: Vector2<u32>) -> Option<UnitId> {
+        self.occupancy.get(&pos).copied()
+    }
+
+    pub fn unit_at_tile(&self, pos: Vector2<u32>) -> Option<&Unit> {
+        self.unit_id_at(pos).and_then(|id| self.units.get(&id))
+    }
+
+    pub fn unit_at_tile_mut(&mut self, pos: Vector2<u32>) -> Option<&mut Unit> {
+        let id = self.unit_id_at(pos)?;
+        self.units.get_mut(&id)
+    }
+
+    /// Every unit covering a tile within `radius` cardinal steps of `center`, in order of ID
+    pub fn units_in_radius(&self, center: Vector2<u32>, radius: u32) -> Vec<&Unit> {
+        // Check whichever is smaller: the units in the sector, or the tiles in the radius
+        let area = 2 * radius as u64 * (radius as u64 + 1) + 1;
+        let mut units = match area > self.units.len() as u64 {
+            true => self
+                .units
+                .values()
+                .filter(|u| {
+                    directions::manhattan_distance(center, u.closest_tile(center)) <= radius
+                })
+                .collect::<Vec<_>>(),
+            false => {
+                let radius = radius as i32;
+                (-radius..=radius)
+                    .flat_map(|dy| {
+                        let width = radius - dy.abs();
+                        (-width..=width).map(move |dx| Vector2::new(dx, dy))
+                    })
+                    .filter_map(|offset| self.tiles.offset(center, offset))
+                    .filter_map(|pos| self.unit_at_tile(pos))
+                    .collect::<Vec<_>>()
+            }
+        };
+        // Large units cover more than one tile in the radius
+        units.sort_by_key(|u| u.id);
+        units.dedup_by_key(|u| u.id);
+        units
+    }
+
+    /// Move a unit to `to`, spending the cost of the path taken from its movement. The
+    /// destination has to be within the unit's current navigation.
+    ///
+    /// Units plan around the enemies they can see, so a path can run into a hidden enemy. When
+    /// that happens the unit is ambushed: it stops on the last free tile before the enemy and
+    /// loses the rest of its movement. The returned path is the one actually taken.
+    pub fn move_unit(&mut self, id: UnitId, to: Vector2<u32>) -> Result<Path, ActionError> {
+        if !self.tiles.contains(to) {
+            return Err(ActionError::OutOfBounds);
+        }
+        let unit = self.unit(id).ok_or(ActionError::NoUnit)?;
+        let from = unit.pos();
+        if from == to {
+            return Err(ActionError::AlreadyThere);
+        }
+        if unit.can_reach_tile(to, self) != Some(true) {
+            return Err(ActionError::Unreachable);
+        }
+        let planned = self
+            .find_unit_path(id, to, Some(unit.movement()))
+            .ok_or(ActionError::Unreachable)?;
+        let (path, ambusher) = self.walk_path(unit, &planned);
+        if path.steps() == 0 && ambusher.is_none() {
+            return Err(ActionError::Unreachable);
+        }
+
+        let to = path.end();
+        // Entering a zone of control that stops movement uses up the rest of it, so the unit
+        // can't carry on with a second move
+        let stopped = ambusher.is_some() || navigation_stops(to, unit, self);
+        let unit = self.units.get_mut(&id).ok_or(ActionError::NoUnit)?;
+        unit.pos = to;
+        unit.movement = match stopped {
+            true => 0.,
+            false => (unit.movement - path.cost()).max(0.),
+        };
+        self.reoccupy(id, from);
+        if path.steps() > 0 {
+            self.events.push(SectorEvent::UnitMoved {
+                unit: id,
+                from,
+                to,
+                path: path.clone(),
+            });
+        }
+        if let Some(enemy) = ambusher {
+            self.events.push(SectorEvent::UnitAmbushed {
+                unit: id,
+                pos: to,
+                enemy,
+            });
+        }
+        self.units_changed();
+        Ok(path)
+    }
+
+    /// Put a unit back where it started along `path`, with `movement` left. Used to undo moves,
+    /// and shows up as an ordinary move back along the same path.
+    pub fn revert_move(
+        &mut self,
+        id: UnitId,
+        path: &Path,
+        movement: f32,
+    ) -> Result<(), ActionError> {
+        let (from, to) = (path.end(), path.start());
+        let unit = self
+            .unit(id)
+            .filter(|u| u.pos == from)
+            .ok_or(ActionError::NoUnit)?;
+        self.check_footprint(Some(id), to, unit.footprint)?;
+        let unit = self.units.get_mut(&id).ok_or(ActionError::NoUnit)?;
+        unit.pos = to;
+        unit.movement = movement;
+        self.reoccupy(id, from);
+        self.events.push(SectorEvent::UnitMoved {
+            unit: id,
+            from,
+            to,
+            path: path.reversed(),
+        });
+        self.units_changed();
+        Ok(())
+    }
+
+    /// Follow `planned` for `unit` until it runs into an enemy it couldn't see, returning the
+    /// path that was actually walked and the enemy, if there was one. A unit can't be left
+    /// standing on an ally, so it backs up to the last place where every tile it covers is free.
+    fn walk_path(&self, unit: &Unit, planned: &Path) -> (Path, Option<UnitId>) {
+        let tiles = planned.tiles();
+        let others = |pos: Vector2<u32>| {
+            footprint_tiles(pos, unit.footprint)
+                .filter_map(|p| self.unit_at_tile(p))
+                .filter(|other| other.id() != unit.id())
+        };
+        let ambush = tiles.iter().enumerate().find_map(|(i, p)| {
+            let enemy = others(*p).find(|other| other.team() != unit.team())?;
+            Some((i, enemy.id()))
+        });
+        let mut stop = ambush.map_or(tiles.len() - 1, |(i, _)| i - 1);
+        while stop > 0 && others(tiles[stop]).next().is_some() {
+            stop -= 1;
+        }
+        (planned.prefix(stop), ambush.map(|(_, enemy)| enemy))
+    }
+
+    /// Take every event that has happened since the last call, oldest first
+    pub fn drain_events(&mut self) -> Drain<'_, SectorEvent> {
+        self.events.drain(..)
+    }
+
+    /// Find the cheapest path between two tiles, going around impassable terrain and any tile
+    /// occupied by a unit other than the one standing on `start`
+    pub fn find_path(
+        &self,
+        start: Vector2<u32>,
+        goal: Vector2<u32>,
+        movement: Movement,
+        max_cost: Option<f32>,
+    ) -> Option<Path> {
+        self.tiles.find_path(start, goal, movement, max_cost, |t| {
+            match self.occupancy.contains_key(&t.pos()) {
+                true => None,
+                false => t.contents().movement_cost(),
+            }
+        })
+    }
+
+    /// Find the cheapest path for a unit, following the same rules as its navigation: enemies
+    /// block the way, allies can be passed through but not stopped on, the unit's zone of
+    /// control rule applies, and it moves by the sector's topology
+    pub fn find_unit_path(
+        &self,
+        id: UnitId,
+        goal: Vector2<u32>,
+        max_cost: Option<f32>,
+    ) -> Option<Path> {
+        let unit = self.unit(id)?;
+        let blocked = footprint_tiles(goal, unit.footprint).any(|p| {
+            self.known_unit_at(unit.team(), p)
+                .is_some_and(|other| other.id() != id)
+        });
+        if blocked {
+            return None;
+        }
+        self.tiles.find_path_stopping(
+            unit.pos(),
+            goal,
+            self.topology,
+            max_cost,
+            |t| navigation_cost(t, unit, self),
+            |pos| navigation_stops(pos, unit, self),
+        )
+    }
+
+    /// Check a sector that has just been deserialized, and rebuild everything that isn't saved
+    /// along with it
+    pub fn restore(&mut self) -> anyhow::Result<()> {
+        ensure!(
+            self.tiles.is_complete(),
+            "Sector {} is missing tiles",
+            self.name
+        );
+        self.occupancy.clear();
+        for (id, unit) in &mut self.units {
+            ensure!(
+                id.0 < self.next_unit_id,
+                "Unit {} was never assigned by sector {}",
+                id.0,
+                self.name
+            );
+            ensure!(
+                unit.footprint.x > 0 && unit.footprint.y > 0,
+                "Unit {} doesn't cover any tiles",
+                id.0
+            );
+            for pos in unit.tiles() {
+                ensure!(
+                    self.tiles.contains(pos),
+                    "Unit {} is outside of sector {}",
+                    id.0,
+                    self.name
+                );
+                if let Some(other) = self.occupancy.insert(pos, *id) {
+                    bail!("Units {} and {} are both on {:?}", other.0, id.0, pos);
+                }
+            }
+            unit.id = *id;
+        }
+        let tile_count = self.tiles.tiles().len();
+        for (team, map) in &self.visibility {
+            ensure!(
+                map.tiles().len() == tile_count,
+                "Visibility of team {} doesn't match the size of sector {}",
+                team.id(),
+                self.name
+            );
+        }
+        for objective in &self.objectives {
+            self.check_objective(objective)?;
+        }
+        for (i, structure) in self.structures.iter().enumerate() {
+            self.check_structure(structure)?;
+            ensure!(
+                self.structures[..i].iter().all(|s| s.pos != structure.pos),
+                "Sector {} has more than one structure on {:?}",
+                self.name,
+                structure.pos
+            );
+        }
+        for unit_type in &self.roster {
+            unit_type.validate()?;
+        }
+        self.units_version = navigation::next_version();
+        Ok(())
+    }
+
+    /// Whether `pos` is next to an enemy of `team` that `team` can see
+    pub fn in_enemy_zone(&self, team: Team, pos: Vector2<u32>) -> bool {
+        directions::cardinal()
+            .filter_map(|d| self.tiles.offset(pos, *d))
+            .filter_map(|p| self.known_unit_at(team, p))
+            .any(|u| u.team() != team)
+    }
+
+    /// Bring everything that depends on unit positions up to date. Needs to be called whenever a
+    /// unit is added, moved or removed, or a structure changes hands.
+    fn units_changed(&mut self) {
+        self.refresh_visibility();
+    }
+
+    /// Restore the movement and actions of every unit on `team`
+    pub fn refresh_team(&mut self, team: Team) {
+        self.units
+            .values_mut()
+            .filter(|u| u.team == team)
+            .for_each(Unit::refresh);
+    }
+
+    pub fn index(&self, pos: Vector2<u32>) -> u32 {
+        return self.size().x * pos.y + pos.x;
+    }
+
+    pub fn from_index(&self, index: u32) -> Vector2<u32> {
+        return Vector2::new(index % self.width(), index / self.width());
+    }
+}
+
+/// Reasons that an action on the sector can be rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionError {
+    /// There's no unit with the given ID, or on the given tile
+    NoUnit,
+    /// The target tile is outside of the sector
+    OutOfBounds,
+    /// The unit is already standing on the target tile
+    AlreadyThere,
+    /// Another unit is already standing on the target tile
+    Occupied,
+    /// The unit can't reach the target tile with its remaining movement
+    Unreachable,
+    /// The unit belongs to a team other than the one currently taking its turn
+    NotYourTurn,
+    /// The unit has already used all of its actions this turn
+    NoActionsLeft,
+    /// Units can't attack their own team
+    SameTeam,
+    /// The target is further away than the attacker's range
+    OutOfRange,
+    /// Terrain between the attacker and the target blocks the attack
+    NoLineOfSight,
+    /// There's no command in the history to undo
+    NothingToUndo,
+    /// The last command can't be undone, because it revealed something or relied on chance
+    Irreversible,
+    /// There's no undone command to redo
+    NothingToRedo,
+    /// The team has already won, lost or drawn
+    GameOver,
+    /// There's no structure on the given tile that the team owns and can recruit from
+    NoRecruiter,
+    /// The structure has already recruited as many units as it can this turn
+    RecruitLimit,
+    /// The unit type can't be recruited in this sector
+    NotInRoster,
+    /// The team can't afford it
+    NotEnoughResources,
+    /// Recruits have to start next to the structure they were recruited from
+    NotAdjacent,
+    /// The unit can't stand on the target tile
+    Impassable,
+}
+
+/// Cost for `unit` to move so that its position is `tile`. Enemy units that the unit's team can
+/// see block the way if they're on any tile the unit would cover, while allied units can be moved
+/// through.
+fn navigation_cost(tile: &GridItem<Tile>, unit: &Unit, sector: &Sector) -> Option<f32> {
+    let team = unit.team();
+    let blocked = footprint_tiles(tile.pos(), unit.footprint)
+        .filter_map(|p| sector.known_unit_at(team, p))
+        .any(|other| other.team() != team);
+    if blocked {
+        return None;
+    }
+    let cost = footprint_cost(tile.pos(), unit, sector)?;
+    match unit.zone_of_control() {
+        ZoneOfControl::ExtraCost(extra) if footprint_in_enemy_zone(tile.pos(), unit, sector) => {
+            Some(cost + extra)
+        }
+        _ => Some(cost),
+    }
+}
+
+/// Whether `unit` has to stop when it moves to `pos`
+fn navigation_stops(pos: Vector2<u32>, unit: &Unit, sector: &Sector) -> bool {
+    unit.zone_of_control() == ZoneOfControl::Stop && footprint_in_enemy_zone(pos, unit, sector)
+}
+
+/// Terrain cost for `unit` to move so that its position is `pos`, ignoring other units. Large
+/// units move at the pace of the slowest tile they'd cover, and can't go anywhere that any of
+/// them is impassable or outside the sector.
+fn footprint_cost(pos: Vector2<u32>, unit: &Unit, sector: &Sector) -> Option<f32> {
+    footprint_tiles(pos, unit.footprint).try_fold(0., |cost: f32, p| {
+        let tile = sector.tile(p)?;
+        Some(cost.max(unit.movement_cost(tile.contents())?))
+    })
+}
+
+/// Whether any tile `unit` would cover on `pos` is in the zone of control of an enemy
+fn footprint_in_enemy_zone(pos: Vector2<u32>, unit: &Unit, sector: &Sector) -> bool {
+    footprint_tiles(pos, unit.footprint).any(|p| sector.in_enemy_zone(unit.team(), p))
+}
+
+/// Every tile covered by something with `footprint` on `pos`, which is its top left tile. Some of
+/// them may be outside the sector.
+fn footprint_tiles(
+    pos: Vector2<u32>,
+    footprint: Vector2<u32>,
+) -> impl Iterator<Item = Vector2<u32>> {
+    (0..footprint.y).flat_map(move |dy| (0..footprint.x).map(move |dx| pos + Vector2::new(dx, dy)))
+}
+
+/// Stable handle to a unit in a sector, which stays the same as the unit moves around
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize,
+)]
+pub struct UnitId(u32);
+
+impl UnitId {
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Side that a unit fights for. Units on the same team are allies, and all others are enemies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct Team(u32);
+
+impl Team {
+    pub fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+// Number of actions (such as attacks) that a unit can take each turn
+const DEFAULT_ACTIONS: u32 = 1;
+
+/// Fixed attributes of a unit, which don't change over the course of a battle
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct UnitStats {
+    pub movement: f32,
+    pub max_health: u32,
+    pub attack: u32,
+    pub defense: u32,
+    /// Furthest distance (in cardinal steps) that the unit can attack
+    pub range: u32,
+    /// How many tiles away the unit can see
+    pub sight: u32,
+}
+
+/// Movement cost for particular terrains, overriding the terrain's usual cost. `None` makes the
+/// terrain impassable. Ordered so that saved units are always the same.
+pub type TerrainCosts = BTreeMap<Terrain, Option<f32>>;
+
+/// How a unit is held up by moving next to enemies. Only enemies that the unit's team can see
+/// exert a zone of control.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneOfControl {
+    /// Enemies don't affect movement
+    #[default]
+    None,
+    /// Entering a tile next to an enemy ends the unit's movement
+    Stop,
+    /// Entering a tile next to an enemy costs this much more than usual
+    ExtraCost(f32),
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Unit {
+    // Assigned by the sector when the unit is added to it
+    id: UnitId,
+    // Top left of the tiles the unit covers
+    pos: Vector2<u32>,
+    // Width and height in tiles
+    footprint: Vector2<u32>,
+    // Generated when first needed, and again whenever it's used after anything it depends on has
+    // changed, so it isn't saved
+    #[serde(skip)]
+    nav: RefCell<Option<NavigationBitmask>>,
+    // ID of the archetype this unit was spawned from, if any
+    archetype: Option<String>,
+    stats: UnitStats,
+    terrain_costs: TerrainCosts,
+    zone_of_control: ZoneOfControl,
+    // Effect put on targets that survive this unit's attacks
+    inflicts: Option<StatusEffect>,
+    // At most one of each kind, since reapplied effects stack onto the existing one
+    effects: Vec<StatusEffect>,
+    health: u32,
+    // Remaining movement and actions this turn
+    movement: f32,
+    actions: u32,
+    max_actions: u32,
+    team: Team,
+}
+
+impl Unit {
+    pub fn new(
+        pos: Vector2<u32>,
+        stats: UnitStats,
+        team: Team,
+        sector: &Sector,
+    ) -> Result<Unit, ()> {
+        let unit = Unit {
+            id: UnitId::default(),
+            pos,
+            footprint: Vector2::new(1, 1),
+            nav: RefCell::new(None),
+            archetype: None,
+            stats,
+            terrain_costs: TerrainCosts::new(),
+            zone_of_control: ZoneOfControl::None,
+            inflicts: None,
+            effects: Vec::new(),
+            health: stats.max_health,
+            movement: stats.movement,
+            actions: DEFAULT_ACTIONS,
+            max_actions: DEFAULT_ACTIONS,
+            team,
+        };
+        if !sector.tiles.contains(pos) {
+            return Err(());
+        }
+        return Ok(unit);
+    }
+
+    pub fn from_type(
+        unit_type: &UnitType,
+        pos: Vector2<u32>,
+        team: Team,
+        sector: &Sector,
+    ) -> anyhow::Result<Unit> {
+        let mut unit = Unit::new(pos, unit_type.stats(), team, sector)
+            .map_err(|_| anyhow!("Could not spawn {} at {:?}", unit_type.id(), pos))?;
+        unit.archetype = Some(unit_type.id().to_string());
+        unit.footprint = unit_type.footprint();
+        unit.terrain_costs = unit_type.terrain_costs().clone();
+        unit.zone_of_control = unit_type.zone_of_control();
+        unit.inflicts = unit_type.inflicts();
+        Ok(unit)
+    }
+
+    pub fn id(&self) -> UnitId {
+        self.id
+    }
+
+    pub fn pos(&self) -> Vector2<u32> {
+        self.pos
+    }
+
+    pub fn footprint(&self) -> Vector2<u32> {
+        self.footprint
+    }
+
+    /// Every tile the unit covers, starting from its position
+    pub fn tiles(&self) -> impl Iterator<Item = Vector2<u32>> {
+        footprint_tiles(self.pos, self.footprint)
+    }
+
+    pub fn covers(&self, pos: Vector2<u32>) -> bool {
+        (self.pos.x..self.pos.x + self.footprint.x).contains(&pos.x)
+            && (self.pos.y..self.pos.y + self.footprint.y).contains(&pos.y)
+    }
+
+    /// The tile covered by the unit that is closest to `pos`
+    pub fn closest_tile(&self, pos: Vector2<u32>) -> Vector2<u32> {
+        Vector2::new(
+            pos.x.clamp(self.pos.x, self.pos.x + self.footprint.x - 1),
+            pos.y.clamp(self.pos.y, self.pos.y + self.footprint.y - 1),
+        )
+    }
+
+    pub fn team(&self) -> Team {
+        self.team
+    }
+
+    pub fn archetype(&self) -> Option<&str> {
+        self.archetype.as_deref()
+    }
+
+    pub fn stats(&self) -> &UnitStats {
+        &self.stats
+    }
+
+    pub fn terrain_costs(&self) -> &TerrainCosts {
+        &self.terrain_costs
+    }
+
+    pub fn zone_of_control(&self) -> ZoneOfControl {
+        self.zone_of_control
+    }
+
+    pub fn inflicts(&self) -> Option<StatusEffect> {
+        self.inflicts
+    }
+
+    pub fn effects(&self) -> &[StatusEffect] {
+        &self.effects
+    }
+
+    /// Combined modifiers from every effect on the unit
+    pub fn modifiers(&self) -> Modifiers {
+        self.effects
+            .iter()
+            .fold(Modifiers::default(), |m, e| m.combine(e.modifiers()))
+    }
+
+    /// Attack after status effects
+    pub fn attack(&self) -> u32 {
+        self.stats
+            .attack
+            .saturating_add_signed(self.modifiers().attack)
+    }
+
+    /// Defense after status effects, not counting terrain
+    pub fn defense(&self) -> u32 {
+        self.stats
+            .defense
+            .saturating_add_signed(self.modifiers().defense)
+    }
+
+    /// Cost for this unit to move onto `tile`, or `None` if it can't
+    pub fn movement_cost(&self, tile: &Tile) -> Option<f32> {
+        match self.terrain_costs.get(&tile.terrain()) {
+            Some(cost) => *cost,
+            None => tile.movement_cost(),
+        }
+    }
+
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+
+    pub fn alive(&self) -> bool {
+        self.health > 0
+    }
+
+    pub fn movement(&self) -> f32 {
+        self.movement
+    }
+
+    /// Movement the unit gets each phase, after status effects
+    pub fn max_movement(&self) -> f32 {
+        let modifiers = self.modifiers();
+        match modifiers.stunned {
+            true => 0.,
+            false => self.stats.movement * modifiers.movement,
+        }
+    }
+
+    pub fn actions(&self) -> u32 {
+        self.actions
+    }
+
+    pub fn max_actions(&self) -> u32 {
+        self.max_actions
+    }
+
+    /// Restore as much movement and as many actions as status effects allow, ready for a new turn
+    fn refresh(&mut self) {
+        self.movement = self.max_movement();
+        self.actions = match self.modifiers().stunned {
+            true => 0,
+            false => self.max_actions,
+        };
+    }
+
+    /// Whether the unit could finish its movement on `pos` right now, or `None` if either it or
+    /// `pos` is outside of `sector`. Its navigation is only generated again if something it
+    /// depends on has changed since it was last used.
+    pub fn can_reach_tile(&self, pos: Vector2<u32>, sector: &Sector) -> Option<bool> {
+        let mut nav = self.nav.borrow_mut();
+        if !nav.as_ref().is_some_and(|n| n.is_current(self, sector)) {
+            *nav = NavigationBitmask::generate(self, sector).ok();
+        }
+        return nav.as_ref()?.tile(pos, sector);
+    }
+}
+
+/// The kind of ground a tile is made of, which decides how it affects movement and combat
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Terrain {
+    Grass,
+    Water,
+    Forest,
+    Mountain,
+}
+
+impl Terrain {
+    pub fn speed_modifier(&self) -> f32 {
+        match self {
+            Terrain::Grass => 1.,
+            Terrain::Water => 0.,
+            Terrain::Forest => 0.5,
+            Terrain::Mountain => 1. / 3.,
+        }
+    }
+
+    /// Added to the defense of any unit standing on this terrain
+    pub fn defense_bonus(&self) -> u32 {
+        match self {
+            Terrain::Grass | Terrain::Water => 0,
+            Terrain::Forest => 1,
+            Terrain::Mountain => 2,
+        }
+    }
+
+    pub fn blocks_sight(&self) -> bool {
+        matches!(self, Terrain::Forest | Terrain::Mountain)
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Tile {
+    atlas_position: Vector2<u32>,
+    terrain: Terrain,
+    elevation: u32,
+}
+
+impl Tile {
+    pub fn new(atlas_position: Vector2<u32>, terrain: Terrain) -> Self {
+        return Self {
+            atlas_position,
+            terrain,
+            elevation: 0,
+        };
+    }
+
+    /// Tiles higher than a unit hide whatever is behind them
+    pub fn elevation(&self) -> u32 {
+        self.elevation
+    }
+
+    pub fn set_elevation(&mut self, elevation: u32) {
+        self.elevation = elevation;
+    }
+
+    pub fn terrain(&self) -> Terrain {
+        self.terrain
+    }
+
+    pub fn set_terrain(&mut self, terrain: Terrain) {
+        self.terrain = terrain;
+    }
+
+    pub fn speed_modifier(&self) -> f32 {
+        return self.terrain.speed_modifier();
+    }
+
+    /// Tiles with no speed at all can't be entered
+    pub fn passable(&self) -> bool {
+        self.speed_modifier() > 0.
+    }
+
+    /// Cost of moving onto this tile, or `None` if it is impassable
+    pub fn movement_cost(&self) -> Option<f32> {
+        self.passable().then(|| 1. / self.speed_modifier())
+    }
+
+    pub fn atlas_position(&self) -> Vector2<u32> {
+        return self.atlas_position;
+    }
+
+    pub fn set_atlas_position(&mut self, position: Vector2<u32>) {
+        self.atlas_position = position;
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use cgmath::Vector2;
+
+use crate::juno::pathfinding::Topology;
+
+use super::{
+    footprint_tiles, navigation_cost, navigation_stops, Sector, Team, TerrainCosts, Unit, UnitId,
+    ZoneOfControl,
+};
+
+/// A version number that has never been handed out before, to any sector. Since no two sectors
+/// ever share one, navigation generated in one sector can never pass as current in another.
+pub(super) fn next_version() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Every tile that a unit is able to finish its movement on, along with everything it was
+/// generated from. Units keep one of these as a cache, which is regenerated whenever it's used
+/// after any of those have changed.
+#[derive(Clone)]
+pub struct NavigationBitmask {
+    movable_tiles: Vec<bool>,
+    size: Vector2<u32>,
+    inputs: NavigationInputs,
+}
+
+/// Everything that navigation depends on. Changes to the terrain, and to where units are and
+/// what each team can see, are tracked by the sector's versions, and the rest is copied from the
+/// sector and unit.
+#[derive(Clone, PartialEq)]
+struct NavigationInputs {
+    terrain_version: u64,
+    units_version: u64,
+    topology: Topology,
+    id: UnitId,
+    pos: Vector2<u32>,
+    footprint: Vector2<u32>,
+    team: Team,
+    movement: f32,
+    terrain_costs: TerrainCosts,
+    zone_of_control: ZoneOfControl,
+}
+
+impl NavigationInputs {
+    fn new(unit: &Unit, sector: &Sector) -> Self {
+        Self {
+            terrain_version: sector.terrain_version,
+            units_version: sector.units_version,
+            topology: sector.topology(),
+            id: unit.id(),
+            pos: unit.pos(),
+            footprint: unit.footprint(),
+            team: unit.team(),
+            movement: unit.movement(),
+            terrain_costs: unit.terrain_costs().clone(),
+            zone_of_control: unit.zone_of_control(),
+        }
+    }
+
+    /// Whether navigation generated from these would still be the same for `unit` in `sector`
+    fn is_current(&self, unit: &Unit, sector: &Sector) -> bool {
+        self.terrain_version == sector.terrain_version
+            && self.units_version == sector.units_version
+            && self.topology == sector.topology()
+            && self.id == unit.id()
+            && self.pos == unit.pos()
+            && self.footprint == unit.footprint()
+            && self.team == unit.team()
+            && self.movement == unit.movement()
+            && &self.terrain_costs == unit.terrain_costs()
+            && self.zone_of_control == unit.zone_of_control()
+    }
+}
+
+impl NavigationBitmask {
+    /// Flood out from the unit's position as far as its movement allows. Enemy units can't be
+    /// moved through, and allied units can be moved through but not stopped on. Tiles next to
+    /// enemies may stop or slow the unit, depending on its zone of control rule. The unit's own
+    /// tile is always reachable. Enemies that the unit's team can't see are ignored.
+    ///
+    /// Large units are navigated by their top left tile, and every rule applies to all of the
+    /// tiles they'd cover.
+    pub(super) fn generate(unit: &Unit, sector: &Sector) -> Result<Self, ()> {
+        if !sector.tiles.contains(unit.pos()) {
+            return Err(());
+        }
+        Ok(Self {
+            movable_tiles: movable_tiles(unit, sector, unit.movement()),
+            size: sector.size(),
+            inputs: NavigationInputs::new(unit, sector),
+        })
+    }
+
+    /// Whether this is still the navigation `unit` would have in `sector` if it were generated
+    /// again now
+    pub(super) fn is_current(&self, unit: &Unit, sector: &Sector) -> bool {
+        self.inputs.is_current(unit, sector)
+    }
+
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    pub fn tile(&self, pos: Vector2<u32>, sector: &Sector) -> Option<bool> {
+        if pos.x >= self.size.x || pos.y >= self.size.y {
+            return None;
+        }
+        self.movable_tiles.get(sector.index(pos) as usize).copied()
+    }
+}
+
+/// Every tile that `unit` could finish on with `movement` to spend, following the same rules as
+/// its navigation, indexed the same way as the sector
+pub(super) fn movable_tiles(unit: &Unit, sector: &Sector, movement: f32) -> Vec<bool> {
+    let tile_costs = sector.tiles.path_costs_stopping(
+        unit.pos(),
+        sector.topology(),
+        Some(movement),
+        |t| navigation_cost(t, unit, sector),
+        |pos| navigation_stops(pos, unit, sector),
+    );
+    tile_costs
+        .iter()
+        .enumerate()
+        .map(|(i, cost)| {
+            let pos = sector.from_index(i as u32);
+            cost.is_some()
+                && footprint_tiles(pos, unit.footprint()).all(|p| {
+                    sector
+                        .known_unit_at(unit.team(), p)
+                        .is_none_or(|other| other.id() == unit.id())
+                })
+        })
+        .collect()
+}
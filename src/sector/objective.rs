@@ -0,0 +1,119 @@
+use anyhow::ensure;
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+
+use super::{Sector, Team, UnitId};
+
+/// Something a team can do to win the match
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Objective {
+    pub team: Team,
+    pub goal: Goal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Goal {
+    /// Destroy every unit on every other team
+    Eliminate,
+    /// End `turns` of the team's phases in a row with one of its units in the region between
+    /// `min` and `max` (inclusive), and no enemies in it
+    HoldRegion {
+        min: Vector2<u32>,
+        max: Vector2<u32>,
+        turns: u32,
+        // Phases in a row that the region has been held for so far
+        #[serde(default)]
+        held: u32,
+    },
+    /// Get `unit` onto `to`. Fails if the unit dies on the way.
+    Escort { unit: UnitId, to: Vector2<u32> },
+    /// Still have units left once `turns` turns are over
+    Survive { turns: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectiveStatus {
+    InProgress,
+    Complete,
+    Failed,
+}
+
+impl Objective {
+    /// How the objective stands during `turn`
+    pub fn status(&self, sector: &Sector, turn: u32) -> ObjectiveStatus {
+        let complete = match self.goal {
+            Goal::Eliminate => sector.units().all(|u| u.team() == self.team),
+            Goal::HoldRegion { turns, held, .. } => held >= turns,
+            Goal::Escort { unit, to } => match sector.unit(unit) {
+                Some(unit) => unit.covers(to),
+                None => return ObjectiveStatus::Failed,
+            },
+            Goal::Survive { turns } => turn > turns,
+        };
+        match complete {
+            true => ObjectiveStatus::Complete,
+            false => ObjectiveStatus::InProgress,
+        }
+    }
+}
+
+impl Sector {
+    pub fn objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+
+    /// Add an objective for one of the teams. Any tiles it mentions have to be in the sector.
+    pub fn add_objective(&mut self, objective: Objective) -> anyhow::Result<()> {
+        self.check_objective(&objective)?;
+        self.objectives.push(objective);
+        Ok(())
+    }
+
+    pub(super) fn check_objective(&self, objective: &Objective) -> anyhow::Result<()> {
+        let positions = match objective.goal {
+            Goal::HoldRegion { min, max, .. } => {
+                ensure!(
+                    min.x <= max.x && min.y <= max.y,
+                    "Objective {objective:?} has its corners the wrong way around"
+                );
+                vec![min, max]
+            }
+            Goal::Escort { to, .. } => vec![to],
+            Goal::Eliminate | Goal::Survive { .. } => Vec::new(),
+        };
+        for pos in positions {
+            ensure!(
+                self.tiles.contains(pos),
+                "Objective {objective:?} is outside of sector {}",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Count how long `team` has held its regions for, at the end of one of its phases
+    pub fn update_objectives(&mut self, team: Team) {
+        let objectives = self.objectives.iter_mut().filter(|o| o.team == team);
+        for objective in objectives {
+            if let Goal::HoldRegion { min, max, held, .. } = &mut objective.goal {
+                // Large units are in the region if any of their tiles are
+                let in_region = self.units.values().filter(|u| {
+                    u.tiles()
+                        .any(|p| (min.x..=max.x).contains(&p.x) && (min.y..=max.y).contains(&p.y))
+                });
+                let (mut ours, mut theirs) = (false, false);
+                for unit in in_region {
+                    match unit.team() == team {
+                        true => ours = true,
+                        false => theirs = true,
+                    }
+                }
+                *held = match ours && !theirs {
+                    true => *held + 1,
+                    false => 0,
+                };
+            }
+        }
+    }
+}
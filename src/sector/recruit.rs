@@ -0,0 +1,110 @@
+use anyhow::ensure;
+use cgmath::Vector2;
+
+use crate::juno::directions;
+
+use super::{
+    archetype::UnitType, event::SectorEvent, footprint_tiles, ActionError, Sector, Team, Unit,
+    UnitId,
+};
+
+impl Sector {
+    /// Unit types that can be recruited here
+    pub fn roster(&self) -> &[UnitType] {
+        &self.roster
+    }
+
+    pub fn set_roster(&mut self, roster: Vec<UnitType>) -> anyhow::Result<()> {
+        for (i, unit_type) in roster.iter().enumerate() {
+            ensure!(
+                roster[..i].iter().all(|t| t.id() != unit_type.id()),
+                "Unit type {} is in the roster more than once",
+                unit_type.id()
+            );
+        }
+        self.roster = roster;
+        Ok(())
+    }
+
+    /// Spend `team`'s resources on a unit of type `unit_type` from the roster, recruited from its
+    /// structure on `from` and put on `to`. Every tile the recruit covers has to be free and
+    /// standable, with at least one of them next to the structure and none of them on it.
+    /// Recruits can't move or act until their team's next phase.
+    pub fn recruit_unit(
+        &mut self,
+        team: Team,
+        from: Vector2<u32>,
+        unit_type: &str,
+        to: Vector2<u32>,
+    ) -> Result<UnitId, ActionError> {
+        let structure = self
+            .structure_at(from)
+            .filter(|s| s.owner == Some(team) && s.kind.benefits().recruits > 0)
+            .ok_or(ActionError::NoRecruiter)?;
+        if structure.recruited >= structure.kind.benefits().recruits {
+            return Err(ActionError::RecruitLimit);
+        }
+        let unit_type = self
+            .roster
+            .iter()
+            .find(|t| t.id() == unit_type)
+            .ok_or(ActionError::NotInRoster)?;
+        let cost = unit_type.cost();
+        if self.resources(team) < cost {
+            return Err(ActionError::NotEnoughResources);
+        }
+        let covered = footprint_tiles(to, unit_type.footprint()).collect::<Vec<_>>();
+        let adjacent = covered
+            .iter()
+            .any(|p| directions::manhattan_distance(*p, from) == 1);
+        if !adjacent || covered.contains(&from) {
+            return Err(ActionError::NotAdjacent);
+        }
+        self.check_footprint(None, to, unit_type.footprint())?;
+        let mut unit =
+            Unit::from_type(unit_type, to, team, self).map_err(|_| ActionError::OutOfBounds)?;
+        let standable = unit.tiles().all(|p| {
+            self.tile(p)
+                .is_some_and(|t| unit.movement_cost(t.contents()).is_some())
+        });
+        if !standable {
+            return Err(ActionError::Impassable);
+        }
+        unit.movement = 0.;
+        unit.actions = 0;
+
+        let id = self.insert_unit(unit)?;
+        *self.resources.entry(team).or_insert(0) -= cost;
+        if let Some(structure) = self.structures.iter_mut().find(|s| s.pos == from) {
+            structure.recruited += 1;
+        }
+        self.events.push(SectorEvent::UnitRecruited {
+            unit: id,
+            from,
+            team,
+        });
+        self.units_changed();
+        Ok(id)
+    }
+
+    /// Take back a unit recruited from the structure on `from`, refunding `cost`
+    pub fn revert_recruit(
+        &mut self,
+        id: UnitId,
+        from: Vector2<u32>,
+        cost: u32,
+    ) -> Result<(), ActionError> {
+        let team = self.unit(id).ok_or(ActionError::NoUnit)?.team();
+        let structure = self
+            .structures
+            .iter_mut()
+            .find(|s| s.pos == from && s.owner == Some(team) && s.recruited > 0)
+            .ok_or(ActionError::NoRecruiter)?;
+        structure.recruited -= 1;
+        self.remove_unit(id);
+        *self.resources.entry(team).or_insert(0) += cost;
+        self.events
+            .push(SectorEvent::RecruitReverted { unit: id, from });
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+
+use super::{event::SectorEvent, ActionError, Sector, Team, Unit, UnitId};
+
+/// The kinds of status effect that a unit can be under
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusKind {
+    /// Half movement
+    Slowed,
+    /// Damage at the start of every phase, and weaker attacks. Stacks up to three times.
+    Poisoned,
+    /// Dug in for extra defense
+    Entrenched,
+    /// No movement or actions at all
+    Stunned,
+}
+
+/// What happens when an effect is applied to a unit that already has an effect of the same kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stacking {
+    /// Keep whichever duration is longer
+    Refresh,
+    /// Add the durations together
+    Extend,
+    /// Add the stacks together up to this many, and keep whichever duration is longer
+    Intensify(u32),
+}
+
+impl StatusKind {
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            StatusKind::Slowed | StatusKind::Entrenched => Stacking::Refresh,
+            StatusKind::Poisoned => Stacking::Intensify(3),
+            StatusKind::Stunned => Stacking::Extend,
+        }
+    }
+
+    /// How an effect of this kind with `stacks` stacks changes a unit
+    pub fn modifiers(&self, stacks: u32) -> Modifiers {
+        let stacks = stacks as i32;
+        match self {
+            StatusKind::Slowed => Modifiers {
+                movement: 0.5,
+                ..Default::default()
+            },
+            StatusKind::Poisoned => Modifiers {
+                attack: -stacks,
+                damage: 2 * stacks as u32,
+                ..Default::default()
+            },
+            StatusKind::Entrenched => Modifiers {
+                defense: 2,
+                ..Default::default()
+            },
+            StatusKind::Stunned => Modifiers {
+                stunned: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Changes to a unit's stats from its status effects
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modifiers {
+    /// Multiplies the movement the unit gets each phase
+    pub movement: f32,
+    pub attack: i32,
+    pub defense: i32,
+    /// Damage taken at the start of each of the unit's phases
+    pub damage: u32,
+    /// Stunned units get no movement or actions
+    pub stunned: bool,
+}
+
+impl Default for Modifiers {
+    fn default() -> Self {
+        Self {
+            movement: 1.,
+            attack: 0,
+            defense: 0,
+            damage: 0,
+            stunned: false,
+        }
+    }
+}
+
+impl Modifiers {
+    /// Both sets of modifiers applied at once
+    pub fn combine(self, other: Modifiers) -> Modifiers {
+        Modifiers {
+            movement: self.movement * other.movement,
+            attack: self.attack + other.attack,
+            defense: self.defense + other.defense,
+            damage: self.damage + other.damage,
+            stunned: self.stunned || other.stunned,
+        }
+    }
+}
+
+/// A status effect on a unit, or one waiting to be applied to a unit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// How many more of the unit's phases the effect lasts for. Counts down at the end of each.
+    pub turns: u32,
+    #[serde(default = "default_stacks")]
+    pub stacks: u32,
+}
+
+fn default_stacks() -> u32 {
+    1
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, turns: u32) -> Self {
+        Self {
+            kind,
+            turns,
+            stacks: 1,
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.kind.modifiers(self.stacks)
+    }
+
+    /// Combine another effect of the same kind into this one, following the kind's stacking rule
+    fn stack(&mut self, other: StatusEffect) {
+        match self.kind.stacking() {
+            Stacking::Refresh => self.turns = self.turns.max(other.turns),
+            Stacking::Extend => self.turns += other.turns,
+            Stacking::Intensify(max_stacks) => {
+                self.stacks = (self.stacks + other.stacks).min(max_stacks);
+                self.turns = self.turns.max(other.turns);
+            }
+        }
+    }
+}
+
+impl Unit {
+    /// Add an effect, stacking it onto any existing effect of the same kind
+    pub(super) fn add_effect(&mut self, effect: StatusEffect) {
+        match self.effects.iter_mut().find(|e| e.kind == effect.kind) {
+            Some(existing) => existing.stack(effect),
+            None => self.effects.push(effect),
+        }
+    }
+}
+
+impl Sector {
+    /// Put a status effect on a unit. Effects that stop the unit take hold straight away, and the
+    /// rest change its stats from now on.
+    pub fn apply_effect(&mut self, id: UnitId, effect: StatusEffect) -> Result<(), ActionError> {
+        let unit = self.units.get_mut(&id).ok_or(ActionError::NoUnit)?;
+        if effect.turns == 0 || effect.stacks == 0 {
+            return Ok(());
+        }
+        unit.add_effect(effect);
+        unit.movement = unit.movement.min(unit.max_movement());
+        if unit.modifiers().stunned {
+            unit.actions = 0;
+        }
+        self.events
+            .push(SectorEvent::EffectApplied { unit: id, effect });
+        Ok(())
+    }
+
+    /// Take an effect off a unit before it runs out, returning it
+    pub fn remove_effect(&mut self, id: UnitId, kind: StatusKind) -> Option<StatusEffect> {
+        let unit = self.units.get_mut(&id)?;
+        let index = unit.effects.iter().position(|e| e.kind == kind)?;
+        let effect = unit.effects.remove(index);
+        self.events
+            .push(SectorEvent::EffectExpired { unit: id, kind });
+        Some(effect)
+    }
+
+    /// Start `team`'s phase. Its units take damage from their effects first, then its structures
+    /// pay out, and then its units get back as much movement and as many actions as their effects
+    /// allow.
+    pub fn start_phase(&mut self, team: Team) {
+        let damaged = self
+            .units
+            .values()
+            .filter(|u| u.team() == team)
+            .flat_map(|u| {
+                u.effects
+                    .iter()
+                    .map(|e| (u.id(), e.kind, e.modifiers().damage))
+            })
+            .filter(|(_, _, damage)| *damage > 0)
+            .collect::<Vec<_>>();
+        for (id, kind, damage) in damaged {
+            let Some(unit) = self.units.get_mut(&id) else {
+                continue;
+            };
+            unit.health = unit.health.saturating_sub(damage);
+            let (alive, pos) = (unit.alive(), unit.pos());
+            self.events.push(SectorEvent::EffectDamaged {
+                unit: id,
+                kind,
+                damage,
+            });
+            if !alive {
+                self.remove_unit(id);
+                self.events.push(SectorEvent::UnitDied {
+                    unit: id,
+                    pos,
+                    team,
+                });
+            }
+        }
+        self.collect_structures(team);
+        self.refresh_team(team);
+    }
+
+    /// End `team`'s phase, counting down the effects on its units and removing any that run out
+    pub fn end_phase(&mut self, team: Team) {
+        for unit in self.units.values_mut().filter(|u| u.team == team) {
+            for effect in unit.effects.iter_mut() {
+                effect.turns = effect.turns.saturating_sub(1);
+            }
+            for effect in unit.effects.iter().filter(|e| e.turns == 0) {
+                self.events.push(SectorEvent::EffectExpired {
+                    unit: unit.id,
+                    kind: effect.kind,
+                });
+            }
+            unit.effects.retain(|e| e.turns > 0);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{anyhow, ensure};
+use cgmath::Vector2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::juno::directions;
+
+use super::{event::SectorEvent, Sector, Team};
+
+/// The kinds of structure that can stand on a tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StructureKind {
+    /// Pays resources to its owner
+    Village,
+    /// Patches up its owner's units that stand on it, and recruits new ones
+    Fort,
+    /// Lets its owner see the tiles around it
+    Shrine,
+}
+
+/// What a structure is worth to the team that owns it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Benefits {
+    /// Resources paid at the start of each of the owner's phases
+    pub income: u32,
+    /// Health restored at the start of each of the owner's phases, to the owner's unit standing
+    /// on the structure
+    pub healing: u32,
+    /// How far the owner can always see from the structure
+    pub sight: u32,
+    /// How many units the owner can recruit from the structure in each of its phases
+    pub recruits: u32,
+}
+
+impl StructureKind {
+    pub fn benefits(&self) -> Benefits {
+        match self {
+            StructureKind::Village => Benefits {
+                income: 2,
+                ..Default::default()
+            },
+            StructureKind::Fort => Benefits {
+                healing: 3,
+                recruits: 1,
+                ..Default::default()
+            },
+            StructureKind::Shrine => Benefits {
+                sight: 6,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A structure on one of the sector's tiles, which belongs to whichever team last ended a phase
+/// with a unit on it
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Structure {
+    pub kind: StructureKind,
+    pub pos: Vector2<u32>,
+    /// `None` until someone captures it
+    pub owner: Option<Team>,
+    /// Units recruited from it so far in its owner's current phase
+    #[serde(default)]
+    pub recruited: u32,
+}
+
+impl Structure {
+    pub fn new(kind: StructureKind, pos: Vector2<u32>) -> Self {
+        Self {
+            kind,
+            pos,
+            owner: None,
+            recruited: 0,
+        }
+    }
+}
+
+impl Sector {
+    pub fn structures(&self) -> &[Structure] {
+        &self.structures
+    }
+
+    pub fn structure_at(&self, pos: Vector2<u32>) -> Option<&Structure> {
+        self.structures.iter().find(|s| s.pos == pos)
+    }
+
+    /// Resources that `team` has built up from its structures, and not yet spent
+    pub fn resources(&self, team: Team) -> u32 {
+        self.resources.get(&team).copied().unwrap_or(0)
+    }
+
+    /// Add a structure. It has to be on a passable tile, without another structure on it.
+    pub fn add_structure(&mut self, structure: Structure) -> anyhow::Result<()> {
+        self.check_structure(&structure)?;
+        ensure!(
+            self.structure_at(structure.pos).is_none(),
+            "There's already a structure on {:?}",
+            structure.pos
+        );
+        self.structures.push(structure);
+        // What the owner can see from it
+        self.units_changed();
+        Ok(())
+    }
+
+    /// Add a structure on the closest tile to `near` that could take it, returning where it went
+    pub fn place_structure(
+        &mut self,
+        kind: StructureKind,
+        owner: Option<Team>,
+        near: Vector2<u32>,
+    ) -> anyhow::Result<Vector2<u32>> {
+        let pos = self
+            .free_structure_tiles()
+            .min_by_key(|pos| directions::manhattan_distance(*pos, near))
+            .ok_or_else(|| anyhow!("There's no room for a {kind:?} in {}", self.name))?;
+        self.add_structure(Structure {
+            owner,
+            ..Structure::new(kind, pos)
+        })?;
+        Ok(pos)
+    }
+
+    /// Add a structure on a random tile that could take it, with no unit on it either, returning
+    /// where it went
+    pub fn scatter_structure(
+        &mut self,
+        kind: StructureKind,
+        owner: Option<Team>,
+        rng: &mut impl Rng,
+    ) -> anyhow::Result<Vector2<u32>> {
+        let free = self
+            .free_structure_tiles()
+            .filter(|pos| !self.occupancy.contains_key(pos))
+            .collect::<Vec<_>>();
+        ensure!(
+            !free.is_empty(),
+            "There's no room for a {kind:?} in {}",
+            self.name
+        );
+        let pos = free[rng.gen_range(0..free.len())];
+        self.add_structure(Structure {
+            owner,
+            ..Structure::new(kind, pos)
+        })?;
+        Ok(pos)
+    }
+
+    fn free_structure_tiles(&self) -> impl Iterator<Item = Vector2<u32>> + '_ {
+        self.tiles()
+            .filter(|t| t.contents().passable())
+            .map(|t| t.pos())
+            .filter(|pos| self.structure_at(*pos).is_none())
+    }
+
+    pub(super) fn check_structure(&self, structure: &Structure) -> anyhow::Result<()> {
+        ensure!(
+            self.tile(structure.pos)
+                .is_some_and(|t| t.contents().passable()),
+            "{:?} isn't on a passable tile of sector {}",
+            structure,
+            self.name
+        );
+        Ok(())
+    }
+
+    /// Hand every structure with one of `team`'s units on it over to `team`, at the end of its
+    /// phase
+    pub fn capture_structures(&mut self, team: Team) {
+        let mut captured = false;
+        for structure in self.structures.iter_mut() {
+            let Some(unit) = self.occupancy.get(&structure.pos).map(|id| &self.units[id]) else {
+                continue;
+            };
+            if unit.team() != team || structure.owner == Some(team) {
+                continue;
+            }
+            self.events.push(SectorEvent::StructureCaptured {
+                pos: structure.pos,
+                team,
+                from: structure.owner,
+            });
+            structure.owner = Some(team);
+            structure.recruited = 0;
+            captured = true;
+        }
+        if captured {
+            // Shrines change hands along with what they let their owners see
+            self.units_changed();
+        }
+    }
+
+    /// Pay out the income and healing from `team`'s structures, and let them recruit again, at
+    /// the start of its phase
+    pub(super) fn collect_structures(&mut self, team: Team) {
+        let owned = self.structures.iter_mut().filter(|s| s.owner == Some(team));
+        for structure in owned {
+            structure.recruited = 0;
+            let benefits = structure.kind.benefits();
+            if benefits.income > 0 {
+                *self.resources.entry(team).or_insert(0) += benefits.income;
+            }
+            let Some(id) = self.occupancy.get(&structure.pos).copied() else {
+                continue;
+            };
+            let Some(unit) = self.units.get_mut(&id).filter(|u| u.team == team) else {
+                continue;
+            };
+            let healed = benefits
+                .healing
+                .min(unit.stats.max_health.saturating_sub(unit.health));
+            if healed > 0 {
+                unit.health += healed;
+                self.events.push(SectorEvent::UnitHealed {
+                    unit: id,
+                    amount: healed,
+                });
+            }
+        }
+    }
+}
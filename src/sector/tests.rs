@@ -0,0 +1,1754 @@
+use std::f32::consts::SQRT_2;
+
+use cgmath::Vector2;
+
+use crate::{
+    juno::{
+        grid::Grid,
+        pathfinding::{Movement, Topology},
+    },
+    terrain::generate_terrain,
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{
+    archetype::{load_unit_types_json, UnitRegistry},
+    combat::AttackResult,
+    event::SectorEvent,
+    navigation,
+    objective::{Goal, Objective, ObjectiveStatus},
+    status::{StatusEffect, StatusKind},
+    structure::{Structure, StructureKind},
+    visibility::Visibility,
+    ActionError, Sector, Team, Terrain, Tile, Unit, UnitId, UnitStats, ZoneOfControl,
+};
+
+/// Open sector of plain tiles with the given impassable tiles
+pub fn test_sector(size: Vector2<u32>, impassable: &[Vector2<u32>]) -> Sector {
+    let mut tiles = Grid::new(size);
+    tiles.fill(Tile::new(Vector2::new(0, 0), Terrain::Grass));
+    for pos in impassable {
+        *tiles.tile_mut(*pos).unwrap().contents_mut() =
+            Tile::new(Vector2::new(0, 0), Terrain::Water);
+    }
+    Sector::new("Test sector".to_string(), tiles, vec![])
+}
+
+pub fn test_stats(movement: f32) -> UnitStats {
+    UnitStats {
+        movement,
+        max_health: 10,
+        attack: 5,
+        defense: 1,
+        range: 1,
+        sight: 10,
+    }
+}
+
+pub fn spawn(sector: &mut Sector, pos: Vector2<u32>, movement: f32, team: Team) -> UnitId {
+    spawn_with_stats(sector, pos, test_stats(movement), team)
+}
+
+pub fn spawn_with_stats(
+    sector: &mut Sector,
+    pos: Vector2<u32>,
+    stats: UnitStats,
+    team: Team,
+) -> UnitId {
+    let unit = Unit::new(pos, stats, team, sector).unwrap();
+    sector.add_unit(unit).unwrap()
+}
+
+fn can_reach(sector: &Sector, unit: UnitId, pos: Vector2<u32>) -> bool {
+    sector
+        .unit(unit)
+        .unwrap()
+        .can_reach_tile(pos, sector)
+        .unwrap()
+}
+
+#[test]
+pub fn test_sector_find_path_avoids_impassable() {
+    let sector = test_sector(
+        Vector2::new(3, 3),
+        &[Vector2::new(1, 0), Vector2::new(1, 1)],
+    );
+    let path = sector
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(2, 0),
+            Movement::Cardinal,
+            None,
+        )
+        .unwrap();
+    assert_eq!(path.cost(), 6.);
+    assert!(sector
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(1, 0),
+            Movement::Cardinal,
+            None
+        )
+        .is_none());
+}
+
+#[test]
+pub fn test_sector_find_path_avoids_units() {
+    let mut sector = test_sector(Vector2::new(3, 2), &[]);
+    spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    spawn(&mut sector, Vector2::new(1, 0), 4., Team::new(0));
+    let path = sector
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(2, 0),
+            Movement::Cardinal,
+            None,
+        )
+        .unwrap();
+    assert_eq!(path.cost(), 4.);
+    assert!(!path.tiles().contains(&Vector2::new(1, 0)));
+    // Occupied tiles can't be a destination either
+    assert!(sector
+        .find_path(
+            Vector2::new(0, 0),
+            Vector2::new(1, 0),
+            Movement::Diagonal,
+            None
+        )
+        .is_none());
+}
+
+#[test]
+pub fn test_navigation_movement_range() {
+    let mut sector = test_sector(Vector2::new(5, 5), &[Vector2::new(1, 0)]);
+    let origin = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    assert!(can_reach(&sector, origin, Vector2::new(0, 0)));
+    assert!(can_reach(&sector, origin, Vector2::new(0, 2)));
+    assert!(can_reach(&sector, origin, Vector2::new(1, 1)));
+    // Blocked by impassable terrain
+    assert!(!can_reach(&sector, origin, Vector2::new(1, 0)));
+    assert!(!can_reach(&sector, origin, Vector2::new(2, 0)));
+    // Out of range
+    assert!(!can_reach(&sector, origin, Vector2::new(0, 3)));
+}
+
+#[test]
+pub fn test_navigation_enemies_block() {
+    // Corridor one tile wide
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let origin = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    spawn(&mut sector, Vector2::new(2, 0), 3., Team::new(1));
+    assert!(can_reach(&sector, origin, Vector2::new(1, 0)));
+    assert!(!can_reach(&sector, origin, Vector2::new(2, 0)));
+    assert!(!can_reach(&sector, origin, Vector2::new(3, 0)));
+}
+
+#[test]
+pub fn test_navigation_allies_pass_through() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let origin = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(0));
+    assert!(!can_reach(&sector, origin, Vector2::new(1, 0)));
+    assert!(can_reach(&sector, origin, Vector2::new(2, 0)));
+    assert!(can_reach(&sector, origin, Vector2::new(3, 0)));
+    let path = sector
+        .find_unit_path(origin, Vector2::new(3, 0), None)
+        .unwrap();
+    assert_eq!(path.cost(), 3.);
+    assert!(sector
+        .find_unit_path(origin, Vector2::new(1, 0), None)
+        .is_none());
+}
+
+#[test]
+pub fn test_navigation_updates_when_units_added() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let origin = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    assert!(can_reach(&sector, origin, Vector2::new(3, 0)));
+    spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(1));
+    assert!(!can_reach(&sector, origin, Vector2::new(3, 0)));
+}
+
+#[test]
+pub fn test_move_unit() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[Vector2::new(1, 0)]);
+    let id = spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    let path = sector.move_unit(id, Vector2::new(2, 0)).unwrap();
+    assert_eq!(path.cost(), 4.);
+    assert!(sector.unit_at_tile(Vector2::new(0, 0)).is_none());
+    let unit = sector.unit_at_tile(Vector2::new(2, 0)).unwrap();
+    assert_eq!(unit.id(), id);
+    assert_eq!(unit.movement(), 0.);
+    // Navigation is refreshed after moving, and there's no movement left
+    assert!(can_reach(&sector, id, Vector2::new(2, 0)));
+    assert!(!can_reach(&sector, id, Vector2::new(3, 0)));
+
+    let events = sector.drain_events().collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![SectorEvent::UnitMoved {
+            unit: id,
+            from: Vector2::new(0, 0),
+            to: Vector2::new(2, 0),
+            path,
+        }]
+    );
+    assert_eq!(sector.drain_events().count(), 0);
+}
+
+#[test]
+pub fn test_move_unit_validation() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let origin = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(0));
+    assert_eq!(
+        sector.move_unit(UnitId(99), Vector2::new(3, 0)),
+        Err(ActionError::NoUnit)
+    );
+    assert_eq!(
+        sector.move_unit(origin, Vector2::new(4, 0)),
+        Err(ActionError::OutOfBounds)
+    );
+    assert_eq!(
+        sector.move_unit(origin, Vector2::new(0, 0)),
+        Err(ActionError::AlreadyThere)
+    );
+    // Can't stop on an ally, or go further than movement allows
+    assert_eq!(
+        sector.move_unit(origin, Vector2::new(1, 0)),
+        Err(ActionError::Unreachable)
+    );
+    assert_eq!(
+        sector.move_unit(origin, Vector2::new(3, 0)),
+        Err(ActionError::Unreachable)
+    );
+    assert_eq!(sector.drain_events().count(), 0);
+    // Passing through the ally is fine
+    assert!(sector.move_unit(origin, Vector2::new(2, 0)).is_ok());
+}
+
+#[test]
+pub fn test_move_unit_updates_other_units() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let ally = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(3, 0), 3., Team::new(1));
+    assert!(can_reach(&sector, ally, Vector2::new(2, 0)));
+    sector.move_unit(enemy, Vector2::new(1, 0)).unwrap();
+    assert!(!can_reach(&sector, ally, Vector2::new(2, 0)));
+}
+
+#[test]
+pub fn test_attack_unit() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut sector = test_sector(Vector2::new(3, 1), &[]);
+    let attacker = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(1));
+    let result = sector.attack_unit(attacker, target, &mut rng).unwrap();
+    // Base damage of 4 (5 attack - 1 defense), rolled between 80% and 120%
+    assert!((3..=5).contains(&result.damage));
+    assert!(!result.killed);
+    assert_eq!(sector.unit(target).unwrap().health(), 10 - result.damage);
+    assert_eq!(sector.unit(attacker).unwrap().actions(), 0);
+    assert_eq!(
+        sector.drain_events().collect::<Vec<_>>(),
+        vec![SectorEvent::UnitAttacked {
+            attacker,
+            target,
+            damage: result.damage,
+        }]
+    );
+    // Only one action per turn
+    assert_eq!(
+        sector.attack_unit(attacker, target, &mut rng),
+        Err(ActionError::NoActionsLeft)
+    );
+}
+
+#[test]
+pub fn test_attack_kills_unit() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut sector = test_sector(Vector2::new(3, 1), &[]);
+    let mut strong = test_stats(2.);
+    strong.attack = 50;
+    let attacker = spawn_with_stats(&mut sector, Vector2::new(0, 0), strong, Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(1));
+    let result = sector.attack_unit(attacker, target, &mut rng).unwrap();
+    assert!(result.killed);
+    assert_eq!(sector.units().count(), 1);
+    assert!(sector.unit(target).is_none());
+    assert!(sector.unit_at_tile(Vector2::new(1, 0)).is_none());
+    let events = sector.drain_events().collect::<Vec<_>>();
+    assert_eq!(
+        events.last(),
+        Some(&SectorEvent::UnitDied {
+            unit: target,
+            pos: Vector2::new(1, 0),
+            team: Team::new(1),
+        })
+    );
+    // The dead unit no longer blocks movement
+    assert!(can_reach(&sector, attacker, Vector2::new(2, 0)));
+}
+
+#[test]
+pub fn test_attack_validation() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut sector = test_sector(Vector2::new(5, 1), &[]);
+    sector
+        .tile_mut(Vector2::new(2, 0))
+        .unwrap()
+        .contents_mut()
+        .set_terrain(Terrain::Mountain);
+    let mut archer = test_stats(2.);
+    archer.range = 3;
+    let archer = spawn_with_stats(&mut sector, Vector2::new(0, 0), archer, Team::new(0));
+    let ally = spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(0));
+    let behind_mountain = spawn(&mut sector, Vector2::new(3, 0), 2., Team::new(1));
+    let far = spawn(&mut sector, Vector2::new(4, 0), 2., Team::new(1));
+    assert_eq!(
+        sector.attack_unit(archer, ally, &mut rng),
+        Err(ActionError::SameTeam)
+    );
+    assert_eq!(
+        sector.attack_unit(archer, far, &mut rng),
+        Err(ActionError::OutOfRange)
+    );
+    assert_eq!(
+        sector.attack_unit(archer, behind_mountain, &mut rng),
+        Err(ActionError::NoLineOfSight)
+    );
+    assert_eq!(
+        sector.attack_unit(archer, UnitId(99), &mut rng),
+        Err(ActionError::NoUnit)
+    );
+}
+
+#[test]
+pub fn test_terrain_defense_bonus() {
+    let mut sector = test_sector(Vector2::new(3, 1), &[]);
+    sector
+        .tile_mut(Vector2::new(1, 0))
+        .unwrap()
+        .contents_mut()
+        .set_terrain(Terrain::Forest);
+    let attacker = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(1));
+    let (attacker, target) = (sector.unit(attacker).unwrap(), sector.unit(target).unwrap());
+    assert_eq!(sector.base_damage(attacker, target), 3);
+}
+
+#[test]
+pub fn test_attack_is_deterministic() {
+    let damage = |seed| {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut sector = test_sector(Vector2::new(2, 1), &[]);
+        let attacker = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+        let target = spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(1));
+        sector.attack_unit(attacker, target, &mut rng).unwrap()
+    };
+    let results = (0..3).map(damage).collect::<Vec<AttackResult>>();
+    assert_eq!(results, (0..3).map(damage).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn test_load_unit_types() {
+    let registry = load_unit_types_json().unwrap();
+    let infantry = registry.get("infantry").unwrap();
+    assert_eq!(infantry.name(), "Infantry");
+    assert!(registry.get("dragon").is_none());
+}
+
+#[test]
+pub fn test_unit_type_validation() {
+    let unit_json = |id: &str, health: u32, costs: &str| {
+        format!(
+            r#"{{"id": "{}", "name": "Test", "sprite": {{"x": 0, "y": 0}}, "movement": 3.0,
+            "health": {}, "attack": {{"damage": 2, "range": 1}}, "defense": 1, "sight": 3,
+            "terrain_costs": {}}}"#,
+            id, health, costs
+        )
+    };
+    let valid = unit_json("a", 5, "{}");
+    assert!(UnitRegistry::from_json(&format!("[{}]", valid)).is_ok());
+    // Duplicate IDs
+    assert!(UnitRegistry::from_json(&format!("[{}, {}]", valid, valid)).is_err());
+    // No health
+    assert!(UnitRegistry::from_json(&format!("[{}]", unit_json("a", 0, "{}"))).is_err());
+    // Negative movement cost, and unknown terrain
+    let negative = unit_json("a", 5, r#"{"forest": -1.0}"#);
+    assert!(UnitRegistry::from_json(&format!("[{}]", negative)).is_err());
+    let unknown = unit_json("a", 5, r#"{"lava": 1.0}"#);
+    assert!(UnitRegistry::from_json(&format!("[{}]", unknown)).is_err());
+    assert!(UnitRegistry::from_json("not json").is_err());
+}
+
+#[test]
+pub fn test_spawn_from_unit_type() {
+    let registry = load_unit_types_json().unwrap();
+    let mut sector = test_sector(Vector2::new(8, 1), &[]);
+    let unit = registry
+        .spawn("archer", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    let archer = registry.get("archer").unwrap();
+    assert_eq!(unit.archetype(), Some("archer"));
+    assert_eq!(*unit.stats(), archer.stats());
+    assert_eq!(unit.health(), archer.stats().max_health);
+    sector.add_unit(unit).unwrap();
+    assert!(registry
+        .spawn("dragon", Vector2::new(1, 0), Team::new(0), &sector)
+        .is_err());
+}
+
+#[test]
+pub fn test_unit_type_terrain_costs() {
+    let registry = load_unit_types_json().unwrap();
+    let mut sector = test_sector(Vector2::new(3, 2), &[]);
+    sector
+        .tile_mut(Vector2::new(1, 0))
+        .unwrap()
+        .contents_mut()
+        .set_terrain(Terrain::Mountain);
+    let cavalry = registry
+        .spawn("cavalry", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    let cavalry = sector.add_unit(cavalry).unwrap();
+    // Cavalry can't cross mountains, so has to go around
+    let path = sector
+        .find_unit_path(cavalry, Vector2::new(2, 0), None)
+        .unwrap();
+    assert_eq!(path.cost(), 4.);
+    assert!(!can_reach(&sector, cavalry, Vector2::new(1, 0)));
+}
+
+fn set_terrain(sector: &mut Sector, positions: &[Vector2<u32>], terrain: Terrain) {
+    for pos in positions {
+        sector
+            .tile_mut(*pos)
+            .unwrap()
+            .contents_mut()
+            .set_terrain(terrain);
+    }
+}
+
+#[test]
+pub fn test_visibility_radius() {
+    let mut sector = test_sector(Vector2::new(10, 1), &[]);
+    let mut stats = test_stats(2.);
+    stats.sight = 3;
+    spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    let team = Team::new(0);
+    assert!(sector.can_see(team, Vector2::new(0, 0)));
+    assert!(sector.can_see(team, Vector2::new(3, 0)));
+    assert_eq!(
+        sector.visibility(team, Vector2::new(4, 0)),
+        Visibility::Unseen
+    );
+    // Teams without any units can't see anything
+    assert_eq!(
+        sector.visibility(Team::new(1), Vector2::new(0, 0)),
+        Visibility::Unseen
+    );
+}
+
+#[test]
+pub fn test_visibility_blocked_by_terrain() {
+    let mut sector = test_sector(Vector2::new(7, 7), &[]);
+    set_terrain(&mut sector, &[Vector2::new(3, 3)], Terrain::Forest);
+    spawn(&mut sector, Vector2::new(3, 1), 2., Team::new(0));
+    let team = Team::new(0);
+    // The forest itself can be seen, but not what's behind it
+    assert!(sector.can_see(team, Vector2::new(3, 3)));
+    assert!(!sector.can_see(team, Vector2::new(3, 4)));
+    assert!(!sector.can_see(team, Vector2::new(3, 6)));
+    assert!(sector.can_see(team, Vector2::new(0, 6)));
+
+    // Higher ground blocks sight the same way
+    let mut sector = test_sector(Vector2::new(7, 7), &[]);
+    sector
+        .tile_mut(Vector2::new(3, 3))
+        .unwrap()
+        .contents_mut()
+        .set_elevation(1);
+    spawn(&mut sector, Vector2::new(3, 1), 2., Team::new(0));
+    assert!(!sector.can_see(team, Vector2::new(3, 4)));
+}
+
+#[test]
+pub fn test_generated_terrain_blocks_sight() {
+    let sector = generate_terrain(Vector2::new(30, 30), "Sight".to_string(), 0);
+    // Some tile in the middle of the map has to hide the tile behind it from the one in front
+    let blocking = sector
+        .tiles()
+        .map(|t| t.pos())
+        .filter(|pos| pos.x > 0 && pos.x < 29)
+        .find(|pos| sector.blocks_sight(0, *pos))
+        .unwrap();
+    assert!(!sector.line_of_sight(blocking - Vector2::new(1, 0), blocking + Vector2::new(1, 0)));
+}
+
+#[test]
+pub fn test_visibility_explored() {
+    let mut sector = test_sector(Vector2::new(12, 1), &[]);
+    let mut stats = test_stats(5.);
+    stats.sight = 2;
+    let unit = spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    let team = Team::new(0);
+    sector.move_unit(unit, Vector2::new(5, 0)).unwrap();
+    assert_eq!(
+        sector.visibility(team, Vector2::new(0, 0)),
+        Visibility::Explored
+    );
+    assert_eq!(
+        sector.visibility(team, Vector2::new(3, 0)),
+        Visibility::Visible
+    );
+    assert_eq!(
+        sector.visibility(team, Vector2::new(8, 0)),
+        Visibility::Unseen
+    );
+}
+
+#[test]
+pub fn test_hidden_units() {
+    let mut sector = test_sector(Vector2::new(7, 1), &[]);
+    set_terrain(&mut sector, &[Vector2::new(2, 0)], Terrain::Forest);
+    let scout = spawn(&mut sector, Vector2::new(0, 0), 6., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(4, 0), 2., Team::new(1));
+    let visible = sector
+        .visible_units(Team::new(0))
+        .map(|u| u.pos())
+        .collect::<Vec<_>>();
+    assert_eq!(visible, vec![Vector2::new(0, 0)]);
+    // Navigation doesn't know the hidden enemy is there
+    assert!(can_reach(&sector, scout, Vector2::new(5, 0)));
+    // ...so it gets ambushed when it tries to move past
+    let path = sector.move_unit(scout, Vector2::new(5, 0)).unwrap();
+    assert_eq!(path.end(), Vector2::new(3, 0));
+    assert_eq!(path.cost(), 4.);
+    let unit = sector.unit_at_tile(Vector2::new(3, 0)).unwrap();
+    assert_eq!(unit.movement(), 0.);
+    let events = sector.drain_events().collect::<Vec<_>>();
+    assert_eq!(
+        events.last(),
+        Some(&SectorEvent::UnitAmbushed {
+            unit: scout,
+            pos: Vector2::new(3, 0),
+            enemy,
+        })
+    );
+    assert!(sector.can_see(Team::new(0), Vector2::new(4, 0)));
+}
+
+#[test]
+pub fn test_ambush_path_cost_follows_topology() {
+    for (movement, cost) in [
+        (Movement::Diagonal, 2. * SQRT_2),
+        (Movement::AlternatingDiagonal, 3.),
+    ] {
+        let mut sector = test_sector(Vector2::new(5, 5), &[]);
+        sector.set_topology(movement.into());
+        let mut stats = test_stats(10.);
+        stats.sight = 1;
+        let scout = spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+        spawn(&mut sector, Vector2::new(3, 3), 2., Team::new(1));
+        sector.drain_events();
+        let path = sector.move_unit(scout, Vector2::new(4, 4)).unwrap();
+        assert_eq!(path.end(), Vector2::new(2, 2));
+        assert!((path.cost() - cost).abs() < 1e-4);
+        let moved = sector.drain_events().find_map(|e| match e {
+            SectorEvent::UnitMoved { path, .. } => Some(path),
+            _ => None,
+        });
+        assert_eq!(moved, Some(path));
+    }
+}
+
+#[test]
+pub fn test_cant_attack_hidden_units() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut sector = test_sector(Vector2::new(3, 1), &[]);
+    let mut stats = test_stats(2.);
+    stats.sight = 0;
+    stats.range = 2;
+    let attacker = spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(2, 0), 2., Team::new(1));
+    assert_eq!(
+        sector.attack_unit(attacker, target, &mut rng),
+        Err(ActionError::NoLineOfSight)
+    );
+}
+
+/// Check that the occupancy index agrees with where every unit actually is
+fn assert_occupancy_in_sync(sector: &Sector) {
+    assert_eq!(
+        sector.occupancy.len(),
+        sector.units().map(|u| u.tiles().count()).sum::<usize>()
+    );
+    for unit in sector.units() {
+        for pos in unit.tiles() {
+            assert_eq!(sector.unit_id_at(pos), Some(unit.id()));
+        }
+    }
+}
+
+#[test]
+pub fn test_unit_ids() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let mut strong = test_stats(3.);
+    strong.attack = 50;
+    let attacker = spawn_with_stats(&mut sector, Vector2::new(0, 0), strong, Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(1));
+    assert_ne!(attacker, target);
+    assert_eq!(sector.unit_id_at(Vector2::new(1, 0)), Some(target));
+    sector.attack_unit(attacker, target, &mut rng).unwrap();
+    // IDs aren't reused once a unit is gone
+    let replacement = spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(1));
+    assert_ne!(replacement, target);
+    assert!(sector.unit(target).is_none());
+    // Can't place two units on the same tile, or outside the sector
+    let unit = Unit::new(Vector2::new(1, 0), test_stats(3.), Team::new(0), &sector).unwrap();
+    assert_eq!(sector.add_unit(unit), Err(ActionError::Occupied));
+    let mut unit = Unit::new(Vector2::new(0, 0), test_stats(3.), Team::new(0), &sector).unwrap();
+    unit.pos = Vector2::new(4, 0);
+    assert_eq!(sector.add_unit(unit), Err(ActionError::OutOfBounds));
+    assert_eq!(sector.units().count(), 2);
+}
+
+#[test]
+pub fn test_occupancy_stays_in_sync() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut sector = test_sector(Vector2::new(6, 6), &[]);
+    let mut strong = test_stats(3.);
+    strong.attack = 50;
+    let mover = spawn_with_stats(&mut sector, Vector2::new(0, 0), strong, Team::new(0));
+    let ally = spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(3, 1), 3., Team::new(1));
+    assert_occupancy_in_sync(&sector);
+
+    sector.move_unit(mover, Vector2::new(2, 1)).unwrap();
+    assert_occupancy_in_sync(&sector);
+    assert!(sector.unit_at_tile(Vector2::new(0, 0)).is_none());
+    assert_eq!(sector.unit_at_tile(Vector2::new(2, 1)).unwrap().id(), mover);
+
+    sector.attack_unit(mover, enemy, &mut rng).unwrap();
+    assert_occupancy_in_sync(&sector);
+    assert!(sector.unit_at_tile(Vector2::new(3, 1)).is_none());
+
+    let removed = sector.remove_unit(ally).unwrap();
+    assert_eq!(removed.pos(), Vector2::new(1, 0));
+    assert!(sector.remove_unit(ally).is_none());
+    assert_occupancy_in_sync(&sector);
+}
+
+#[test]
+pub fn test_units_in_radius() {
+    let mut sector = test_sector(Vector2::new(10, 10), &[]);
+    let center = spawn(&mut sector, Vector2::new(5, 5), 3., Team::new(0));
+    let near = spawn(&mut sector, Vector2::new(6, 6), 3., Team::new(1));
+    let edge = spawn(&mut sector, Vector2::new(5, 8), 3., Team::new(1));
+    spawn(&mut sector, Vector2::new(7, 7), 3., Team::new(1));
+    spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let ids = |radius| {
+        sector
+            .units_in_radius(Vector2::new(5, 5), radius)
+            .iter()
+            .map(|u| u.id())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(0), vec![center]);
+    // Small radii scan tiles, and large ones scan units, so check both give the same answer
+    assert_eq!(ids(1), vec![center]);
+    assert_eq!(ids(3), vec![center, near, edge]);
+    assert_eq!(sector.units_in_radius(Vector2::new(0, 0), 20).len(), 5);
+    // Radius queries are clipped to the sector
+    assert_eq!(sector.units_in_radius(Vector2::new(9, 9), 1).len(), 0);
+}
+
+#[test]
+pub fn test_threat_map() {
+    let mut sector = test_sector(Vector2::new(8, 1), &[]);
+    let ally = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(5, 0), 2., Team::new(1));
+    let threats = sector.threat_map(Team::new(0));
+    // The enemy can move 2 tiles either way and attack 1 tile past that
+    assert!(!threats.is_threatened(Vector2::new(1, 0)));
+    for x in 2..8 {
+        assert!(threats.is_threatened(Vector2::new(x, 0)));
+    }
+    let tile = threats.tile(Vector2::new(2, 0)).unwrap();
+    assert_eq!(tile.units().collect::<Vec<_>>(), vec![enemy]);
+    // Attack of 5 rolled up to 120%
+    assert_eq!(threats.max_damage(&sector, Vector2::new(2, 0)), 6);
+    assert_eq!(tile.max_damage(1), 5);
+    // The enemy team's map is about the ally
+    let threats = sector.threat_map(Team::new(1));
+    assert_eq!(
+        threats
+            .tile(Vector2::new(1, 0))
+            .unwrap()
+            .units()
+            .collect::<Vec<_>>(),
+        vec![ally]
+    );
+    assert!(!threats.is_threatened(Vector2::new(4, 0)));
+}
+
+#[test]
+pub fn test_threat_map_combines_units() {
+    let mut sector = test_sector(Vector2::new(7, 3), &[]);
+    set_terrain(&mut sector, &[Vector2::new(3, 1)], Terrain::Mountain);
+    let mut archer = test_stats(0.);
+    archer.range = 3;
+    // The mountain hides the archer from the first ally, so another needs to be close by
+    spawn(&mut sector, Vector2::new(0, 1), 0., Team::new(0));
+    spawn(&mut sector, Vector2::new(6, 2), 0., Team::new(0));
+    let first = spawn_with_stats(&mut sector, Vector2::new(6, 1), archer, Team::new(1));
+    let second = spawn(&mut sector, Vector2::new(5, 0), 1., Team::new(1));
+    let threats = sector.threat_map(Team::new(0));
+    let both = threats.tile(Vector2::new(4, 0)).unwrap();
+    assert_eq!(both.units().collect::<Vec<_>>(), vec![first, second]);
+    assert_eq!(both.max_damage(0), 12);
+    // The mountain blocks the archer's line of sight
+    assert!(!threats.is_threatened(Vector2::new(2, 1)));
+}
+
+#[test]
+pub fn test_threat_map_ignores_hidden_enemies() {
+    let mut sector = test_sector(Vector2::new(7, 1), &[]);
+    set_terrain(&mut sector, &[Vector2::new(2, 0)], Terrain::Forest);
+    spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    spawn(&mut sector, Vector2::new(4, 0), 2., Team::new(1));
+    let threats = sector.threat_map(Team::new(0));
+    assert!((0..7).all(|x| !threats.is_threatened(Vector2::new(x, 0))));
+}
+
+fn set_zone_of_control(sector: &mut Sector, unit: UnitId, zone_of_control: ZoneOfControl) {
+    sector.unit_mut(unit).unwrap().zone_of_control = zone_of_control;
+}
+
+#[test]
+pub fn test_zone_of_control_stops_movement() {
+    // The enemy's zone covers every tile in column 3 and the tiles either side of it
+    let mut sector = test_sector(Vector2::new(6, 3), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 1), 5., Team::new(0));
+    spawn(&mut sector, Vector2::new(3, 1), 0., Team::new(1));
+    assert!(can_reach(&sector, unit, Vector2::new(4, 0)));
+    set_zone_of_control(&mut sector, unit, ZoneOfControl::Stop);
+    assert!(can_reach(&sector, unit, Vector2::new(3, 0)));
+    assert!(!can_reach(&sector, unit, Vector2::new(4, 0)));
+    assert!(sector
+        .find_unit_path(unit, Vector2::new(4, 0), None)
+        .is_none());
+    assert_eq!(
+        sector.move_unit(unit, Vector2::new(4, 0)),
+        Err(ActionError::Unreachable)
+    );
+    // Entering the zone uses up the rest of the unit's movement
+    sector.move_unit(unit, Vector2::new(2, 1)).unwrap();
+    assert_eq!(sector.unit(unit).unwrap().movement(), 0.);
+    // Allies don't have a zone of control
+    let mut sector = test_sector(Vector2::new(6, 3), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 1), 5., Team::new(0));
+    spawn(&mut sector, Vector2::new(3, 1), 0., Team::new(0));
+    set_zone_of_control(&mut sector, unit, ZoneOfControl::Stop);
+    assert!(can_reach(&sector, unit, Vector2::new(4, 0)));
+}
+
+#[test]
+pub fn test_zone_of_control_extra_cost() {
+    let mut sector = test_sector(Vector2::new(6, 3), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 1), 5., Team::new(0));
+    spawn(&mut sector, Vector2::new(3, 1), 0., Team::new(1));
+    set_zone_of_control(&mut sector, unit, ZoneOfControl::ExtraCost(1.));
+    let path = sector
+        .find_unit_path(unit, Vector2::new(4, 0), None)
+        .unwrap();
+    assert_eq!(path.cost(), 6.);
+    assert!(can_reach(&sector, unit, Vector2::new(3, 0)));
+    assert!(!can_reach(&sector, unit, Vector2::new(4, 0)));
+    sector.move_unit(unit, Vector2::new(2, 1)).unwrap();
+    assert_eq!(sector.unit(unit).unwrap().movement(), 2.);
+}
+
+#[test]
+pub fn test_zone_of_control_ambush_cost() {
+    // The lookout can see the first enemy, but nobody can see the second one until the scout
+    // walks into it while in the first one's zone of control
+    let mut sector = test_sector(Vector2::new(7, 3), &[]);
+    let mut stats = test_stats(10.);
+    stats.sight = 1;
+    let scout = spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    spawn_with_stats(&mut sector, Vector2::new(3, 2), stats, Team::new(0));
+    spawn(&mut sector, Vector2::new(3, 1), 0., Team::new(1));
+    spawn(&mut sector, Vector2::new(4, 0), 0., Team::new(1));
+    set_zone_of_control(&mut sector, scout, ZoneOfControl::ExtraCost(1.));
+    let path = sector.move_unit(scout, Vector2::new(6, 0)).unwrap();
+    assert_eq!(path.end(), Vector2::new(3, 0));
+    assert_eq!(path.cost(), 4.);
+}
+
+#[test]
+pub fn test_zone_of_control_ignores_hidden_enemies() {
+    let mut sector = test_sector(Vector2::new(7, 3), &[]);
+    let forest = (0..3).map(|y| Vector2::new(2, y)).collect::<Vec<_>>();
+    set_terrain(&mut sector, &forest, Terrain::Forest);
+    let unit = spawn(&mut sector, Vector2::new(0, 1), 10., Team::new(0));
+    spawn(&mut sector, Vector2::new(5, 1), 0., Team::new(1));
+    set_zone_of_control(&mut sector, unit, ZoneOfControl::Stop);
+    assert!(!sector.in_enemy_zone(Team::new(0), Vector2::new(5, 0)));
+    assert!(can_reach(&sector, unit, Vector2::new(6, 0)));
+}
+
+#[test]
+pub fn test_unit_type_zone_of_control() {
+    let registry = load_unit_types_json().unwrap();
+    assert_eq!(
+        registry.get("infantry").unwrap().zone_of_control(),
+        ZoneOfControl::Stop
+    );
+    assert_eq!(
+        registry.get("ranger").unwrap().zone_of_control(),
+        ZoneOfControl::ExtraCost(1.)
+    );
+    let sector = test_sector(Vector2::new(1, 1), &[]);
+    let ranger = registry
+        .spawn("ranger", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    assert_eq!(ranger.zone_of_control(), ZoneOfControl::ExtraCost(1.));
+    let unit_json = |zone_of_control: &str| {
+        format!(
+            r#"[{{"id": "a", "name": "Test", "sprite": {{"x": 0, "y": 0}}, "movement": 3.0,
+            "health": 5, "attack": {{"damage": 2, "range": 1}}, "defense": 1, "sight": 3,
+            "terrain_costs": {{}}, "zone_of_control": {}}}]"#,
+            zone_of_control
+        )
+    };
+    assert!(UnitRegistry::from_json(&unit_json(r#""none""#)).is_ok());
+    assert!(UnitRegistry::from_json(&unit_json(r#"{"extra_cost": 2.0}"#)).is_ok());
+    assert!(UnitRegistry::from_json(&unit_json(r#"{"extra_cost": -1.0}"#)).is_err());
+    assert!(UnitRegistry::from_json(&unit_json(r#""sometimes""#)).is_err());
+}
+
+#[test]
+pub fn test_status_effect_stacking() {
+    let mut sector = test_sector(Vector2::new(2, 1), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    let apply = |sector: &mut Sector, kind, turns| {
+        sector
+            .apply_effect(unit, StatusEffect::new(kind, turns))
+            .unwrap();
+    };
+    let effect = |sector: &Sector, kind| {
+        *sector
+            .unit(unit)
+            .unwrap()
+            .effects()
+            .iter()
+            .find(|e: &&StatusEffect| e.kind == kind)
+            .unwrap()
+    };
+    // Slowed keeps the longest duration
+    apply(&mut sector, StatusKind::Slowed, 3);
+    apply(&mut sector, StatusKind::Slowed, 1);
+    assert_eq!(effect(&sector, StatusKind::Slowed).turns, 3);
+    // Stuns add up
+    apply(&mut sector, StatusKind::Stunned, 1);
+    apply(&mut sector, StatusKind::Stunned, 2);
+    assert_eq!(effect(&sector, StatusKind::Stunned).turns, 3);
+    // Poison intensifies up to three stacks
+    for _ in 0..5 {
+        apply(&mut sector, StatusKind::Poisoned, 2);
+    }
+    assert_eq!(effect(&sector, StatusKind::Poisoned).stacks, 3);
+    assert_eq!(sector.unit(unit).unwrap().effects().len(), 3);
+    assert_eq!(
+        sector.apply_effect(UnitId(99), StatusEffect::new(StatusKind::Slowed, 1)),
+        Err(ActionError::NoUnit)
+    );
+}
+
+#[test]
+pub fn test_status_effect_modifiers() {
+    let mut sector = test_sector(Vector2::new(6, 1), &[]);
+    let attacker = spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    let target = spawn(&mut sector, Vector2::new(1, 0), 4., Team::new(1));
+    // Attack 5 against defense 1
+    let damage = |sector: &Sector| {
+        sector.base_damage(sector.unit(attacker).unwrap(), sector.unit(target).unwrap())
+    };
+    assert_eq!(damage(&sector), 4);
+    let entrenched = StatusEffect::new(StatusKind::Entrenched, 1);
+    sector.apply_effect(target, entrenched).unwrap();
+    assert_eq!(damage(&sector), 2);
+    let poisoned = StatusEffect::new(StatusKind::Poisoned, 1);
+    sector.apply_effect(attacker, poisoned).unwrap();
+    assert_eq!(sector.unit(attacker).unwrap().attack(), 4);
+    assert_eq!(damage(&sector), 1);
+    // Slowing takes away movement straight away, and navigation follows
+    let slowed = StatusEffect::new(StatusKind::Slowed, 1);
+    sector.apply_effect(target, slowed).unwrap();
+    let unit = sector.unit(target).unwrap();
+    assert_eq!((unit.movement(), unit.max_movement()), (2., 2.));
+    assert!(can_reach(&sector, target, Vector2::new(3, 0)));
+    assert!(!can_reach(&sector, target, Vector2::new(4, 0)));
+}
+
+#[test]
+pub fn test_stunned_units_cant_act() {
+    let mut sector = test_sector(Vector2::new(6, 1), &[]);
+    let stunned = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(1, 0), 2., Team::new(1));
+    let stun = StatusEffect::new(StatusKind::Stunned, 1);
+    sector.apply_effect(stunned, stun).unwrap();
+    assert_eq!(
+        sector.check_attack(stunned, enemy),
+        Err(ActionError::NoActionsLeft)
+    );
+    assert!(!can_reach(&sector, stunned, Vector2::new(1, 0)));
+    sector.start_phase(Team::new(0));
+    let unit = sector.unit(stunned).unwrap();
+    assert_eq!((unit.movement(), unit.actions()), (0., 0));
+    // Stunned enemies aren't a threat
+    assert!(!sector
+        .threat_map(Team::new(1))
+        .is_threatened(Vector2::new(1, 0)));
+    // Once the stun runs out, the unit is back to normal the phase after
+    sector.end_phase(Team::new(0));
+    assert!(sector.unit(stunned).unwrap().effects().is_empty());
+    sector.start_phase(Team::new(0));
+    let unit = sector.unit(stunned).unwrap();
+    assert_eq!((unit.movement(), unit.actions()), (2., 1));
+}
+
+#[test]
+pub fn test_status_effects_tick() {
+    let mut sector = test_sector(Vector2::new(3, 1), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    let other = spawn(&mut sector, Vector2::new(2, 0), 2., Team::new(1));
+    let mut poison = StatusEffect::new(StatusKind::Poisoned, 2);
+    poison.stacks = 2;
+    sector.apply_effect(unit, poison).unwrap();
+    sector.apply_effect(other, poison).unwrap();
+    sector.drain_events().count();
+    // Only the team whose phase it is ticks
+    sector.start_phase(Team::new(0));
+    assert_eq!(sector.unit(unit).unwrap().health(), 6);
+    assert_eq!(sector.unit(other).unwrap().health(), 10);
+    assert_eq!(
+        sector.drain_events().collect::<Vec<_>>(),
+        vec![SectorEvent::EffectDamaged {
+            unit,
+            kind: StatusKind::Poisoned,
+            damage: 4,
+        }]
+    );
+    sector.end_phase(Team::new(0));
+    assert_eq!(sector.unit(unit).unwrap().effects()[0].turns, 1);
+    assert_eq!(sector.unit(other).unwrap().effects()[0].turns, 2);
+    sector.start_phase(Team::new(0));
+    sector.end_phase(Team::new(0));
+    assert_eq!(sector.unit(unit).unwrap().health(), 2);
+    assert!(sector.unit(unit).unwrap().effects().is_empty());
+    assert!(sector.drain_events().any(|e| e
+        == SectorEvent::EffectExpired {
+            unit,
+            kind: StatusKind::Poisoned
+        }));
+    // Effects can kill
+    sector.apply_effect(unit, poison).unwrap();
+    sector.start_phase(Team::new(0));
+    assert!(sector.unit(unit).is_none());
+    assert!(sector.unit_at_tile(Vector2::new(0, 0)).is_none());
+    assert!(sector
+        .drain_events()
+        .any(|e| matches!(e, SectorEvent::UnitDied { unit: u, .. } if u == unit)));
+}
+
+#[test]
+pub fn test_attack_inflicts_effect() {
+    let registry = load_unit_types_json().unwrap();
+    let mut sector = test_sector(Vector2::new(6, 1), &[]);
+    let ranger = registry
+        .spawn("ranger", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    let ranger = sector.add_unit(ranger).unwrap();
+    let mut stats = test_stats(4.);
+    stats.max_health = 50;
+    let target = spawn_with_stats(&mut sector, Vector2::new(2, 0), stats, Team::new(1));
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    sector.attack_unit(ranger, target, &mut rng).unwrap();
+    assert_eq!(
+        sector.unit(target).unwrap().effects(),
+        &[StatusEffect::new(StatusKind::Slowed, 1)]
+    );
+    assert_eq!(sector.unit(target).unwrap().max_movement(), 2.);
+    let invalid = r#"[{"id": "a", "name": "Test", "sprite": {"x": 0, "y": 0}, "movement": 3.0,
+        "health": 5, "attack": {"damage": 2, "range": 1, "inflicts": {"kind": "stunned",
+        "turns": 0}}, "defense": 1, "sight": 3}]"#;
+    assert!(UnitRegistry::from_json(invalid).is_err());
+}
+
+#[test]
+pub fn test_objective_status() {
+    let mut sector = test_sector(Vector2::new(5, 1), &[]);
+    let ally = spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(4, 0), 4., Team::new(1));
+    let status = |sector: &Sector, goal, turn| {
+        Objective {
+            team: Team::new(0),
+            goal,
+        }
+        .status(sector, turn)
+    };
+    let escort = Goal::Escort {
+        unit: ally,
+        to: Vector2::new(2, 0),
+    };
+    let survive = Goal::Survive { turns: 3 };
+    assert_eq!(
+        status(&sector, Goal::Eliminate, 1),
+        ObjectiveStatus::InProgress
+    );
+    assert_eq!(status(&sector, escort, 1), ObjectiveStatus::InProgress);
+    assert_eq!(status(&sector, survive, 3), ObjectiveStatus::InProgress);
+    assert_eq!(status(&sector, survive, 4), ObjectiveStatus::Complete);
+    sector.move_unit(ally, Vector2::new(2, 0)).unwrap();
+    assert_eq!(status(&sector, escort, 1), ObjectiveStatus::Complete);
+    sector.remove_unit(enemy);
+    assert_eq!(
+        status(&sector, Goal::Eliminate, 1),
+        ObjectiveStatus::Complete
+    );
+    sector.remove_unit(ally);
+    assert_eq!(status(&sector, escort, 1), ObjectiveStatus::Failed);
+}
+
+#[test]
+pub fn test_hold_region() {
+    let mut sector = test_sector(Vector2::new(6, 1), &[]);
+    let ally = spawn(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    let hold = Objective {
+        team: Team::new(0),
+        goal: Goal::HoldRegion {
+            min: Vector2::new(2, 0),
+            max: Vector2::new(3, 0),
+            turns: 2,
+            held: 0,
+        },
+    };
+    sector.add_objective(hold).unwrap();
+    let held = |sector: &Sector| match sector.objectives()[0].goal {
+        Goal::HoldRegion { held, .. } => held,
+        _ => unreachable!(),
+    };
+    sector.update_objectives(Team::new(0));
+    assert_eq!(held(&sector), 0);
+    sector.move_unit(ally, Vector2::new(3, 0)).unwrap();
+    sector.update_objectives(Team::new(0));
+    // Only the team's own phases count
+    sector.update_objectives(Team::new(1));
+    assert_eq!(held(&sector), 1);
+    assert_eq!(
+        sector.objectives()[0].status(&sector, 1),
+        ObjectiveStatus::InProgress
+    );
+    // An enemy in the region contests it, and the count starts again
+    let enemy = spawn(&mut sector, Vector2::new(2, 0), 4., Team::new(1));
+    sector.update_objectives(Team::new(0));
+    assert_eq!(held(&sector), 0);
+    sector.remove_unit(enemy);
+    sector.update_objectives(Team::new(0));
+    sector.update_objectives(Team::new(0));
+    assert_eq!(
+        sector.objectives()[0].status(&sector, 1),
+        ObjectiveStatus::Complete
+    );
+}
+
+#[test]
+pub fn test_objective_validation() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let objective = |goal| Objective {
+        team: Team::new(0),
+        goal,
+    };
+    let region = |min, max| Goal::HoldRegion {
+        min,
+        max,
+        turns: 1,
+        held: 0,
+    };
+    let outside = region(Vector2::new(0, 0), Vector2::new(4, 1));
+    assert!(sector.add_objective(objective(outside)).is_err());
+    let backwards = region(Vector2::new(2, 2), Vector2::new(1, 1));
+    assert!(sector.add_objective(objective(backwards)).is_err());
+    let escort = Goal::Escort {
+        unit: UnitId(0),
+        to: Vector2::new(0, 9),
+    };
+    assert!(sector.add_objective(objective(escort)).is_err());
+    assert!(sector.objectives().is_empty());
+    let valid = region(Vector2::new(1, 1), Vector2::new(3, 3));
+    assert!(sector.add_objective(objective(valid)).is_ok());
+    assert!(sector.add_objective(objective(Goal::Eliminate)).is_ok());
+}
+
+#[test]
+pub fn test_place_unit() {
+    // Water can't be stood on, so the closest tile to (0, 0) is taken first
+    let mut sector = test_sector(Vector2::new(2, 2), &[Vector2::new(0, 0)]);
+    let first = spawn(&mut sector, Vector2::new(1, 0), 3., Team::new(0));
+    let unit = Unit::new(Vector2::new(0, 0), test_stats(3.), Team::new(0), &sector).unwrap();
+    let placed = sector.place_unit(unit.clone(), Vector2::new(0, 0)).unwrap();
+    assert_ne!(placed, first);
+    assert_eq!(sector.unit(placed).unwrap().pos(), Vector2::new(0, 1));
+    sector.place_unit(unit.clone(), Vector2::new(0, 0)).unwrap();
+    assert_eq!(
+        sector.place_unit(unit, Vector2::new(0, 0)),
+        Err(ActionError::Occupied)
+    );
+}
+
+/// Add a unit covering 2x2 tiles, with `pos` as its top left
+fn spawn_large(sector: &mut Sector, pos: Vector2<u32>, movement: f32, team: Team) -> UnitId {
+    let mut unit = Unit::new(pos, test_stats(movement), team, sector).unwrap();
+    unit.footprint = Vector2::new(2, 2);
+    sector.add_unit(unit).unwrap()
+}
+
+#[test]
+pub fn test_large_unit_occupancy() {
+    let mut sector = test_sector(Vector2::new(5, 5), &[]);
+    let large = spawn_large(&mut sector, Vector2::new(1, 1), 4., Team::new(0));
+    assert_occupancy_in_sync(&sector);
+    for pos in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+        assert_eq!(sector.unit_id_at(Vector2::new(pos.0, pos.1)), Some(large));
+    }
+    assert!(sector.unit(large).unwrap().covers(Vector2::new(2, 2)));
+    assert!(!sector.unit(large).unwrap().covers(Vector2::new(3, 2)));
+
+    // Nothing can overlap any of its tiles, and it can't hang off the edge of the sector
+    let unit = Unit::new(Vector2::new(2, 2), test_stats(3.), Team::new(0), &sector).unwrap();
+    assert_eq!(sector.add_unit(unit), Err(ActionError::Occupied));
+    let mut unit = Unit::new(Vector2::new(0, 0), test_stats(3.), Team::new(0), &sector).unwrap();
+    unit.footprint = Vector2::new(2, 2);
+    assert_eq!(sector.add_unit(unit.clone()), Err(ActionError::Occupied));
+    unit.pos = Vector2::new(4, 0);
+    assert_eq!(sector.add_unit(unit), Err(ActionError::OutOfBounds));
+
+    // Found by any of its tiles
+    let ids = |center, radius| {
+        sector
+            .units_in_radius(center, radius)
+            .iter()
+            .map(|u| u.id())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(Vector2::new(2, 2), 0), vec![large]);
+    assert_eq!(ids(Vector2::new(3, 3), 2), vec![large]);
+    assert!(ids(Vector2::new(3, 3), 1).is_empty());
+
+    // Every tile is indexed again after loading
+    let mut loaded: Sector =
+        serde_json::from_str(&serde_json::to_string(&sector).unwrap()).unwrap();
+    loaded.restore().unwrap();
+    assert_occupancy_in_sync(&loaded);
+
+    sector.move_unit(large, Vector2::new(3, 3)).unwrap();
+    assert_occupancy_in_sync(&sector);
+    assert!(sector.unit_at_tile(Vector2::new(1, 1)).is_none());
+    assert_eq!(sector.unit_id_at(Vector2::new(4, 4)), Some(large));
+    sector.remove_unit(large).unwrap();
+    assert!(sector.occupancy.is_empty());
+}
+
+#[test]
+pub fn test_large_unit_navigation() {
+    // Wall of water with a gap one tile wide
+    let water = [Vector2::new(2, 0), Vector2::new(2, 2), Vector2::new(2, 3)];
+    let mut sector = test_sector(Vector2::new(5, 4), &water);
+    let large = spawn_large(&mut sector, Vector2::new(0, 0), 10., Team::new(0));
+    let small = spawn(&mut sector, Vector2::new(0, 3), 10., Team::new(0));
+    assert!(can_reach(&sector, large, Vector2::new(0, 1)));
+    assert!(can_reach(&sector, small, Vector2::new(3, 0)));
+    // Too big for the gap, or to stand with any tile on water
+    assert!(!can_reach(&sector, large, Vector2::new(1, 0)));
+    assert!(!can_reach(&sector, large, Vector2::new(3, 0)));
+    // Would cover the ally, or hang off the edge
+    assert!(!can_reach(&sector, large, Vector2::new(0, 2)));
+    assert!(!can_reach(&sector, large, Vector2::new(1, 3)));
+    assert_eq!(
+        sector.move_unit(large, Vector2::new(3, 0)),
+        Err(ActionError::Unreachable)
+    );
+    assert!(sector
+        .find_unit_path(large, Vector2::new(3, 0), None)
+        .is_none());
+}
+
+#[test]
+pub fn test_large_unit_blocks_and_is_blocked() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let large = spawn_large(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(3, 3), 6., Team::new(1));
+    spawn(&mut sector, Vector2::new(3, 0), 3., Team::new(0));
+    // Only somewhere to stop if every tile it would cover is free
+    assert!(can_reach(&sector, large, Vector2::new(0, 2)));
+    assert!(!can_reach(&sector, large, Vector2::new(2, 2)));
+    assert!(!can_reach(&sector, large, Vector2::new(2, 0)));
+    // Every one of its tiles blocks enemies
+    assert!(!can_reach(&sector, enemy, Vector2::new(1, 1)));
+    assert!(can_reach(&sector, enemy, Vector2::new(0, 3)));
+    // It can be attacked on whichever of its tiles is closest
+    sector.move_unit(enemy, Vector2::new(2, 1)).unwrap();
+    assert_eq!(sector.check_attack(enemy, large), Ok(()));
+}
+
+#[test]
+pub fn test_large_unit_attacks_from_any_side() {
+    // Range 1, so only the large unit's bottom right tile is close enough
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let large = spawn_large(&mut sector, Vector2::new(0, 0), 4., Team::new(0));
+    for (pos, result) in [
+        (Vector2::new(2, 1), Ok(())),
+        (Vector2::new(1, 2), Ok(())),
+        (Vector2::new(3, 3), Err(ActionError::OutOfRange)),
+    ] {
+        let enemy = spawn(&mut sector, pos, 4., Team::new(1));
+        assert_eq!(sector.check_attack(large, enemy), result);
+        sector.remove_unit(enemy);
+    }
+}
+
+#[test]
+pub fn test_large_unit_threat_map() {
+    // The same reach as above, for a large enemy that can't move
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let large = spawn_large(&mut sector, Vector2::new(0, 0), 0., Team::new(1));
+    spawn(&mut sector, Vector2::new(3, 3), 0., Team::new(0));
+    let threats = sector.threat_map(Team::new(0));
+    for pos in [Vector2::new(2, 1), Vector2::new(1, 2), Vector2::new(2, 0)] {
+        assert_eq!(
+            threats.tile(pos).unwrap().units().collect::<Vec<_>>(),
+            vec![large]
+        );
+    }
+    assert!(!threats.is_threatened(Vector2::new(2, 2)));
+    assert!(!threats.is_threatened(Vector2::new(3, 1)));
+}
+
+#[test]
+pub fn test_large_unit_terrain_defense() {
+    // Only the tile furthest from the attacker is in the forest
+    let mut sector = test_sector(Vector2::new(4, 2), &[]);
+    let attacker = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    let large = spawn_large(&mut sector, Vector2::new(1, 0), 2., Team::new(1));
+    let damage = |sector: &Sector| {
+        sector.base_damage(sector.unit(attacker).unwrap(), sector.unit(large).unwrap())
+    };
+    assert_eq!(damage(&sector), 4);
+    set_terrain(&mut sector, &[Vector2::new(2, 1)], Terrain::Forest);
+    assert_eq!(damage(&sector), 3);
+    assert_eq!(
+        sector.terrain_defense(Vector2::new(1, 0), Vector2::new(2, 2)),
+        1
+    );
+    assert_eq!(
+        sector.terrain_defense(Vector2::new(1, 0), Vector2::new(1, 1)),
+        0
+    );
+}
+
+#[test]
+pub fn test_large_unit_holds_regions_and_sees() {
+    let mut sector = test_sector(Vector2::new(6, 4), &[]);
+    let team = Team::new(0);
+    let mut stats = test_stats(4.);
+    stats.sight = 2;
+    let mut unit = Unit::new(Vector2::new(0, 0), stats, team, &sector).unwrap();
+    unit.footprint = Vector2::new(2, 2);
+    sector.add_unit(unit).unwrap();
+    // Only its bottom right tile can see this far, or is in the region
+    assert!(sector.can_see(team, Vector2::new(3, 1)));
+    let hold = Objective {
+        team,
+        goal: Goal::HoldRegion {
+            min: Vector2::new(1, 1),
+            max: Vector2::new(3, 3),
+            turns: 1,
+            held: 0,
+        },
+    };
+    sector.add_objective(hold).unwrap();
+    sector.update_objectives(team);
+    assert_eq!(
+        sector.objectives()[0].status(&sector, 1),
+        ObjectiveStatus::Complete
+    );
+}
+
+#[test]
+pub fn test_large_unit_passes_allies() {
+    // Corridor two tiles wide, so the unit has to cover the middle to get through
+    let mut sector = test_sector(Vector2::new(6, 2), &[]);
+    let large = spawn_large(&mut sector, Vector2::new(0, 0), 10., Team::new(0));
+    let ally = spawn(&mut sector, Vector2::new(2, 0), 3., Team::new(0));
+    assert!(can_reach(&sector, large, Vector2::new(4, 0)));
+    assert!(!can_reach(&sector, large, Vector2::new(1, 0)));
+    assert!(!can_reach(&sector, large, Vector2::new(2, 0)));
+    sector.remove_unit(ally);
+    spawn(&mut sector, Vector2::new(2, 1), 3., Team::new(1));
+    assert!(!can_reach(&sector, large, Vector2::new(4, 0)));
+}
+
+#[test]
+pub fn test_unit_type_footprint() {
+    let registry = load_unit_types_json().unwrap();
+    let sector = test_sector(Vector2::new(4, 4), &[]);
+    let infantry = registry
+        .spawn("infantry", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    assert_eq!(infantry.footprint(), Vector2::new(1, 1));
+    let siege = registry
+        .spawn("siege_engine", Vector2::new(0, 0), Team::new(0), &sector)
+        .unwrap();
+    assert_eq!(siege.footprint(), Vector2::new(2, 2));
+    let empty = r#"[{"id": "a", "name": "Test", "sprite": {"x": 0, "y": 0}, "movement": 3.0,
+        "health": 5, "attack": {"damage": 2, "range": 1}, "defense": 1, "sight": 3,
+        "footprint": {"x": 0, "y": 2}}]"#;
+    assert!(UnitRegistry::from_json(empty).is_err());
+}
+
+#[test]
+pub fn test_topology_navigation() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 1.5, Team::new(0));
+    assert!(!can_reach(&sector, unit, Vector2::new(1, 1)));
+    sector.set_topology(Movement::Diagonal.into());
+    assert!(can_reach(&sector, unit, Vector2::new(1, 1)));
+    let path = sector
+        .find_unit_path(unit, Vector2::new(1, 1), None)
+        .unwrap();
+    assert_eq!(path.steps(), 1);
+    sector.move_unit(unit, Vector2::new(1, 1)).unwrap();
+    assert!((sector.unit(unit).unwrap().movement() - (1.5 - SQRT_2)).abs() < 1e-4);
+
+    // Enemies block corners as well as tiles
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 1.5, Team::new(0));
+    spawn(&mut sector, Vector2::new(1, 0), 1., Team::new(1));
+    sector.set_topology(Movement::Diagonal.into());
+    assert!(can_reach(&sector, unit, Vector2::new(1, 1)));
+    sector.set_topology(Topology {
+        movement: Movement::Diagonal,
+        cut_corners: false,
+    });
+    assert!(!can_reach(&sector, unit, Vector2::new(1, 1)));
+    assert!(sector
+        .find_unit_path(unit, Vector2::new(1, 1), None)
+        .is_some_and(|p| p.steps() == 2));
+}
+
+#[test]
+pub fn test_threat_map_topology() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    spawn(&mut sector, Vector2::new(0, 0), 1.5, Team::new(1));
+    spawn(&mut sector, Vector2::new(3, 3), 1., Team::new(0));
+    assert!(!sector
+        .threat_map(Team::new(0))
+        .is_threatened(Vector2::new(2, 1)));
+    // Moving diagonally first puts the tile within reach
+    sector.set_topology(Movement::Diagonal.into());
+    assert!(sector
+        .threat_map(Team::new(0))
+        .is_threatened(Vector2::new(2, 1)));
+}
+
+#[test]
+pub fn test_navigation_follows_terrain() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    assert!(can_reach(&sector, unit, Vector2::new(2, 0)));
+    set_terrain(&mut sector, &[Vector2::new(1, 0)], Terrain::Water);
+    assert!(!can_reach(&sector, unit, Vector2::new(2, 0)));
+    set_terrain(&mut sector, &[Vector2::new(1, 0)], Terrain::Grass);
+    assert!(can_reach(&sector, unit, Vector2::new(2, 0)));
+}
+
+#[test]
+pub fn test_navigation_follows_units() {
+    let mut sector = test_sector(Vector2::new(4, 1), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(2, 0), 3., Team::new(1));
+    assert!(!can_reach(&sector, unit, Vector2::new(3, 0)));
+    let removed = sector.remove_unit(enemy).unwrap();
+    assert!(can_reach(&sector, unit, Vector2::new(3, 0)));
+    let enemy = sector.add_unit(removed).unwrap();
+    assert!(!can_reach(&sector, unit, Vector2::new(3, 0)));
+
+    // Another unit moving out of the way
+    sector.refresh_team(Team::new(1));
+    sector.move_unit(enemy, Vector2::new(3, 0)).unwrap();
+    assert!(can_reach(&sector, unit, Vector2::new(2, 0)));
+    assert!(!can_reach(&sector, unit, Vector2::new(3, 0)));
+
+    // An enemy that can't be seen doesn't block, until it's seen
+    let mut sector = test_sector(Vector2::new(5, 1), &[]);
+    let mut stats = test_stats(4.);
+    stats.sight = 1;
+    let unit = spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    spawn(&mut sector, Vector2::new(3, 0), 1., Team::new(1));
+    assert!(can_reach(&sector, unit, Vector2::new(4, 0)));
+    spawn_with_stats(&mut sector, Vector2::new(2, 0), stats, Team::new(0));
+    assert!(!can_reach(&sector, unit, Vector2::new(4, 0)));
+}
+
+#[test]
+pub fn test_navigation_follows_unit_stats() {
+    let mut sector = test_sector(Vector2::new(4, 3), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 1), 3., Team::new(0));
+    assert!(can_reach(&sector, unit, Vector2::new(3, 1)));
+
+    // Spending movement, and getting it back
+    sector.move_unit(unit, Vector2::new(1, 1)).unwrap();
+    assert!(can_reach(&sector, unit, Vector2::new(3, 1)));
+    assert!(!can_reach(&sector, unit, Vector2::new(3, 0)));
+    sector.refresh_team(Team::new(0));
+    assert!(can_reach(&sector, unit, Vector2::new(3, 0)));
+
+    // Changes made directly to the unit
+    spawn(&mut sector, Vector2::new(2, 0), 1., Team::new(1));
+    assert!(can_reach(&sector, unit, Vector2::new(3, 1)));
+    sector.unit_mut(unit).unwrap().zone_of_control = ZoneOfControl::Stop;
+    assert!(!can_reach(&sector, unit, Vector2::new(3, 1)));
+    sector.unit_mut(unit).unwrap().zone_of_control = ZoneOfControl::None;
+    sector
+        .unit_mut(unit)
+        .unwrap()
+        .terrain_costs
+        .insert(Terrain::Grass, Some(2.));
+    assert!(!can_reach(&sector, unit, Vector2::new(3, 1)));
+    assert!(can_reach(&sector, unit, Vector2::new(1, 2)));
+}
+
+#[test]
+pub fn test_navigation_follows_unit_between_sectors() {
+    let mut first = test_sector(Vector2::new(3, 1), &[]);
+    let unit = spawn(&mut first, Vector2::new(0, 0), 2., Team::new(0));
+    assert!(can_reach(&first, unit, Vector2::new(2, 0)));
+    // The same size, and the unit lands on the same tile, but the way is blocked
+    let mut second = test_sector(Vector2::new(3, 1), &[Vector2::new(1, 0)]);
+    let moved = first.remove_unit(unit).unwrap();
+    let unit = second.place_unit(moved, Vector2::new(0, 0)).unwrap();
+    assert!(!can_reach(&second, unit, Vector2::new(2, 0)));
+}
+
+#[test]
+pub fn test_navigation_is_reused() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let unit = spawn(&mut sector, Vector2::new(0, 0), 2., Team::new(0));
+    assert!(can_reach(&sector, unit, Vector2::new(1, 1)));
+    let is_current = |sector: &Sector| {
+        let unit = sector.unit(unit).unwrap();
+        let nav = unit.nav.borrow();
+        nav.as_ref().is_some_and(|n| n.is_current(unit, sector))
+    };
+    assert!(is_current(&sector));
+    // Nothing changes when a move is rejected
+    assert!(sector.move_unit(unit, Vector2::new(3, 3)).is_err());
+    assert!(is_current(&sector));
+    sector.move_unit(unit, Vector2::new(1, 0)).unwrap();
+    assert!(!is_current(&sector));
+}
+
+/// Play out random moves, terrain changes and units coming and going, checking that every unit's
+/// navigation matches navigation generated from scratch after each one
+#[test]
+pub fn test_navigation_never_stale() {
+    use rand::Rng;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let size = Vector2::new(6, 6);
+    let mut sector = test_sector(size, &[]);
+    for i in 0..6 {
+        let pos = Vector2::new(i, rng.gen_range(0..size.y));
+        spawn(&mut sector, pos, 3., Team::new(i % 2));
+    }
+    let mut removed = Vec::new();
+    for _ in 0..300 {
+        let ids = sector.units().map(|u| u.id()).collect::<Vec<_>>();
+        let pos = Vector2::new(rng.gen_range(0..size.x), rng.gen_range(0..size.y));
+        match rng.gen_range(0..6) {
+            0 | 1 if !ids.is_empty() => {
+                let _ = sector.move_unit(ids[rng.gen_range(0..ids.len())], pos);
+            }
+            2 => {
+                let terrain = match rng.gen_bool(0.5) {
+                    true => Terrain::Water,
+                    false => Terrain::Grass,
+                };
+                set_terrain(&mut sector, &[pos], terrain);
+            }
+            3 if !ids.is_empty() => {
+                removed.extend(sector.remove_unit(ids[rng.gen_range(0..ids.len())]));
+            }
+            4 if !removed.is_empty() => {
+                let _ = sector.place_unit(removed.swap_remove(0), pos);
+            }
+            _ => sector.refresh_team(Team::new(rng.gen_range(0..2))),
+        }
+        for unit in sector.units() {
+            let fresh = navigation::movable_tiles(unit, &sector, unit.movement());
+            for tile in sector.tiles() {
+                let index = sector.index(tile.pos()) as usize;
+                assert_eq!(unit.can_reach_tile(tile.pos(), &sector), Some(fresh[index]));
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_capture_structures() {
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    let village = Vector2::new(1, 1);
+    sector
+        .add_structure(Structure::new(StructureKind::Village, village))
+        .unwrap();
+    let unit = spawn(&mut sector, village, 3., Team::new(0));
+    sector.capture_structures(Team::new(1));
+    assert_eq!(sector.structure_at(village).unwrap().owner, None);
+    sector.drain_events().for_each(drop);
+    sector.capture_structures(Team::new(0));
+    assert_eq!(
+        sector.structure_at(village).unwrap().owner,
+        Some(Team::new(0))
+    );
+    assert_eq!(
+        sector.drain_events().collect::<Vec<_>>(),
+        vec![SectorEvent::StructureCaptured {
+            pos: village,
+            team: Team::new(0),
+            from: None,
+        }]
+    );
+    // Holding on to a structure isn't capturing it again
+    sector.capture_structures(Team::new(0));
+    assert_eq!(sector.drain_events().count(), 0);
+
+    sector.remove_unit(unit);
+    spawn(&mut sector, village, 3., Team::new(1));
+    sector.capture_structures(Team::new(1));
+    assert!(sector.drain_events().any(|e| e
+        == SectorEvent::StructureCaptured {
+            pos: village,
+            team: Team::new(1),
+            from: Some(Team::new(0)),
+        }));
+    // Large units capture everything they cover
+    let mut sector = test_sector(Vector2::new(4, 4), &[]);
+    for pos in [Vector2::new(0, 0), Vector2::new(1, 1)] {
+        sector
+            .add_structure(Structure::new(StructureKind::Fort, pos))
+            .unwrap();
+    }
+    spawn_large(&mut sector, Vector2::new(0, 0), 3., Team::new(0));
+    sector.capture_structures(Team::new(0));
+    assert!(sector
+        .structures()
+        .iter()
+        .all(|s| s.owner == Some(Team::new(0))));
+}
+
+#[test]
+pub fn test_structure_benefits() {
+    let mut sector = test_sector(Vector2::new(6, 6), &[]);
+    let owned = |kind, pos, team| Structure {
+        owner: Some(team),
+        ..Structure::new(kind, pos)
+    };
+    for structure in [
+        owned(StructureKind::Village, Vector2::new(0, 0), Team::new(0)),
+        owned(StructureKind::Village, Vector2::new(5, 0), Team::new(0)),
+        owned(StructureKind::Fort, Vector2::new(2, 2), Team::new(0)),
+        owned(StructureKind::Fort, Vector2::new(3, 3), Team::new(0)),
+    ] {
+        sector.add_structure(structure).unwrap();
+    }
+    let healed = spawn(&mut sector, Vector2::new(2, 2), 3., Team::new(0));
+    let enemy = spawn(&mut sector, Vector2::new(3, 3), 3., Team::new(1));
+    for unit in [healed, enemy] {
+        sector.unit_mut(unit).unwrap().health = 5;
+    }
+
+    sector.start_phase(Team::new(0));
+    assert_eq!(sector.resources(Team::new(0)), 4);
+    assert_eq!(sector.resources(Team::new(1)), 0);
+    assert_eq!(sector.unit(healed).unwrap().health(), 8);
+    assert!(sector.drain_events().any(|e| e
+        == SectorEvent::UnitHealed {
+            unit: healed,
+            amount: 3,
+        }));
+    // Healing stops at full health, and never helps the enemy
+    sector.start_phase(Team::new(0));
+    assert_eq!(sector.resources(Team::new(0)), 8);
+    assert_eq!(sector.unit(healed).unwrap().health(), 10);
+    assert_eq!(sector.unit(enemy).unwrap().health(), 5);
+    sector.start_phase(Team::new(1));
+    assert_eq!(sector.unit(enemy).unwrap().health(), 5);
+}
+
+#[test]
+pub fn test_shrine_vision() {
+    let mut sector = test_sector(Vector2::new(12, 1), &[]);
+    let mut stats = test_stats(1.);
+    stats.sight = 1;
+    spawn_with_stats(&mut sector, Vector2::new(0, 0), stats, Team::new(0));
+    let shrine = Vector2::new(9, 0);
+    sector
+        .add_structure(Structure::new(StructureKind::Shrine, shrine))
+        .unwrap();
+    assert!(!sector.can_see(Team::new(0), Vector2::new(6, 0)));
+    let visitor = spawn(&mut sector, shrine, 1., Team::new(0));
+    sector.capture_structures(Team::new(0));
+    sector.remove_unit(visitor);
+    assert!(sector.can_see(Team::new(0), Vector2::new(6, 0)));
+    assert!(sector.can_see(Team::new(0), shrine));
+    // Even a team with no units left sees from its shrines
+    let mut sector = test_sector(Vector2::new(12, 1), &[]);
+    sector
+        .add_structure(Structure {
+            owner: Some(Team::new(1)),
+            ..Structure::new(StructureKind::Shrine, shrine)
+        })
+        .unwrap();
+    assert!(sector.can_see(Team::new(1), shrine));
+    assert!(!sector.can_see(Team::new(1), Vector2::new(0, 0)));
+}
+
+#[test]
+pub fn test_structure_placement() {
+    let mut sector = test_sector(Vector2::new(3, 1), &[Vector2::new(0, 0)]);
+    assert!(sector
+        .add_structure(Structure::new(StructureKind::Fort, Vector2::new(0, 0)))
+        .is_err());
+    assert!(sector
+        .add_structure(Structure::new(StructureKind::Fort, Vector2::new(3, 0)))
+        .is_err());
+    let placed = sector
+        .place_structure(StructureKind::Fort, None, Vector2::new(0, 0))
+        .unwrap();
+    assert_eq!(placed, Vector2::new(1, 0));
+    assert!(sector
+        .add_structure(Structure::new(StructureKind::Village, placed))
+        .is_err());
+
+    // Scattered structures avoid units as well
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    spawn(&mut sector, Vector2::new(2, 0), 1., Team::new(0));
+    assert!(sector
+        .scatter_structure(StructureKind::Village, None, &mut rng)
+        .is_err());
+    assert!(sector
+        .place_structure(StructureKind::Village, None, Vector2::new(0, 0))
+        .is_ok_and(|pos| pos == Vector2::new(2, 0)));
+    assert!(sector
+        .place_structure(StructureKind::Village, None, Vector2::new(0, 0))
+        .is_err());
+}
+
+#[test]
+pub fn test_recruit_unit() {
+    let mut sector = test_sector(Vector2::new(5, 5), &[Vector2::new(2, 1)]);
+    let fort = Vector2::new(2, 2);
+    let (ours, theirs) = (Team::new(0), Team::new(1));
+    for (kind, pos, owner) in [
+        (StructureKind::Fort, fort, ours),
+        (StructureKind::Village, Vector2::new(0, 0), ours),
+        (StructureKind::Fort, Vector2::new(4, 4), theirs),
+    ] {
+        sector
+            .add_structure(Structure {
+                owner: Some(owner),
+                ..Structure::new(kind, pos)
+            })
+            .unwrap();
+    }
+    let registry = load_unit_types_json().unwrap();
+    sector
+        .set_roster(registry.types().cloned().collect())
+        .unwrap();
+    spawn(&mut sector, Vector2::new(1, 2), 1., theirs);
+    sector.resources.insert(ours, 10);
+
+    let mut recruit = |from, unit_type, to| sector.recruit_unit(ours, from, unit_type, to);
+    let next_to = Vector2::new(3, 2);
+    for from in [Vector2::new(1, 1), Vector2::new(0, 0), Vector2::new(4, 4)] {
+        assert_eq!(
+            recruit(from, "infantry", next_to),
+            Err(ActionError::NoRecruiter)
+        );
+    }
+    assert_eq!(
+        recruit(fort, "dragon", next_to),
+        Err(ActionError::NotInRoster)
+    );
+    assert_eq!(
+        recruit(fort, "siege_engine", Vector2::new(3, 1)),
+        Err(ActionError::NotEnoughResources)
+    );
+    for to in [fort, Vector2::new(4, 2), Vector2::new(3, 3)] {
+        assert_eq!(recruit(fort, "infantry", to), Err(ActionError::NotAdjacent));
+    }
+    assert_eq!(
+        recruit(fort, "infantry", Vector2::new(2, 1)),
+        Err(ActionError::Impassable)
+    );
+    assert_eq!(
+        recruit(fort, "infantry", Vector2::new(1, 2)),
+        Err(ActionError::Occupied)
+    );
+
+    let recruited = recruit(fort, "infantry", next_to).unwrap();
+    assert_eq!(
+        recruit(fort, "infantry", Vector2::new(2, 3)),
+        Err(ActionError::RecruitLimit)
+    );
+    assert_eq!(sector.resources(ours), 6);
+    let unit = sector.unit(recruited).unwrap();
+    assert_eq!(
+        (unit.archetype(), unit.team(), unit.pos()),
+        (Some("infantry"), ours, next_to)
+    );
+    // Recruits wait until their team's next phase
+    assert_eq!((unit.movement(), unit.actions()), (0., 0));
+    assert!(sector.drain_events().any(|e| e
+        == SectorEvent::UnitRecruited {
+            unit: recruited,
+            from: fort,
+            team: ours,
+        }));
+    assert_occupancy_in_sync(&sector);
+
+    // Taking it back refunds it, and lets the fort recruit again
+    sector.revert_recruit(recruited, fort, 4).unwrap();
+    assert!(sector.unit(recruited).is_none());
+    assert_eq!(sector.resources(ours), 10);
+    assert_occupancy_in_sync(&sector);
+    sector
+        .recruit_unit(ours, fort, "infantry", next_to)
+        .unwrap();
+
+    // Large recruits only need to touch the structure
+    sector.start_phase(ours);
+    sector.resources.insert(ours, 12);
+    let siege = sector
+        .recruit_unit(ours, fort, "siege_engine", Vector2::new(2, 3))
+        .unwrap();
+    assert!(sector.unit(siege).unwrap().covers(Vector2::new(3, 4)));
+    assert_eq!(sector.resources(ours), 0);
+    assert_occupancy_in_sync(&sector);
+}
@@ -0,0 +1,150 @@
+use cgmath::Vector2;
+
+use super::{
+    combat::{in_range, max_damage},
+    footprint_tiles, navigation, Sector, Team, Unit, UnitId,
+};
+
+/// One unit that could attack a tile next turn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Threat {
+    pub unit: UnitId,
+    pub attack: u32,
+}
+
+/// Every unit that could attack a tile next turn
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TileThreat {
+    threats: Vec<Threat>,
+}
+
+impl TileThreat {
+    pub fn threats(&self) -> &[Threat] {
+        &self.threats
+    }
+
+    pub fn units(&self) -> impl Iterator<Item = UnitId> + '_ {
+        self.threats.iter().map(|t| t.unit)
+    }
+
+    pub fn is_threatened(&self) -> bool {
+        !self.threats.is_empty()
+    }
+
+    /// Most damage that a unit with `defense` (including any terrain bonus) could take here, if
+    /// every threatening unit attacked it with the best possible roll
+    pub fn max_damage(&self, defense: u32) -> u32 {
+        self.threats
+            .iter()
+            .map(|t| max_damage(t.attack, defense))
+            .sum()
+    }
+}
+
+/// Which tiles the enemies of a team could attack on their next turn, indexed the same way as
+/// the sector. See `Sector::threat_map`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreatMap {
+    tiles: Vec<TileThreat>,
+    size: Vector2<u32>,
+}
+
+impl ThreatMap {
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    pub fn tile(&self, pos: Vector2<u32>) -> Option<&TileThreat> {
+        if pos.x >= self.size.x || pos.y >= self.size.y {
+            return None;
+        }
+        self.tiles.get((pos.y * self.size.x + pos.x) as usize)
+    }
+
+    pub fn is_threatened(&self, pos: Vector2<u32>) -> bool {
+        self.tile(pos).is_some_and(TileThreat::is_threatened)
+    }
+
+    /// Every unit that could attack a unit covering `footprint` tiles from `pos`, each only once
+    /// however many of those tiles it could attack
+    pub fn footprint_threats(&self, pos: Vector2<u32>, footprint: Vector2<u32>) -> TileThreat {
+        let mut threats: Vec<Threat> = Vec::new();
+        for tile in footprint_tiles(pos, footprint).filter_map(|p| self.tile(p)) {
+            for threat in tile.threats() {
+                if !threats.iter().any(|t| t.unit == threat.unit) {
+                    threats.push(*threat);
+                }
+            }
+        }
+        TileThreat { threats }
+    }
+
+    /// Most damage a unit without any defense of its own could take on `pos`, taking the terrain
+    /// there into account
+    pub fn max_damage(&self, sector: &Sector, pos: Vector2<u32>) -> u32 {
+        let terrain_bonus = sector
+            .tile(pos)
+            .map_or(0, |t| t.contents().terrain().defense_bonus());
+        self.tile(pos).map_or(0, |t| t.max_damage(terrain_bonus))
+    }
+}
+
+impl Sector {
+    /// Every tile that enemies of `team` could attack next turn, after moving as far as their full
+    /// movement allows. Only enemies that `team` can currently see are included. Effects on
+    /// enemies last at least until the end of their next phase, so they're taken into account.
+    pub fn threat_map(&self, team: Team) -> ThreatMap {
+        let mut tiles = vec![TileThreat::default(); self.tiles.tiles().len()];
+        let enemies = self
+            .visible_units(team)
+            .filter(|u| u.team() != team && !u.modifiers().stunned);
+        for enemy in enemies {
+            let threat = Threat {
+                unit: enemy.id(),
+                attack: enemy.attack(),
+            };
+            for (i, threatened) in self.attackable_tiles(enemy).into_iter().enumerate() {
+                if threatened {
+                    tiles[i].threats.push(threat);
+                }
+            }
+        }
+        ThreatMap {
+            tiles,
+            size: self.size(),
+        }
+    }
+
+    /// Every tile that `unit` could attack from somewhere it could move to with full movement,
+    /// from any of the tiles it would cover there, indexed the same way as the sector
+    pub fn attackable_tiles(&self, unit: &Unit) -> Vec<bool> {
+        let mut attackable = vec![false; self.tiles.tiles().len()];
+        let movable = navigation::movable_tiles(unit, self, unit.max_movement());
+        let range = unit.stats().range as i32;
+        let origins = movable
+            .iter()
+            .enumerate()
+            .filter(|(_, movable)| **movable)
+            .map(|(i, _)| self.tiles.from_index(i));
+        // Large units attack from whichever of their tiles is closest, so any of them will do
+        let attack_from = origins.flat_map(|origin| footprint_tiles(origin, unit.footprint()));
+        for from in attack_from {
+            for dy in -range..=range {
+                let width = range - dy.abs();
+                for dx in -width..=width {
+                    let Some(target) = self.tiles.offset(from, Vector2::new(dx, dy)) else {
+                        continue;
+                    };
+                    let index = self.tiles.index(target);
+                    if !attackable[index]
+                        && in_range(unit, from, target)
+                        && self.line_of_sight(from, target)
+                    {
+                        attackable[index] = true;
+                    }
+                }
+            }
+        }
+        attackable
+    }
+}
@@ -0,0 +1,157 @@
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::juno::shadowcast;
+
+use super::{navigation, Sector, Team, Unit};
+
+/// How much a team knows about a tile
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Visibility {
+    /// Never been seen, so the terrain isn't known
+    #[default]
+    Unseen,
+    /// Seen before, but not currently in sight of any unit. Units here are hidden.
+    Explored,
+    /// Currently in sight of at least one unit
+    Visible,
+}
+
+/// Visibility of every tile in a sector for one team, indexed the same way as the sector
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct VisibilityMap {
+    tiles: Vec<Visibility>,
+}
+
+impl VisibilityMap {
+    pub fn new(size: Vector2<u32>) -> Self {
+        Self {
+            tiles: vec![Visibility::Unseen; (size.x * size.y) as usize],
+        }
+    }
+
+    pub fn tiles(&self) -> &[Visibility] {
+        &self.tiles
+    }
+}
+
+impl Sector {
+    pub fn visibility(&self, team: Team, pos: Vector2<u32>) -> Visibility {
+        if !self.tiles.contains(pos) {
+            return Visibility::Unseen;
+        }
+        self.visibility
+            .get(&team)
+            .map_or(Visibility::Unseen, |map| map.tiles[self.tiles.index(pos)])
+    }
+
+    pub fn can_see(&self, team: Team, pos: Vector2<u32>) -> bool {
+        self.visibility(team, pos) == Visibility::Visible
+    }
+
+    /// How many tiles `team` has never seen
+    pub fn unseen_tiles(&self, team: Team) -> usize {
+        self.visibility
+            .get(&team)
+            .map_or(self.tiles.tiles().len(), |map| {
+                map.tiles
+                    .iter()
+                    .filter(|v| **v == Visibility::Unseen)
+                    .count()
+            })
+    }
+
+    /// Every unit that `team` knows about: its own units, and any others in sight. Large units
+    /// are in sight if any of the tiles they cover is.
+    pub fn visible_units(&self, team: Team) -> impl Iterator<Item = &Unit> {
+        self.units
+            .values()
+            .filter(move |u| self.knows_about(team, u))
+    }
+
+    fn knows_about(&self, team: Team, unit: &Unit) -> bool {
+        unit.team() == team || unit.tiles().any(|pos| self.can_see(team, pos))
+    }
+
+    /// Recalculate what every team can currently see, from its units and structures. Anything
+    /// that was visible but is now out of sight becomes explored.
+    pub fn refresh_visibility(&mut self) {
+        // Which enemies each team knows about, and so can navigate around, may change
+        self.units_version = navigation::next_version();
+        let mut visibility = std::mem::take(&mut self.visibility);
+        let teams = self.units.values().map(|u| u.team());
+        for team in teams.chain(self.structures.iter().filter_map(|s| s.owner)) {
+            visibility
+                .entry(team)
+                .or_insert_with(|| VisibilityMap::new(self.size()));
+        }
+        for (team, map) in visibility.iter_mut() {
+            map.tiles
+                .iter_mut()
+                .filter(|v| **v == Visibility::Visible)
+                .for_each(|v| *v = Visibility::Explored);
+            // Large units see from every tile they cover
+            let eyes = self
+                .units
+                .values()
+                .filter(|u| u.team() == *team)
+                .flat_map(|u| u.tiles().map(move |pos| (pos, u.stats().sight)));
+            for (eye, sight) in eyes {
+                let elevation = self.elevation(eye);
+                shadowcast::field_of_view(
+                    eye,
+                    sight,
+                    self.size(),
+                    |pos| self.blocks_sight(elevation, pos),
+                    |pos| map.tiles[self.tiles.index(pos)] = Visibility::Visible,
+                );
+            }
+            let lookouts = self
+                .structures
+                .iter()
+                .filter(|s| s.owner == Some(*team) && s.kind.benefits().sight > 0);
+            for structure in lookouts {
+                let elevation = self.elevation(structure.pos);
+                shadowcast::field_of_view(
+                    structure.pos,
+                    structure.kind.benefits().sight,
+                    self.size(),
+                    |pos| self.blocks_sight(elevation, pos),
+                    |pos| map.tiles[self.tiles.index(pos)] = Visibility::Visible,
+                );
+            }
+        }
+        self.visibility = visibility;
+    }
+
+    /// Let `team` know the terrain of every tile, without seeing any more units than it already
+    /// can
+    pub fn reveal_map(&mut self, team: Team) {
+        let size = self.size();
+        let map = self
+            .visibility
+            .entry(team)
+            .or_insert_with(|| VisibilityMap::new(size));
+        map.tiles
+            .iter_mut()
+            .filter(|v| **v == Visibility::Unseen)
+            .for_each(|v| *v = Visibility::Explored);
+    }
+
+    /// Whether `pos` hides whatever is behind it from a viewer standing at `elevation`
+    pub fn blocks_sight(&self, elevation: u32, pos: Vector2<u32>) -> bool {
+        self.tile(pos).is_none_or(|t| {
+            t.contents().terrain().blocks_sight() || t.contents().elevation() > elevation
+        })
+    }
+
+    fn elevation(&self, pos: Vector2<u32>) -> u32 {
+        self.tile(pos).map_or(0, |t| t.contents().elevation())
+    }
+
+    /// The unit on `pos`, as far as `team` knows. Enemies that are out of sight are left out, so
+    /// navigation plans around only what the team can actually see.
+    pub(super) fn known_unit_at(&self, team: Team, pos: Vector2<u32>) -> Option<&Unit> {
+        self.unit_at_tile(pos).filter(|u| self.knows_about(team, u))
+    }
+}
@@ -1,6 +1,6 @@
 use cgmath::Vector2;
 use log::info;
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 
 use crate::juno::{
     directions,
@@ -9,7 +9,11 @@ use crate::juno::{
 
 use super::structs::{Entropy, GenTile, GenerationStage, StaticTileInfo, Subsector};
 
-pub fn generate_primary_sectors(meta_grid: &mut Grid<Subsector>, static_tiles: &[StaticTileInfo]) {
+pub fn generate_primary_sectors(
+    meta_grid: &mut Grid<Subsector>,
+    static_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
+) {
     let primary_sectors = meta_grid.tiles_mut().filter(|t| {
         t.contents()
             .generation_stage()
@@ -20,18 +24,19 @@ pub fn generate_primary_sectors(meta_grid: &mut Grid<Subsector>, static_tiles: &
         // HACK: do proper stuff for tertiary sectors
         let subsector = subsector.contents_mut();
         subsector.grid_mut().fill(GenTile::empty());
-        let initial_tile = static_tiles.choose(&mut rand::thread_rng()).unwrap();
+        let initial_tile = static_tiles.choose(rng).unwrap();
         let grid_item = GridItem::new(Vector2::new(0, 0), GenTile::new(initial_tile.clone()));
         if let Some(t) = subsector.grid_mut().tile_mut(Vector2::new(0, 0)) {
             *t = grid_item
         }
-        generate_subsector(subsector, static_tiles)
+        generate_subsector(subsector, static_tiles, rng)
     }
 }
 
 pub fn generate_secondary_sectors(
     meta_grid: &mut Grid<Subsector>,
     static_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
 ) {
     let secondary_sectors = meta_grid.tiles_mut().filter(|t| {
         t.contents().generation_stage() == &GenerationStage::SecondaryVertical
@@ -42,17 +47,21 @@ pub fn generate_secondary_sectors(
         // HACK: do proper stuff for tertiary sectors
         let subsector = subsector.contents_mut();
         subsector.grid_mut().fill(GenTile::empty());
-        let initial_tile = static_tiles.choose(&mut rand::thread_rng()).unwrap();
+        let initial_tile = static_tiles.choose(rng).unwrap();
         let grid_item = GridItem::new(Vector2::new(0, 0), GenTile::new(initial_tile.clone()));
         if let Some(t) = subsector.grid_mut().tile_mut(Vector2::new(0, 0)) {
             *t = grid_item;
         }
         calculate_entropy(subsector.grid_mut(), Vector2::new(0, 0), static_tiles);
-        generate_subsector(subsector, static_tiles)
+        generate_subsector(subsector, static_tiles, rng)
     }
 }
 
-pub fn generate_tertiary_sectors(meta_grid: &mut Grid<Subsector>, static_tiles: &[StaticTileInfo]) {
+pub fn generate_tertiary_sectors(
+    meta_grid: &mut Grid<Subsector>,
+    static_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
+) {
     let tertiary_sectors = meta_grid.tiles_mut().filter(|t| {
         t.contents()
             .generation_stage()
@@ -63,24 +72,28 @@ pub fn generate_tertiary_sectors(meta_grid: &mut Grid<Subsector>, static_tiles:
         // HACK: do proper stuff for tertiary sectors
         let subsector = subsector.contents_mut();
         subsector.grid_mut().fill(GenTile::empty());
-        let initial_tile = static_tiles.choose(&mut rand::thread_rng()).unwrap();
+        let initial_tile = static_tiles.choose(rng).unwrap();
         let grid_item = GridItem::new(Vector2::new(0, 0), GenTile::new(initial_tile.clone()));
         if let Some(t) = subsector.grid_mut().tile_mut(Vector2::new(0, 0)) {
             *t = grid_item
         }
-        generate_subsector(subsector, static_tiles)
+        generate_subsector(subsector, static_tiles, rng)
     }
 }
 
-pub fn generate_subsector(subsector: &mut Subsector, static_tiles: &[StaticTileInfo]) {
+pub fn generate_subsector(
+    subsector: &mut Subsector,
+    static_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
+) {
     while count_empty_tiles(&subsector) > 0 {
         if let Some(sel_tile) = min_entropy(&subsector.grid().clone()) {
-            select_tile(subsector.grid_mut(), sel_tile, static_tiles);
+            select_tile(subsector.grid_mut(), sel_tile, static_tiles, rng);
             calculate_entropy(subsector.grid_mut(), sel_tile, static_tiles);
         }
         // We need to pick a new untouched tile and set it's entropy
         else {
-            calculate_entropy_for_empty_tile(subsector.grid_mut(), static_tiles);
+            calculate_entropy_for_empty_tile(subsector.grid_mut(), static_tiles, rng);
         }
     }
 }
@@ -96,6 +109,7 @@ fn count_empty_tiles(subsector: &Subsector) -> u32 {
 fn calculate_entropy_for_empty_tile(
     subsector: &mut Grid<GenTile>,
     static_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
 ) {
     let empty_tile_opt = subsector
         .tiles()
@@ -103,12 +117,17 @@ fn calculate_entropy_for_empty_tile(
         .nth(0)
         .cloned();
     if let Some(empty_tile) = empty_tile_opt {
-        select_tile(subsector, empty_tile.pos(), static_tiles);
+        select_tile(subsector, empty_tile.pos(), static_tiles, rng);
         calculate_entropy(subsector, empty_tile.pos(), static_tiles);
     }
 }
 
-pub fn select_tile(grid: &mut Grid<GenTile>, pos: Vector2<u32>, static_tiles: &[StaticTileInfo]) {
+pub fn select_tile(
+    grid: &mut Grid<GenTile>,
+    pos: Vector2<u32>,
+    static_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
+) {
     // Find possible adjacent tiles
     // 1. Filter static tiles with regard to edges
     // 2. `choose_tile()` from the filtered list
@@ -118,14 +137,18 @@ pub fn select_tile(grid: &mut Grid<GenTile>, pos: Vector2<u32>, static_tiles: &[
         .cloned()
         .collect::<Vec<_>>();
     if let Some(tile) = grid.tile_mut(pos) {
-        let chosen = choose_tile(pos, possible_tiles.as_slice());
+        let chosen = choose_tile(pos, possible_tiles.as_slice(), rng);
         tile.contents_mut().set_static_tile(chosen);
         tile.contents_mut().remove_entropy();
     }
 }
 
-fn choose_tile(pos: Vector2<u32>, possible_tiles: &[StaticTileInfo]) -> StaticTileInfo {
-    match possible_tiles.choose(&mut rand::thread_rng()) {
+fn choose_tile(
+    pos: Vector2<u32>,
+    possible_tiles: &[StaticTileInfo],
+    rng: &mut impl Rng,
+) -> StaticTileInfo {
+    match possible_tiles.choose(rng) {
         Some(tile) => tile.clone(),
         // Default red error tile
         None => StaticTileInfo::new(
@@ -1,5 +1,7 @@
 use cgmath::Vector2;
 use log::info;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
 use crate::sector::Sector;
 
@@ -22,29 +24,33 @@ const LONG_RATIO: u32 = 4; // Ratio between base size and "long" size
 const SHORT_LENGTH: u32 = BASE_SIZE;
 const LONG_LENGTH: u32 = BASE_SIZE * LONG_RATIO;
 
-pub fn generate_terrain(size: Vector2<u32>, name: String) -> Sector {
+/// Generate a sector of terrain. The same size and seed always give the same terrain.
+pub fn generate_terrain(size: Vector2<u32>, name: String, seed: u64) -> Sector {
     // For now let's put in some pseudocode!
     // 1. Find the primary sectors
     let static_tiles_vec = load_tilemap_json();
     let static_tiles = static_tiles_vec.as_slice();
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
     let mut meta_grid = subsectors(size);
     info!("Created meta grid");
     // 2. Fill each primary subsector randomly using wave function collapse —  each subsector on a
     //    seperate thread
-    generate_primary_sectors(&mut meta_grid, static_tiles);
+    generate_primary_sectors(&mut meta_grid, static_tiles, &mut rng);
     info!("Generated primary sectors");
     // 3. Find secondary sectors, including the borders from the the primary sectors *but not* any
     //    tiles from tertiary sectors
     // 4. Fill each secondary sector randomly using wave function collapse —  each subsector on a
     //    seperate thread
-    // generate_secondary_sectors(&mut meta_grid, static_tiles);
+    // generate_secondary_sectors(&mut meta_grid, static_tiles, &mut rng);
     // 5. ...(tertiary sectors)
-    // generate_tertiary_sectors(&mut meta_grid, static_tiles);
+    // generate_tertiary_sectors(&mut meta_grid, static_tiles, &mut rng);
     info!("Generated all sectors");
     // 6. Combine all of the disperate subsectors, resolving overlapping tiles and prioritising
     //    later sectors for overlaps (figure out which subsector should "own" the tile, I.E. the
     //    latest subsector which will contain this tile)
-    neo_stitch_subsectors(meta_grid, name, size)
+    let mut sector = neo_stitch_subsectors(meta_grid, name, size);
+    sector.set_terrain_seed(Some(seed));
+    sector
 }
 
 // HACK: This is temporary, until I write a proper asset loading system
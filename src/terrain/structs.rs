@@ -1,7 +1,10 @@
 use cgmath::Vector2;
 use serde::{Deserialize, Serialize};
 
-use crate::juno::grid::{Grid, GridItem};
+use crate::{
+    juno::grid::{Grid, GridItem},
+    sector::Terrain,
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StaticTileInfo {
@@ -11,6 +14,11 @@ pub struct StaticTileInfo {
     left: String,
     up: String,
     right: String,
+    /// Worked out from the edges if it isn't given, see `terrain`
+    #[serde(default)]
+    terrain: Option<Terrain>,
+    #[serde(default)]
+    elevation: u32,
 }
 
 impl StaticTileInfo {
@@ -22,6 +30,8 @@ impl StaticTileInfo {
             left,
             up,
             right,
+            terrain: None,
+            elevation: 0,
         }
     }
 
@@ -44,6 +54,26 @@ impl StaticTileInfo {
     pub fn up(&self) -> &String {
         &self.up
     }
+
+    /// The terrain the tileset gives this tile. Otherwise, tiles made up entirely of water can't
+    /// be walked on, and everything else is treated as grass.
+    pub fn terrain(&self) -> Terrain {
+        if let Some(terrain) = self.terrain {
+            return terrain;
+        }
+        match [&self.down, &self.left, &self.up, &self.right]
+            .iter()
+            .all(|edge| edge.as_str() == "water")
+        {
+            true => Terrain::Water,
+            false => Terrain::Grass,
+        }
+    }
+
+    /// How far the tile is raised above the ground, like the top of a cliff
+    pub fn elevation(&self) -> u32 {
+        self.elevation
+    }
 }
 
 // Tile for multithreading generation
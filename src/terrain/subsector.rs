@@ -6,7 +6,7 @@ use crate::{
         directions::{self, i32_u32_cast},
         grid::{Grid, GridItem},
     },
-    sector::{Sector, Tile},
+    sector::{Sector, Terrain, Tile},
 };
 
 use super::{
@@ -146,7 +146,10 @@ pub fn stitch_subsectors(meta_grid: Grid<Subsector>, name: String, size: Vector2
     for y in 0..sector_grid.height() {
         for x in 0..sector_grid.width() {
             let (subsector, pos_in_subsector) = tile_position(Vector2::new(x, y));
-            let error_tile = GridItem::new(Vector2::new(x, y), Tile::new(Vector2::new(21, 5), 1.));
+            let error_tile = GridItem::new(
+                Vector2::new(x, y),
+                Tile::new(Vector2::new(21, 5), Terrain::Grass),
+            );
             sector_grid.push(
                 meta_grid
                     .tile(subsector)
@@ -166,7 +169,7 @@ pub fn stitch_subsectors(meta_grid: Grid<Subsector>, name: String, size: Vector2
                                                 )
                                             },
                                         ),
-                                        1.,
+                                        Terrain::Grass,
                                     ),
                                 )
                             },
@@ -184,28 +187,30 @@ pub fn neo_stitch_subsectors(
     size: Vector2<u32>,
 ) -> Sector {
     let mut sector_grid = Grid::new(size);
-    sector_grid.fill(Tile::new(Vector2::new(21, 4), 1.));
+    sector_grid.fill(Tile::new(Vector2::new(21, 4), Terrain::Grass));
     for subsector in meta_grid.tiles() {
         for tile in subsector.contents().grid().tiles() {
             let pos = subsector_tile_position(tile.pos(), subsector.pos());
             if let Some(prev_tile) = sector_grid.tile_mut(pos) {
-                prev_tile.contents_mut().set_atlas_position(
-                    i32_u32_cast(
-                        tile.contents()
-                            .static_tile()
-                            .clone()
-                            .unwrap_or(StaticTileInfo::new(
-                                22,
-                                5,
-                                "grass".to_string(),
-                                "grass".to_string(),
-                                "grass".to_string(),
-                                "grass".to_string(),
-                            ))
-                            .pos(),
-                    )
-                    .unwrap(),
-                )
+                let static_tile =
+                    tile.contents()
+                        .static_tile()
+                        .clone()
+                        .unwrap_or(StaticTileInfo::new(
+                            22,
+                            5,
+                            "grass".to_string(),
+                            "grass".to_string(),
+                            "grass".to_string(),
+                            "grass".to_string(),
+                        ));
+                prev_tile
+                    .contents_mut()
+                    .set_atlas_position(i32_u32_cast(static_tile.pos()).unwrap());
+                prev_tile.contents_mut().set_terrain(static_tile.terrain());
+                prev_tile
+                    .contents_mut()
+                    .set_elevation(static_tile.elevation());
             }
         }
     }
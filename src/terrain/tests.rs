@@ -2,11 +2,11 @@ use cgmath::Vector2;
 use log::info;
 use rand::seq::SliceRandom;
 
-use crate::juno::grid::Grid;
+use crate::{juno::grid::Grid, sector::Terrain};
 
 use super::{
     generate::{generate_subsector, select_tile},
-    load_tilemap_json,
+    generate_terrain, load_tilemap_json,
     structs::{GenTile, GenerationStage, Subsector},
 };
 
@@ -15,7 +15,7 @@ pub fn test_subsector_generation() {
     let tiles = load_tilemap_json();
     let mut subsector = Subsector::new(GenerationStage::Primary, Grid::new(Vector2::new(8, 8)));
     subsector.grid_mut().fill(GenTile::empty());
-    generate_subsector(&mut subsector, tiles.as_slice());
+    generate_subsector(&mut subsector, tiles.as_slice(), &mut rand::thread_rng());
     let empty_count = subsector
         .grid()
         .tiles()
@@ -47,7 +47,7 @@ pub fn test_tile_allowed() {
         .contents_mut()
         .set_static_tile(bottom_tile.clone());
     let down = bottom_tile.up();
-    select_tile(&mut grid, Vector2::new(1, 1), tiles.as_slice());
+    select_tile(&mut grid, Vector2::new(1, 1), tiles.as_slice(), &mut rand);
     let chosen_static = grid
         .tile(Vector2::new(1, 1))
         .unwrap()
@@ -73,7 +73,7 @@ pub fn test_tile_allowed() {
         .contents_mut()
         .set_static_tile(right_tile.clone());
     let right= right_tile.left();
-    select_tile(&mut grid, Vector2::new(1, 1), tiles.as_slice());
+    select_tile(&mut grid, Vector2::new(1, 1), tiles.as_slice(), &mut rand);
     let chosen_static = grid
         .tile(Vector2::new(1, 1))
         .unwrap()
@@ -83,5 +83,23 @@ pub fn test_tile_allowed() {
         .unwrap();
     assert_eq!(chosen_static.left(), left);
     assert_eq!(chosen_static.right(), right)
+}
 
+#[test]
+pub fn test_generation_is_seeded() {
+    let generate = |seed| {
+        let sector = generate_terrain(Vector2::new(20, 20), "Seeded".to_string(), seed);
+        serde_json::to_string(&sector).unwrap()
+    };
+    assert_eq!(generate(1), generate(1));
+    assert_ne!(generate(1), generate(2));
+}
+
+#[test]
+pub fn test_generation_varies_terrain() {
+    let sector = generate_terrain(Vector2::new(30, 30), "Varied".to_string(), 0);
+    let has = |terrain| sector.tiles().any(|t| t.contents().terrain() == terrain);
+    assert!(has(Terrain::Forest));
+    assert!(has(Terrain::Mountain));
+    assert!(sector.tiles().any(|t| t.contents().elevation() > 0));
 }
@@ -10,7 +10,7 @@ use crate::{
         directions,
         grid::{Grid, GridItem},
     },
-    sector::{Sector, Tile},
+    sector::{Sector, Terrain, Tile},
 };
 
 const MAX_CHUNK_LENGTH: u32 = 8;
@@ -92,7 +92,7 @@ fn divide_to_subsectors<'a>(size: Vector2<i32>) -> Grid<MTGenSubSector<'a>> {
 
 fn test_meta_sec(meta_grid: Grid<MTGenSubSector>, name: String, size: Vector2<u32>) -> Sector {
     let mut sector_grid = Grid::new(size.clone());
-    sector_grid.fill(Tile::new(Vector2::new(0, 0), 1.));
+    sector_grid.fill(Tile::new(Vector2::new(0, 0), Terrain::Grass));
     for subsector in meta_grid.tiles() {
         let sub_x = (0..subsector.pos().x).fold(0, |acc, t| {
             acc + match t % 2 == 0 {
@@ -112,7 +112,7 @@ fn test_meta_sec(meta_grid: Grid<MTGenSubSector>, name: String, size: Vector2<u3
             {
                 *old_tile = GridItem::new(
                     Vector2::new(sub_x + tile.pos().x, sub_y + tile.pos().y),
-                    Tile::new(tile.contents().static_tile.pos(), 1.),
+                    Tile::new(tile.contents().static_tile.pos(), Terrain::Grass),
                 );
             }
         }
@@ -154,7 +154,7 @@ fn meta_grid_to_sector(
                     .tiles()
                     .tile(Vector2::new(sub_x, sub_y))
                 {
-                    let sector_tile = Tile::new(tile.contents().static_tile.pos(), 1.);
+                    let sector_tile = Tile::new(tile.contents().static_tile.pos(), Terrain::Grass);
                     sector_grid.push(GridItem::new(Vector2::new(x, y), sector_tile));
                 } else {
                 }
@@ -214,7 +214,7 @@ fn generation_grid_to_sector(name: String, generation_grid: Grid<TerrainGenerati
         .tiles()
         .filter_map(|item| {
             item.contents().static_tile().as_ref().map(|static_tile| {
-                GridItem::new(item.pos().clone(), Tile::new(static_tile.pos().clone(), 1.))
+                GridItem::new(item.pos().clone(), Tile::new(static_tile.pos().clone(), Terrain::Grass))
             })
         })
         .for_each(|item| tiles.push(item));
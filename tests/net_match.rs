@@ -0,0 +1,71 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_project_calamity");
+
+/// Start the game without a window, recording its replay to a file of its own
+fn headless(args: &[&str], record: &PathBuf) -> Child {
+    Command::new(BINARY)
+        .arg("--headless")
+        .args(args)
+        .arg("--record")
+        .arg(record)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// The turn and checksum a headless network match stopped with, from its log
+fn stopped_at(log: &str) -> (u32, String) {
+    let line = log
+        .lines()
+        .find_map(|l| l.split_once("Stopped on turn ").map(|(_, rest)| rest))
+        .unwrap_or_else(|| panic!("Match never stopped:\n{log}"));
+    let (turn, checksum) = line.split_once(" with checksum ").unwrap();
+    (turn.parse().unwrap(), checksum.trim().to_string())
+}
+
+#[test]
+pub fn test_headless_processes_stay_in_sync() {
+    // Let the system pick a free port for the host
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let dir = std::env::temp_dir();
+    let replays = ["host", "join"]
+        .map(|side| dir.join(format!("net_match_{}_{side}.json", std::process::id())));
+
+    let mut host = headless(&["--host", &address], &replays[0]);
+    let mut host_out = BufReader::new(host.stdout.take().unwrap());
+    // Only join once the host is listening
+    let mut host_log = String::new();
+    while !host_log.contains("Waiting for another player") {
+        assert!(
+            host_out.read_line(&mut host_log).unwrap() > 0,
+            "Host stopped before it was ready:\n{host_log}"
+        );
+    }
+    let mut join = headless(&["--join", &address], &replays[1]);
+    let mut join_log = String::new();
+    join.stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut join_log)
+        .unwrap();
+    host_out.read_to_string(&mut host_log).unwrap();
+    assert!(join.wait().unwrap().success(), "{join_log}");
+    assert!(host.wait().unwrap().success(), "{host_log}");
+    for replay in &replays {
+        std::fs::remove_file(replay).unwrap();
+    }
+
+    let (turn, checksum) = stopped_at(&host_log);
+    assert_eq!(stopped_at(&join_log), (turn, checksum));
+    assert!(turn > 1);
+}